@@ -8,21 +8,20 @@ use std::rc::Rc;
 use stammer::elements::builder::ElementBuilder;
 use stammer::elements::{Element, SizingStrategy};
 use stammer::Panel;
-use zaailing::arena::NodeArena;
-use zaailing::node::{Node, NodeKind};
+use zaailing::{Node, NodeArena, NodeId, NodeKind};
 
 fn dom_node_as_stammer_element(
     font: Rc<stammer::Font>,
     node: &Node,
-    arena: &mut NodeArena,
+    arena: &NodeArena,
 ) -> Element<Data> {
     let mut children = vec![];
     for child in node.children().iter() {
-        let child = arena.get_node(*child).clone();
+        let child = arena.get_node(*child);
 
-        let element = match child.kind {
+        let element = match &child.kind {
             NodeKind::Text { data } => {
-                if data.trim().is_empty() {
+                if child.is_whitespace_only_text() {
                     continue;
                 }
 
@@ -30,7 +29,10 @@ fn dom_node_as_stammer_element(
                     .build()
                     .with_maxwidth(400)
             }
-            _ => dom_node_as_stammer_element(font.clone(), &child, arena),
+            // A wbr element is only a line break opportunity, so it has nothing to
+            // render.
+            _ if child.is_element_with_tag_name("wbr") => continue,
+            _ => dom_node_as_stammer_element(font.clone(), child, arena),
         };
         children.push(element);
     }
@@ -45,20 +47,20 @@ struct Data {
     height: u32,
 }
 
-fn get_document(arena: &mut NodeArena) -> Node {
+fn get_document(arena: &mut NodeArena) -> NodeId {
     let mut html = String::new();
     std::io::stdin().read_to_string(&mut html).unwrap();
 
     let document = zaailing::Dom::parse(html.as_str(), arena);
 
-    document.dump(&arena);
+    arena.get_node(document).dump(arena);
 
     document
 }
 
-fn get_body(arena: &mut NodeArena, document: &Node) -> Node {
+fn get_body(arena: &mut NodeArena, document: NodeId) -> Node {
     // FIXME: This is VERY ad-hoc.
-    let html = document.children()[1];
+    let html = arena.get_node(document).children()[1];
     let body = arena.get_node(html).children()[2];
     let body = arena.get_node(body).clone();
     body
@@ -76,9 +78,9 @@ struct Model {
 }
 
 fn model(app: &App) -> Model {
-    let mut arena = zaailing::arena::NodeArena::new();
+    let mut arena = NodeArena::new();
     let document = get_document(&mut arena);
-    let body = get_body(&mut arena, &document);
+    let body = get_body(&mut arena, document);
 
     let font_path = "/etc/tid/fonts/times15.uf2".to_string();
     let font = match stammer::Font::load_from_file(&font_path) {
@@ -89,7 +91,7 @@ fn model(app: &App) -> Model {
         }
     };
 
-    let mut document_element = dom_node_as_stammer_element(Rc::new(font), &body, &mut arena);
+    let mut document_element = dom_node_as_stammer_element(Rc::new(font), &body, &arena);
     document_element.size.maxwidth = Some(512);
     document_element.size.maxheight = Some(512);
 
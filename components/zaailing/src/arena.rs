@@ -1,20 +1,115 @@
-use crate::node::Node;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use crate::encoding;
+use crate::node::{Node, NodeKind};
+use crate::parser::Namespace;
+use crate::traversal::Visitor;
+use crate::Dom;
 
 pub type NodeId = usize;
 
-#[derive(Debug, Clone)]
+/// An error thrown by one of the DOM algorithms, named after the
+/// corresponding `DOMException`.
+///
+/// https://webidl.spec.whatwg.org/#idl-DOMException-error-names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomException {
+    /// The operation would yield an incorrect node tree.
+    HierarchyRequest,
+    /// The object can not be found here.
+    NotFound,
+    /// The supplied node is incorrect or has an incorrect ancestor for this
+    /// operation.
+    InvalidNodeType,
+    /// The object can not be modified.
+    NoModificationAllowed,
+}
+
+impl std::fmt::Display for DomException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DomException::HierarchyRequest => {
+                write!(f, "The operation would yield an incorrect node tree")
+            }
+            DomException::NotFound => write!(f, "The object can not be found here"),
+            DomException::InvalidNodeType => {
+                write!(f, "The supplied node is incorrect for this operation")
+            }
+            DomException::NoModificationAllowed => write!(f, "The object can not be modified"),
+        }
+    }
+}
+
+/// Where [NodeArena::insert_adjacent_html] inserts the parsed nodes, relative
+/// to the reference element.
+///
+/// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-insertadjacenthtml
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjacentPosition {
+    /// Before the element itself.
+    BeforeBegin,
+    /// Before the first child of the element.
+    AfterBegin,
+    /// After the last child of the element.
+    BeforeEnd,
+    /// After the element itself.
+    AfterEnd,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct NodeArena {
     nodes: Vec<Node>,
+    user_data: UserData,
+}
+
+/// Values attached to nodes with [NodeArena::set_user_data], keyed by the
+/// node and the type of the value.
+#[derive(Default)]
+struct UserData(HashMap<(NodeId, TypeId), Box<dyn Any>>);
+
+impl Clone for UserData {
+    /// The values can't be cloned, so a clone starts out empty.
+    fn clone(&self) -> Self {
+        UserData::default()
+    }
+}
+
+impl std::fmt::Debug for UserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UserData")
+            .field("len", &self.0.len())
+            .finish()
+    }
 }
 
 impl NodeArena {
     pub fn new() -> Self {
-        Self { nodes: vec![] }
+        Self {
+            nodes: vec![],
+            user_data: UserData::default(),
+        }
     }
 
-    pub fn create_node(&mut self, node: Node) -> NodeId {
+    pub fn create_node(&mut self, mut node: Node) -> NodeId {
+        let node_id = self.nodes.len();
+        // A document is its own node document.
+        if node.is_document() {
+            node.document = Some(node_id);
+        }
         self.nodes.push(node);
-        self.nodes.len() - 1
+        node_id
+    }
+
+    /// Removes all nodes from the arena, keeping the allocated capacity so
+    /// the arena can be reused to parse another document without
+    /// reallocating.
+    ///
+    /// Every [NodeId] handed out before is invalidated, as ids start from
+    /// zero again for the nodes created afterwards.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.user_data.0.clear();
     }
 
     pub fn get_node(&self, node_id: NodeId) -> &Node {
@@ -25,14 +120,577 @@ impl NodeArena {
         self.nodes.get_mut(node_id).unwrap()
     }
 
-    pub fn get_node_id(&self, node: &Node) -> NodeId {
-        self.nodes.iter().position(|n| n == node).unwrap()
+    pub fn child_count(&self, node: NodeId) -> usize {
+        self.get_node(node).children().len()
+    }
+
+    pub fn nth_child(&self, node: NodeId, index: usize) -> Option<NodeId> {
+        self.get_node(node).children().get(index).copied()
+    }
+
+    /// Returns the root of `node`, which is its topmost inclusive ancestor.
+    /// For a node in a document this is the document itself; for a node in a
+    /// detached subtree it is the root of that subtree.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-tree-root
+    pub fn root(&self, node: NodeId) -> NodeId {
+        let mut root = node;
+        while let Some(parent) = self.get_node(root).parent() {
+            root = parent;
+        }
+        root
+    }
+
+    /// Returns the number of ancestors of `node`, so the root has depth 0
+    /// and the `html` element of a document has depth 1.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-tree-ancestor
+    pub fn depth(&self, node: NodeId) -> usize {
+        let mut depth = 0;
+        let mut current = node;
+        while let Some(parent) = self.get_node(current).parent() {
+            depth += 1;
+            current = parent;
+        }
+        depth
+    }
+
+    /// Compares two subtrees by their structure and contents, ignoring where
+    /// they are located in the tree.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-node-equals
+    pub fn nodes_structurally_equal(&self, a: NodeId, b: NodeId) -> bool {
+        let mut pairs = vec![(a, b)];
+
+        while let Some((a, b)) = pairs.pop() {
+            let (a, b) = (self.get_node(a), self.get_node(b));
+
+            // A and B implement the same interfaces, and the following are
+            // equal, switching on the interface A implements:
+            let kinds_equal = match (&a.kind, &b.kind) {
+                (NodeKind::Document { .. }, NodeKind::Document { .. }) => true,
+                // DocumentType: Its name, public ID, and system ID.
+                (
+                    NodeKind::DocumentType {
+                        name: a_name,
+                        public_id: a_public_id,
+                        system_id: a_system_id,
+                    },
+                    NodeKind::DocumentType {
+                        name: b_name,
+                        public_id: b_public_id,
+                        system_id: b_system_id,
+                    },
+                ) => a_name == b_name && a_public_id == b_public_id && a_system_id == b_system_id,
+                // Element: Its namespace, namespace prefix, local name, and its
+                // attribute list’s size.
+                (
+                    NodeKind::Element {
+                        namespace_uri: a_namespace_uri,
+                        prefix: a_prefix,
+                        local_name: a_local_name,
+                        attributes: a_attributes,
+                        ..
+                    },
+                    NodeKind::Element {
+                        namespace_uri: b_namespace_uri,
+                        prefix: b_prefix,
+                        local_name: b_local_name,
+                        attributes: b_attributes,
+                        ..
+                    },
+                ) => {
+                    a_namespace_uri == b_namespace_uri
+                        && a_prefix == b_prefix
+                        && a_local_name == b_local_name
+                        && a_attributes.len() == b_attributes.len()
+                        // If A is an element, each attribute in its attribute
+                        // list has an attribute that equals an attribute in B’s
+                        // attribute list.
+                        && a_attributes.iter().all(|a_attribute| {
+                            b_attributes.iter().any(|b_attribute| {
+                                a_attribute.namespace_uri == b_attribute.namespace_uri
+                                    && a_attribute.local_name == b_attribute.local_name
+                                    && a_attribute.value == b_attribute.value
+                            })
+                        })
+                }
+                // Text, Comment: Its data.
+                (NodeKind::Text { data: a_data }, NodeKind::Text { data: b_data })
+                | (NodeKind::Comment { data: a_data }, NodeKind::Comment { data: b_data }) => {
+                    a_data == b_data
+                }
+                _ => false,
+            };
+            if !kinds_equal {
+                return false;
+            }
+
+            // A and B have the same number of children.
+            if a.children().len() != b.children().len() {
+                return false;
+            }
+
+            // Each child of A equals the child of B at the identical index.
+            pairs.extend(
+                a.children()
+                    .iter()
+                    .copied()
+                    .zip(b.children().iter().copied()),
+            );
+        }
+
+        true
+    }
+}
+
+/// # Building
+///
+/// Convenience methods for building a tree by hand. Nodes created here
+/// belong to the first document in the arena, which is created if there is
+/// none yet.
+impl NodeArena {
+    /// Creates an element in the HTML namespace, like
+    /// `document.createElement(tag_name)`. The element is not inserted into
+    /// the tree.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-document-createelement
+    pub fn create_element(&mut self, tag_name: &str) -> NodeId {
+        let document = self.default_document();
+
+        // If this is an HTML document, then set localName to localName in ASCII
+        // lowercase.
+        let local_name = tag_name.to_ascii_lowercase();
+
+        self.create_node(Node::create_element(
+            document,
+            local_name,
+            Namespace::Html,
+            None,
+            None,
+            false,
+        ))
+    }
+
+    /// Creates a text node, like `document.createTextNode(data)`.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-document-createtextnode
+    pub fn create_text(&mut self, data: &str) -> NodeId {
+        let document = self.default_document();
+        self.create_node(Node::create_text(document, data.to_string()))
+    }
+
+    /// Creates a comment node, like `document.createComment(data)`.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-document-createcomment
+    pub fn create_comment(&mut self, data: &str) -> NodeId {
+        let document = self.default_document();
+        self.create_node(Node::create_comment(document, data.to_string()))
+    }
+
+    /// Appends `child` to the children of `parent` and returns `child`, like
+    /// `parent.appendChild(child)`. Fails if `child` can't be inserted into
+    /// `parent`, see [NodeArena::ensure_pre_insertion_validity].
+    ///
+    /// https://dom.spec.whatwg.org/#dom-node-appendchild
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) -> Result<NodeId, DomException> {
+        // The appendChild(node) method steps are to return the result of
+        // appending node to this.
+        self.insert_before(parent, child, None)
+    }
+
+    /// Inserts `node` into the children of `parent` before `child`, or at the
+    /// end if `child` is `None`, and returns `node`, like
+    /// `parent.insertBefore(node, child)`. Fails if `node` can't be inserted
+    /// there, see [NodeArena::ensure_pre_insertion_validity].
+    ///
+    /// https://dom.spec.whatwg.org/#dom-node-insertbefore
+    pub fn insert_before(
+        &mut self,
+        parent: NodeId,
+        node: NodeId,
+        child: Option<NodeId>,
+    ) -> Result<NodeId, DomException> {
+        // The insertBefore(node, child) method steps are to return the result
+        // of pre-inserting node into this before child.
+        self.ensure_pre_insertion_validity(node, parent, child)?;
+        Ok(self.pre_insert(node, parent, child))
+    }
+
+    /// Removes `node` from its parent, if it has one, and returns it, like
+    /// `node.remove()`. The subtree below `node` is left intact, so it can be
+    /// inserted somewhere else with [NodeArena::append_child] or
+    /// [NodeArena::insert_before].
+    ///
+    /// https://dom.spec.whatwg.org/#dom-childnode-remove
+    pub fn detach(&mut self, node: NodeId) -> NodeId {
+        // The remove() method steps are: If this's parent is null, then return.
+        // Remove this.
+        if self.get_node(node).parent().is_some() {
+            self.remove(node);
+        }
+        node
+    }
+
+    /// Wraps `node` in a new element in the HTML namespace with the tag name
+    /// `wrapper_tag`, which takes the place of `node` in the tree, and
+    /// returns the wrapper. If `node` has no parent, the wrapper has none
+    /// either.
+    ///
+    /// Fails without changing the tree if `node` can't be the child of an
+    /// element or the wrapper can't take its place, like when `node` is a
+    /// DOCTYPE or a document element.
+    pub fn wrap(&mut self, node: NodeId, wrapper_tag: &str) -> Result<NodeId, DomException> {
+        // NOTE: Nodes are never freed, so everything is checked before the
+        //       wrapper is created to not leave it behind when this fails.
+        if matches!(
+            self.get_node(node).kind,
+            NodeKind::Document { .. } | NodeKind::DocumentType { .. }
+        ) {
+            return Err(DomException::HierarchyRequest);
+        }
+        let parent = self.get_node(node).parent();
+        if let Some(parent) = parent {
+            if self.get_node(parent).is_document()
+                && !self.can_insert_element_into_document(parent, Some(node))
+            {
+                return Err(DomException::HierarchyRequest);
+            }
+        }
+
+        let wrapper = self.create_element(wrapper_tag);
+        if let Some(parent) = parent {
+            self.insert_before(parent, wrapper, Some(node))?;
+        }
+
+        self.append_child(wrapper, node)?;
+        Ok(wrapper)
+    }
+
+    /// Parses `html` as a fragment and inserts the resulting nodes at
+    /// `position` relative to `reference`, like
+    /// `reference.insertAdjacentHTML(position, html)`. Returns the inserted
+    /// nodes in tree order.
+    ///
+    /// Fails with [DomException::HierarchyRequest] if `reference` is not an
+    /// element, and with [DomException::NoModificationAllowed] when inserting
+    /// before or after an element without a parent, or with a parent that is
+    /// a document. The tree is left unchanged when it fails.
+    ///
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-insertadjacenthtml
+    pub fn insert_adjacent_html(
+        &mut self,
+        reference: NodeId,
+        position: AdjacentPosition,
+        html: &str,
+    ) -> Result<Vec<NodeId>, DomException> {
+        // NOTE: insertAdjacentHTML() is only defined on elements.
+        if !self.get_node(reference).is_element() {
+            return Err(DomException::HierarchyRequest);
+        }
+
+        // Let context be null.
+        // Use the first matching item from this list:
+        let context = match position {
+            // If position is an ASCII case-insensitive match for the string
+            // "beforebegin" or "afterend":
+            AdjacentPosition::BeforeBegin | AdjacentPosition::AfterEnd => {
+                // Set context to this's parent.
+                // If context is null or a Document, throw a
+                // "NoModificationAllowedError" DOMException.
+                match self.get_node(reference).parent() {
+                    Some(parent) if !self.get_node(parent).is_document() => parent,
+                    _ => return Err(DomException::NoModificationAllowed),
+                }
+            }
+            // If position is an ASCII case-insensitive match for the string
+            // "afterbegin" or "beforeend": Set context to this.
+            AdjacentPosition::AfterBegin | AdjacentPosition::BeforeEnd => reference,
+        };
+
+        // If context is not an Element or all of the following are true:
+        // - context's node document is an HTML document;
+        // - context's local name is "html"; and
+        // - context's namespace is the HTML namespace,
+        // then set context to the result of creating an element given this's
+        // node document, "body", and the HTML namespace.
+        // Let fragment be the result of invoking the fragment parsing
+        // algorithm steps with context and compliantString.
+        let context_node = self.get_node(context);
+        let nodes = if !context_node.is_element()
+            || (context_node.is_element_in_namespace(Namespace::Html)
+                && context_node.is_element_with_tag_name("html"))
+        {
+            // NOTE: Nodes are never freed from an arena, so the fragment is
+            //       parsed into a separate one to not leave the new body
+            //       element behind, and only the parsed nodes are copied over.
+            let (fragment_arena, fragment_nodes) = Dom::parse_fragment(html, "body");
+            let document = self.get_node(reference).node_document();
+            fragment_nodes
+                .into_iter()
+                .map(|node| self.copy_subtree_from(&fragment_arena, node, document))
+                .collect()
+        } else {
+            Dom::parse_fragment_with_context(html, self, context)
+        };
+
+        // Use the first matching item from this list:
+        let (parent, child) = match position {
+            // If position is an ASCII case-insensitive match for the string
+            // "beforebegin": Insert fragment into this's parent before this.
+            AdjacentPosition::BeforeBegin => (context, Some(reference)),
+            // If position is an ASCII case-insensitive match for the string
+            // "afterbegin": Insert fragment into this before its first child.
+            AdjacentPosition::AfterBegin => (
+                reference,
+                self.get_node(reference).children().first().copied(),
+            ),
+            // If position is an ASCII case-insensitive match for the string
+            // "beforeend": Append fragment to this.
+            AdjacentPosition::BeforeEnd => (reference, None),
+            // If position is an ASCII case-insensitive match for the string
+            // "afterend": Insert fragment into this's parent before this's
+            // next sibling.
+            AdjacentPosition::AfterEnd => (context, self.next_sibling(reference)),
+        };
+
+        // NOTE: All nodes are checked before any of them is inserted, so a
+        //       failure leaves the tree unchanged, like inserting a single
+        //       DocumentFragment would.
+        for node in nodes.iter() {
+            self.ensure_pre_insertion_validity(*node, parent, child)?;
+        }
+        for node in nodes.iter() {
+            self.pre_insert(*node, parent, child);
+        }
+        Ok(nodes)
+    }
+
+    /// Copies `node` and its descendants from `other` into this arena, with
+    /// `document` as their node document. Returns the copy of `node`, which
+    /// has no parent.
+    fn copy_subtree_from(&mut self, other: &NodeArena, node: NodeId, document: NodeId) -> NodeId {
+        let copy_node = |arena: &mut NodeArena, node: NodeId| {
+            arena.create_node(Node {
+                kind: other.get_node(node).kind.clone(),
+                document: Some(document),
+                children: Vec::new(),
+                parent: None,
+                previous_sibling: None,
+                next_sibling: None,
+            })
+        };
+
+        let root = copy_node(self, node);
+        let mut pending = vec![(node, root)];
+        while let Some((original, copy)) = pending.pop() {
+            for child in other.get_node(original).children() {
+                let child_copy = copy_node(self, *child);
+                self.append(child_copy, copy);
+                pending.push((*child, child_copy));
+            }
+        }
+        root
+    }
+
+    /// Changes the tag name of the element `node` to `new_tag`, keeping its
+    /// namespace, attributes and children. Like [NodeArena::create_element],
+    /// the name is lowercased for elements in the HTML namespace.
+    ///
+    /// Fails with [DomException::InvalidNodeType] if `node` is not an element.
+    pub fn rename_element(&mut self, node: NodeId, new_tag: &str) -> Result<(), DomException> {
+        let node = self.get_node_mut(node);
+        let is_html = node.is_element_in_namespace(Namespace::Html);
+        let NodeKind::Element {
+            prefix,
+            local_name,
+            tag_name,
+            ..
+        } = &mut node.kind
+        else {
+            return Err(DomException::InvalidNodeType);
+        };
+
+        *local_name = if is_html {
+            new_tag.to_ascii_lowercase()
+        } else {
+            new_tag.to_string()
+        };
+        *tag_name = match prefix {
+            Some(prefix) => format!("{prefix}:{local_name}"),
+            None => local_name.clone(),
+        };
+        Ok(())
+    }
+
+    fn default_document(&mut self) -> NodeId {
+        match self.nodes.iter().position(|node| node.is_document()) {
+            Some(document) => document,
+            None => self.create_node(Node::create_document()),
+        }
     }
 }
+
+/// # User Data
+///
+/// Tools built on top of the tree, like a layout engine, can attach their own
+/// values to nodes instead of keeping a map from [NodeId] to their data next
+/// to the arena. A node holds at most one value of each type.
+impl NodeArena {
+    /// Attaches `value` to `node`, replacing the value of the same type that
+    /// was attached before, if any.
+    ///
+    /// Values can be of any type, including ones like `Rc` that can't be
+    /// sent to another thread. Clones of the arena don't get the values that
+    /// were attached to the original.
+    pub fn set_user_data<T: Any>(&mut self, node: NodeId, value: T) {
+        self.user_data
+            .0
+            .insert((node, TypeId::of::<T>()), Box::new(value));
+    }
+
+    /// Returns the value of type `T` attached to `node`, if any.
+    pub fn user_data<T: Any>(&self, node: NodeId) -> Option<&T> {
+        self.user_data
+            .0
+            .get(&(node, TypeId::of::<T>()))
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Detaches the value of type `T` from `node`. Returns whether there was
+    /// one.
+    pub fn remove_user_data<T: Any>(&mut self, node: NodeId) -> bool {
+        self.user_data
+            .0
+            .remove(&(node, TypeId::of::<T>()))
+            .is_some()
+    }
+}
+
 /// # Mutation Algorithms
 ///
 /// https://dom.spec.whatwg.org/#mutation-algorithms
 impl NodeArena {
+    /// Checks whether `node` can be inserted into `parent` before `child`
+    /// without producing an invalid tree.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-node-ensure-pre-insertion-validity
+    pub fn ensure_pre_insertion_validity(
+        &self,
+        node: NodeId,
+        parent: NodeId,
+        child: Option<NodeId>,
+    ) -> Result<(), DomException> {
+        self.ensure_hierarchy_validity(node, parent, child)?;
+
+        // If parent is a document, and any of the statements below, switched
+        // on the interface node implements, are true, then throw a
+        // "HierarchyRequestError" DOMException.
+        if self.get_node(parent).is_document() {
+            let children = self.get_node(parent).children();
+            let child_index = child.and_then(|child| children.iter().position(|c| *c == child));
+            let is_doctype =
+                |node: &NodeId| matches!(self.get_node(*node).kind, NodeKind::DocumentType { .. });
+            let is_element = |node: &NodeId| self.get_node(*node).is_element();
+
+            let is_invalid = match self.get_node(node).kind {
+                // Element: parent has an element child, child is a doctype, or
+                // child is non-null and a doctype is following child.
+                NodeKind::Element { .. } => !self.can_insert_element_into_document(parent, child),
+                // DocumentType: parent has a doctype child, child is non-null
+                // and an element is preceding child, or child is null and
+                // parent has an element child.
+                NodeKind::DocumentType { .. } => {
+                    children.iter().any(is_doctype)
+                        || match child_index {
+                            Some(index) => children[..index].iter().any(is_element),
+                            None => children.iter().any(is_element),
+                        }
+                }
+                _ => false,
+            };
+
+            if is_invalid {
+                return Err(DomException::HierarchyRequest);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether an element can be inserted into `document` before `child`,
+    /// which is not the case if it has an element child, `child` is a
+    /// doctype, or a doctype is following `child`.
+    fn can_insert_element_into_document(&self, document: NodeId, child: Option<NodeId>) -> bool {
+        let children = self.get_node(document).children();
+        let is_doctype =
+            |node: &NodeId| matches!(self.get_node(*node).kind, NodeKind::DocumentType { .. });
+        let child_index = child.and_then(|child| children.iter().position(|c| *c == child));
+
+        !(children
+            .iter()
+            .any(|node| self.get_node(*node).is_element())
+            || child.as_ref().is_some_and(is_doctype)
+            || child_index.is_some_and(|index| children[index + 1..].iter().any(is_doctype)))
+    }
+
+    /// The checks that ensuring pre-insertion validity and replacing a child
+    /// have in common, which are all but the ones for the children of a
+    /// document `parent`.
+    fn ensure_hierarchy_validity(
+        &self,
+        node: NodeId,
+        parent: NodeId,
+        child: Option<NodeId>,
+    ) -> Result<(), DomException> {
+        // If parent is not a Document, DocumentFragment, or Element node, then
+        // throw a "HierarchyRequestError" DOMException.
+        if !self.get_node(parent).is_document() && !self.get_node(parent).is_element() {
+            return Err(DomException::HierarchyRequest);
+        }
+
+        // If node is a host-including inclusive ancestor of parent, then throw
+        // a "HierarchyRequestError" DOMException.
+        let mut ancestor = Some(parent);
+        while let Some(current) = ancestor {
+            if current == node {
+                return Err(DomException::HierarchyRequest);
+            }
+            ancestor = self.get_node(current).parent();
+        }
+
+        // If child is non-null and its parent is not parent, then throw a
+        // "NotFoundError" DOMException.
+        if let Some(child) = child {
+            if self.get_node(child).parent() != Some(parent) {
+                return Err(DomException::NotFound);
+            }
+        }
+
+        // If node is not a DocumentFragment, DocumentType, Element, or
+        // CharacterData node, then throw a "HierarchyRequestError"
+        // DOMException.
+        if self.get_node(node).is_document() {
+            return Err(DomException::HierarchyRequest);
+        }
+
+        // If either node is a Text node and parent is a document, or node is a
+        // doctype and parent is not a document, then throw a
+        // "HierarchyRequestError" DOMException.
+        let parent_is_document = self.get_node(parent).is_document();
+        match self.get_node(node).kind {
+            NodeKind::Text { .. } if parent_is_document => {
+                return Err(DomException::HierarchyRequest);
+            }
+            NodeKind::DocumentType { .. } if !parent_is_document => {
+                return Err(DomException::HierarchyRequest);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
     /// https://dom.spec.whatwg.org/#concept-node-pre-insert
     pub fn pre_insert(
         &mut self,
@@ -41,12 +699,17 @@ impl NodeArena {
         before_child: Option<NodeId>,
     ) -> NodeId {
         // TODO: Ensure pre-insertion validity of node into parent before child.
+        //       The parser only inserts valid nodes, so callers that need the
+        //       check call [NodeArena::ensure_pre_insertion_validity] first.
 
         // Let referenceChild be child.
-        let reference_child = before_child;
+        let mut reference_child = before_child;
 
-        // TODO: If referenceChild is node, then set referenceChild to node’s
-        // next sibling.
+        // If referenceChild is node, then set referenceChild to node’s next
+        // sibling.
+        if reference_child == Some(node) {
+            reference_child = self.next_sibling(node);
+        }
 
         // Insert node into parent before referenceChild.
         self.insert(node, into_parent, reference_child);
@@ -56,38 +719,18 @@ impl NodeArena {
     }
 
     pub fn previous_sibling(&self, node: NodeId) -> Option<NodeId> {
-        // FIXME: store previous sibling in node
-        if let Some(parent) = self.nodes[node].parent() {
-            let children = self.nodes[parent].children();
-            let index = children.iter().position(|child| *child == node);
-            if let Some(index) = index {
-                if index > 0 {
-                    return Some(children[index - 1]);
-                }
-            }
-        }
-        None
+        self.nodes[node].previous_sibling
     }
 
     pub fn next_sibling(&self, node: NodeId) -> Option<NodeId> {
-        // FIXME: store previous sibling in node
-        if let Some(parent) = self.nodes[node].parent() {
-            let children = self.nodes[parent].children();
-            let index = children.iter().position(|child| *child == node);
-            if let Some(index) = index {
-                if index < children.len() - 1 {
-                    return Some(children[index + 1]);
-                }
-            }
-        }
-        None
+        self.nodes[node].next_sibling
     }
 
     /// https://dom.spec.whatwg.org/#concept-node-insert
     pub fn insert(&mut self, node: NodeId, into_parent: NodeId, before_child: Option<NodeId>) {
         // TODO: Let nodes be node’s children, if node is a DocumentFragment node;
         // otherwise « node ».
-        let nodes = vec![node];
+        let nodes = [node];
 
         // Let count be nodes’s size.
         let count = nodes.len();
@@ -107,9 +750,9 @@ impl NodeArena {
         // For each node in nodes, in tree order:
         for node in nodes.iter() {
             // Adopt node into parent’s node document.
-            self.adopt(*node, self.get_node(into_parent).node_document(self));
+            self.adopt(*node, self.get_node(into_parent).node_document());
 
-            if let Some(before_child) = before_child {
+            let previous_sibling = if let Some(before_child) = before_child {
                 // Otherwise, insert node into parent’s children before child’s
                 // index.
                 let index = self
@@ -119,10 +762,16 @@ impl NodeArena {
                     .position(|n| *n == before_child)
                     .unwrap();
                 self.get_node_mut(into_parent).children.insert(index, *node);
+                self.previous_sibling(before_child)
             } else {
                 // If child is null, then append node to parent’s children.
+                let last_child = self.get_node(into_parent).children().last().copied();
                 self.get_node_mut(into_parent).children.push(*node);
-            }
+                last_child
+            };
+            self.get_node_mut(*node).parent = Some(into_parent);
+            self.link_siblings(previous_sibling, Some(*node));
+            self.link_siblings(Some(*node), before_child);
 
             // TODO: If parent is a shadow host whose shadow root’s slot
             // assignment is "named" and node is a slottable, then
@@ -144,29 +793,961 @@ impl NodeArena {
         // TODO: Run the children changed steps for parent.
     }
 
+    /// Replaces `old_child` with `new_child` in the children of the parent of
+    /// `old_child`, like `parent.replaceChild(new_child, old_child)`. Returns
+    /// `old_child`, which no longer has a parent afterwards.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-node-replace
+    pub fn replace_child(
+        &mut self,
+        new_child: NodeId,
+        old_child: NodeId,
+    ) -> Result<NodeId, DomException> {
+        let (node, child) = (new_child, old_child);
+
+        // NOTE: The parent is the parent of child, so if child has no parent,
+        //       it can't be found in parent.
+        let parent = self
+            .get_node(child)
+            .parent()
+            .ok_or(DomException::NotFound)?;
+
+        self.ensure_hierarchy_validity(node, parent, Some(child))?;
+
+        // If parent is a document, and any of the statements below, switched
+        // on the interface node implements, are true, then throw a
+        // "HierarchyRequestError" DOMException.
+        if self.get_node(parent).is_document() {
+            let children = self.get_node(parent).children();
+            let child_index = children.iter().position(|c| *c == child).unwrap();
+            let is_doctype =
+                |node: &NodeId| matches!(self.get_node(*node).kind, NodeKind::DocumentType { .. });
+
+            let is_invalid = match self.get_node(node).kind {
+                // Element: parent has an element child that is not child or a
+                // doctype is following child.
+                NodeKind::Element { .. } => {
+                    children
+                        .iter()
+                        .any(|c| *c != child && self.get_node(*c).is_element())
+                        || children[child_index + 1..].iter().any(is_doctype)
+                }
+                // DocumentType: parent has a doctype child that is not child,
+                // or an element is preceding child.
+                NodeKind::DocumentType { .. } => {
+                    children.iter().any(|c| *c != child && is_doctype(c))
+                        || children[..child_index]
+                            .iter()
+                            .any(|c| self.get_node(*c).is_element())
+                }
+                _ => false,
+            };
+
+            if is_invalid {
+                return Err(DomException::HierarchyRequest);
+            }
+        }
+
+        // Let referenceChild be child's next sibling.
+        let mut reference_child = self.next_sibling(child);
+
+        // If referenceChild is node, then set referenceChild to node's next
+        // sibling.
+        if reference_child == Some(node) {
+            reference_child = self.next_sibling(node);
+        }
+
+        // TODO: Let previousSibling be child's previous sibling.
+
+        // TODO: Let removedNodes be the empty set.
+
+        // If child's parent is non-null, then:
+        // TODO: Set removedNodes to « child ».
+        // Remove child with the suppress observers flag set.
+        self.remove(child);
+
+        // TODO: Let nodes be node's children if node is a DocumentFragment
+        // node; otherwise « node ».
+
+        // Insert node into parent before referenceChild with the suppress
+        // observers flag set.
+        self.insert(node, parent, reference_child);
+
+        // TODO: Queue a tree mutation record for parent with nodes,
+        // removedNodes, previousSibling, and referenceChild.
+
+        // Return child.
+        Ok(child)
+    }
+
     /// https://dom.spec.whatwg.org/#concept-node-append
     pub fn append(&mut self, node: NodeId, into_parent: NodeId) -> NodeId {
         // To append a node to a parent, pre-insert node into parent before null.
         self.pre_insert(node, into_parent, None)
     }
 
+    /// https://dom.spec.whatwg.org/#concept-node-remove
+    pub fn remove(&mut self, node: NodeId) {
+        // Let parent be node’s parent.
+        // Assert: parent is non-null.
+        let parent = self
+            .get_node(node)
+            .parent()
+            .expect("Node should have a parent when it is removed");
+
+        // TODO: Let index be node’s index.
+
+        // TODO: For each live range whose start node is an inclusive descendant
+        // of node, set its start to (parent, index).
+
+        // TODO: For each live range whose end node is an inclusive descendant of
+        // node, set its end to (parent, index).
+
+        // TODO: For each NodeIterator object iterator whose root’s node
+        // document is node’s node document, run the NodeIterator pre-removing
+        // steps given node and iterator.
+
+        // Let oldPreviousSibling be node’s previous sibling.
+        let old_previous_sibling = self.previous_sibling(node);
+
+        // Let oldNextSibling be node’s next sibling.
+        let old_next_sibling = self.next_sibling(node);
+
+        // Remove node from its parent’s children.
+        self.get_node_mut(parent)
+            .children
+            .retain(|child| *child != node);
+        self.get_node_mut(node).parent = None;
+        self.get_node_mut(node).previous_sibling = None;
+        self.get_node_mut(node).next_sibling = None;
+        self.link_siblings(old_previous_sibling, old_next_sibling);
+
+        // TODO: Run the remaining removal steps (slots, live ranges, mutation
+        // records and the children changed steps).
+    }
+
+    /// Makes `next` the next sibling of `previous` and vice versa. Either side
+    /// may be `None` at the edges of a parent's children.
+    fn link_siblings(&mut self, previous: Option<NodeId>, next: Option<NodeId>) {
+        if let Some(previous) = previous {
+            self.get_node_mut(previous).next_sibling = next;
+        }
+        if let Some(next) = next {
+            self.get_node_mut(next).previous_sibling = previous;
+        }
+    }
+
     /// https://dom.spec.whatwg.org/#concept-node-adopt
     pub fn adopt(&mut self, node: NodeId, document: NodeId) {
         // Let oldDocument be node’s node document.
-        let old_document = self.get_node(node).node_document(self);
+        let old_document = self.get_node(node).node_document();
 
-        // TODO: If node’s parent is non-null, then remove node.
+        // If node’s parent is non-null, then remove node.
         if self.get_node(node).parent().is_some() {
-            todo!();
+            self.remove(node);
         }
 
         // If document is not oldDocument, then:
         if document != old_document {
-            // TODO: This is not spec compliant.
-            let children = self.get_node(node).children().to_vec();
-            for child in children.iter() {
-                self.get_node_mut(*child).document = Some(document);
+            // For each inclusiveDescendant in node’s shadow-including inclusive
+            // descendants: Set inclusiveDescendant’s node document to document.
+            let inclusive_descendants: Vec<_> = std::iter::once(node)
+                .chain(self.descendants(node))
+                .collect();
+            for inclusive_descendant in inclusive_descendants {
+                self.get_node_mut(inclusive_descendant).document = Some(document);
+            }
+
+            // TODO: Adopt the attributes of elements and run the adopting steps.
+        }
+    }
+}
+
+/// # Document Metadata
+impl NodeArena {
+    /// Returns the document element of `document`, which is its first
+    /// element child.
+    ///
+    /// https://dom.spec.whatwg.org/#document-element
+    pub fn document_element(&self, document: NodeId) -> Option<NodeId> {
+        self.get_node(document)
+            .children()
+            .iter()
+            .copied()
+            .find(|child| self.get_node(*child).is_element())
+    }
+
+    /// Returns the head element of `document`, which is the first head
+    /// element child of the html element, if there is one.
+    ///
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-head
+    pub fn head(&self, document: NodeId) -> Option<NodeId> {
+        let html = self
+            .document_element(document)
+            .filter(|html| self.get_node(*html).is_element_with_tag_name("html"))?;
+
+        self.get_node(html)
+            .children()
+            .iter()
+            .copied()
+            .find(|child| self.get_node(*child).is_element_with_tag_name("head"))
+    }
+
+    /// Returns the encoding label declared by the first `<meta charset>` or
+    /// `<meta http-equiv="content-type">` element in the head of `document`.
+    ///
+    /// The label is returned as written, with surrounding whitespace removed.
+    /// It is not checked against the known encodings.
+    ///
+    /// https://html.spec.whatwg.org/multipage/semantics.html#character-encoding-declaration
+    pub fn meta_charset(&self, document: NodeId) -> Option<String> {
+        let head = self.head(document)?;
+
+        self.get_node(head).children().iter().find_map(|child| {
+            let child = self.get_node(*child);
+            if !child.is_element_with_tag_name("meta") {
+                return None;
+            }
+
+            let label = if let Some(charset) = child.get_attribute("charset") {
+                charset
+            } else if child
+                .get_attribute("http-equiv")
+                .is_some_and(|http_equiv| http_equiv.eq_ignore_ascii_case("content-type"))
+            {
+                let content = child.get_attribute("content")?;
+                let label = encoding::extract_label_from_content(content.as_bytes())?;
+                std::str::from_utf8(label).ok()?
+            } else {
+                return None;
+            };
+
+            Some(
+                label
+                    .trim_matches(|c: char| c.is_ascii_whitespace())
+                    .to_string(),
+            )
+        })
+    }
+}
+
+/// # Text Content
+impl NodeArena {
+    /// Returns the text content of `node`, like the `textContent` getter. For
+    /// elements and documents this is the data of all descendant text nodes
+    /// concatenated in tree order.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-node-textcontent
+    pub fn text_content(&self, node: NodeId) -> String {
+        self.collect_text(node, false)
+    }
+
+    /// Like [NodeArena::text_content], but skips text nodes that only contain
+    /// whitespace, such as the indentation between elements. Whitespace
+    /// inside other text nodes is kept as is.
+    pub fn text_content_trimmed(&self, node: NodeId) -> String {
+        self.collect_text(node, true)
+    }
+
+    /// Sets the text content of `node`, like the `textContent` setter. The
+    /// children of an element are replaced by a single text node containing
+    /// `text`, or removed if `text` is empty. For text and comment nodes the
+    /// data is replaced instead. Other nodes are left alone.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-node-textcontent
+    pub fn set_text_content(&mut self, node: NodeId, text: &str) {
+        if self.get_node(node).is_element() {
+            // String replace all with the given value within this.
+            self.string_replace_all(text, node);
+            return;
+        }
+
+        match &mut self.get_node_mut(node).kind {
+            // Replace data with node this, offset 0, count this’s length, and data the given value.
+            NodeKind::Text { data } | NodeKind::Comment { data } => *data = text.to_string(),
+            // Otherwise: Do nothing.
+            _ => {}
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#string-replace-all
+    fn string_replace_all(&mut self, string: &str, parent: NodeId) {
+        // Let node be null.
+        // If string is not the empty string, then set node to a new Text node whose data is string and node document is parent’s node document.
+        let node = (!string.is_empty()).then(|| {
+            let document = self.get_node(parent).node_document();
+            self.create_node(Node::create_text(document, string.to_string()))
+        });
+
+        // Replace all with node within parent.
+        let children = self.get_node(parent).children().to_vec();
+        for child in children {
+            self.remove(child);
+        }
+        if let Some(node) = node {
+            self.append(node, parent);
+        }
+    }
+
+    /// Returns the readable text of `node`, roughly like the `innerText`
+    /// getter without a layout engine: block level elements like `p`, `div`
+    /// and `li` are put on their own lines, `br` elements become line breaks
+    /// (while `wbr` elements, which are only break opportunities, add
+    /// nothing) and runs of whitespace collapse into a single space, except
+    /// inside `pre` and `textarea`. The contents of elements that are never rendered,
+    /// like `head`, `script` and `style`, are left out.
+    ///
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-innertext
+    pub fn rendered_text(&self, node: NodeId) -> String {
+        let mut collector = RenderedTextCollector::default();
+        self.visit(node, &mut collector);
+        collector.text
+    }
+
+    fn collect_text(&self, node: NodeId, skip_whitespace_only: bool) -> String {
+        if let NodeKind::Comment { data } = &self.get_node(node).kind {
+            return data.clone();
+        }
+
+        let mut collector = TextCollector {
+            skip_whitespace_only,
+            text: String::new(),
+        };
+        self.visit(node, &mut collector);
+        collector.text
+    }
+}
+
+struct TextCollector {
+    skip_whitespace_only: bool,
+    text: String,
+}
+
+impl Visitor for TextCollector {
+    fn enter(&mut self, arena: &NodeArena, node: NodeId) {
+        let node = arena.get_node(node);
+        if self.skip_whitespace_only && node.is_whitespace_only_text() {
+            return;
+        }
+
+        if let NodeKind::Text { data } = &node.kind {
+            self.text.push_str(data);
+        }
+    }
+}
+
+/// Elements that are rendered as blocks by default, so their contents are put
+/// on separate lines by [NodeArena::rendered_text].
+static BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "dd",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "legend",
+    "li",
+    "listing",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "plaintext",
+    "pre",
+    "search",
+    "section",
+    "summary",
+    "table",
+    "tr",
+    "ul",
+    "xmp",
+];
+
+/// Elements whose contents are never rendered.
+static HIDDEN_ELEMENTS: &[&str] = &["head", "noframes", "script", "style", "template", "title"];
+
+/// Elements in which whitespace is not collapsed.
+static PREFORMATTED_ELEMENTS: &[&str] = &["listing", "plaintext", "pre", "textarea", "xmp"];
+
+#[derive(Default)]
+struct RenderedTextCollector {
+    text: String,
+    /// The number of open hidden elements around the current node.
+    hidden_depth: usize,
+    /// The number of open preformatted elements around the current node.
+    preformatted_depth: usize,
+    /// Whether a block started or ended since the last text, so the next text
+    /// goes on a new line.
+    pending_line_break: bool,
+    /// Whether collapsed whitespace or a table cell boundary was seen since
+    /// the last text.
+    pending_space: bool,
+}
+
+impl RenderedTextCollector {
+    fn at_start_of_line(&self) -> bool {
+        self.text.is_empty() || self.text.ends_with('\n')
+    }
+
+    /// Writes the separator that was seen before the next piece of text.
+    fn flush_pending(&mut self) {
+        if self.pending_line_break && !self.at_start_of_line() {
+            self.text.push('\n');
+        } else if self.pending_space && !self.at_start_of_line() {
+            self.text.push(' ');
+        }
+        self.pending_line_break = false;
+        self.pending_space = false;
+    }
+
+    fn push_text(&mut self, data: &str) {
+        for char in data.chars() {
+            if self.preformatted_depth > 0 {
+                self.flush_pending();
+                self.text.push(char);
+            } else if char.is_ascii_whitespace() {
+                self.pending_space = true;
+            } else {
+                self.flush_pending();
+                self.text.push(char);
             }
         }
     }
 }
+
+impl Visitor for RenderedTextCollector {
+    fn enter(&mut self, arena: &NodeArena, node: NodeId) {
+        let node = arena.get_node(node);
+        if node.is_element_with_one_of_tag_names(HIDDEN_ELEMENTS) {
+            self.hidden_depth += 1;
+        }
+        if self.hidden_depth > 0 {
+            return;
+        }
+
+        if node.is_element_with_one_of_tag_names(PREFORMATTED_ELEMENTS) {
+            self.preformatted_depth += 1;
+        }
+
+        match &node.kind {
+            NodeKind::Text { data } => self.push_text(data),
+            NodeKind::Element { .. } if node.is_element_with_tag_name("br") => {
+                self.flush_pending();
+                self.text.push('\n');
+            }
+            NodeKind::Element { .. } if node.is_element_with_one_of_tag_names(BLOCK_ELEMENTS) => {
+                self.pending_line_break = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn leave(&mut self, arena: &NodeArena, node: NodeId) {
+        let node = arena.get_node(node);
+        if self.hidden_depth > 0 {
+            if node.is_element_with_one_of_tag_names(HIDDEN_ELEMENTS) {
+                self.hidden_depth -= 1;
+            }
+            return;
+        }
+
+        if node.is_element_with_one_of_tag_names(PREFORMATTED_ELEMENTS) {
+            self.preformatted_depth -= 1;
+        }
+
+        if node.is_element_with_one_of_tag_names(BLOCK_ELEMENTS) {
+            self.pending_line_break = true;
+        } else if node.is_element_with_one_of_tag_names(&["td", "th"]) {
+            self.pending_space = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse;
+
+    #[test]
+    fn sibling_links_follow_the_children() {
+        // The sibling links are stored on the nodes instead of being looked up
+        // in the children of the parent, so they have to be kept in sync with
+        // every mutation.
+        fn assert_in_sync(arena: &NodeArena, parent: NodeId) {
+            let children = arena.get_node(parent).children();
+            for (index, child) in children.iter().enumerate() {
+                let previous = index.checked_sub(1).map(|index| children[index]);
+                assert_eq!(arena.previous_sibling(*child), previous);
+                assert_eq!(arena.next_sibling(*child), children.get(index + 1).copied());
+            }
+        }
+
+        let mut arena = NodeArena::new();
+        let ul = arena.create_element("ul");
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|_| arena.create_element("li"));
+        arena.append_child(ul, a).unwrap();
+        arena.append_child(ul, c).unwrap();
+        assert_in_sync(&arena, ul);
+
+        arena.insert_before(ul, b, Some(c)).unwrap();
+        assert_in_sync(&arena, ul);
+        arena.insert_before(ul, d, Some(a)).unwrap();
+        assert_in_sync(&arena, ul);
+
+        // Moving a child to the end unlinks it from its old position.
+        arena.append_child(ul, d).unwrap();
+        assert_eq!(arena.get_node(ul).children(), [a, b, c, d]);
+        assert_in_sync(&arena, ul);
+
+        let e = arena.create_element("li");
+        arena.replace_child(e, b).unwrap();
+        assert_eq!(arena.previous_sibling(b), None);
+        assert_eq!(arena.next_sibling(b), None);
+        assert_in_sync(&arena, ul);
+
+        arena.detach(a);
+        arena.detach(d);
+        assert_eq!(arena.get_node(ul).children(), [e, c]);
+        assert_in_sync(&arena, ul);
+    }
+
+    #[test]
+    fn child_kinds_are_read_by_reference() {
+        let (dom, ul) = parse("<ul><li>a</li>text<!--c--></ul>", "ul");
+        let arena = dom.arena();
+        assert_eq!(arena.child_count(ul), 3);
+
+        let kinds: Vec<&NodeKind> = (0..arena.child_count(ul))
+            .map(|index| &arena.get_node(arena.nth_child(ul, index).unwrap()).kind)
+            .collect();
+        assert!(matches!(kinds[0], NodeKind::Element { local_name, .. } if local_name == "li"));
+        assert!(matches!(kinds[1], NodeKind::Text { data } if data == "text"));
+        assert!(matches!(kinds[2], NodeKind::Comment { data } if data == "c"));
+        assert_eq!(arena.nth_child(ul, 3), None);
+    }
+
+    #[test]
+    fn compares_subtrees_structurally() {
+        let dom = Dom::parse_owned(
+            "<div><p class=a>x<b>y</b></p></div>\
+             <section><p class=a>x<b>y</b></p><p class=b>x<b>y</b></p><p class=a>x<i>y</i></p></section>",
+        );
+        let arena = dom.arena();
+        let paragraphs: Vec<NodeId> = arena.elements_by_tag(dom.document(), "p").collect();
+
+        assert!(arena.nodes_structurally_equal(paragraphs[0], paragraphs[1]));
+        assert!(arena.nodes_structurally_equal(paragraphs[0], paragraphs[0]));
+        assert!(!arena.nodes_structurally_equal(paragraphs[0], paragraphs[2]));
+        assert!(!arena.nodes_structurally_equal(paragraphs[0], paragraphs[3]));
+    }
+
+    #[test]
+    fn reads_the_declared_charset() {
+        let dom = Dom::parse_owned("<head><title>x</title><meta charset=\"utf-8\"></head>");
+        assert_eq!(
+            dom.arena().meta_charset(dom.document()),
+            Some("utf-8".to_string())
+        );
+
+        let dom = Dom::parse_owned(
+            "<meta http-equiv=Content-Type content=\"text/html; charset=windows-1252\">",
+        );
+        assert_eq!(
+            dom.arena().meta_charset(dom.document()),
+            Some("windows-1252".to_string())
+        );
+
+        let dom = Dom::parse_owned("<meta name=viewport>");
+        assert_eq!(dom.arena().meta_charset(dom.document()), None);
+    }
+
+    #[test]
+    fn builds_a_tree_by_hand() {
+        let mut arena = NodeArena::new();
+        let div = arena.create_element("DIV");
+        let text = arena.create_text("hi");
+        let comment = arena.create_comment("note");
+        assert_eq!(arena.append_child(div, text), Ok(text));
+        assert_eq!(arena.append_child(div, comment), Ok(comment));
+        assert_eq!(arena.outer_html(div), "<div>hi<!--note--></div>");
+
+        // A node can't be inserted into itself.
+        assert!(arena.append_child(div, div).is_err());
+    }
+
+    #[test]
+    fn replaces_a_middle_child() {
+        let mut arena = NodeArena::new();
+        let ul = arena.create_element("ul");
+        let [a, b, c] = ["a", "b", "c"].map(|text| {
+            let li = arena.create_element("li");
+            let text = arena.create_text(text);
+            arena.append_child(li, text).unwrap();
+            arena.append_child(ul, li).unwrap()
+        });
+        let new = arena.create_element("hr");
+
+        assert_eq!(arena.replace_child(new, b), Ok(b));
+        assert_eq!(arena.get_node(ul).children(), [a, new, c]);
+        assert_eq!(arena.get_node(new).parent(), Some(ul));
+        assert_eq!(arena.get_node(b).parent(), None);
+        assert_eq!(arena.previous_sibling(new), Some(a));
+        assert_eq!(arena.next_sibling(new), Some(c));
+        assert_eq!(arena.next_sibling(a), Some(new));
+        assert_eq!(arena.previous_sibling(c), Some(new));
+        assert_eq!(arena.outer_html(ul), "<ul><li>a</li><hr><li>c</li></ul>");
+
+        // The shared checks still apply.
+        assert_eq!(
+            arena.replace_child(ul, a),
+            Err(DomException::HierarchyRequest)
+        );
+        assert_eq!(arena.replace_child(a, b), Err(DomException::NotFound));
+    }
+
+    #[test]
+    fn finds_the_root() {
+        let (mut dom, deepest) = parse("<div><ul><li><b><i>x</i></b></li></ul></div>", "i");
+        let document = dom.document();
+        assert_eq!(dom.arena().root(deepest), document);
+        assert_eq!(dom.arena().root(document), document);
+
+        let arena = dom.arena_mut();
+        let ul = arena.elements_by_tag(document, "ul").next().unwrap();
+        arena.detach(ul);
+        assert_eq!(arena.root(deepest), ul);
+    }
+
+    #[test]
+    fn clears_the_arena_for_reuse() {
+        let mut arena = NodeArena::new();
+        let first = Dom::parse("<p>one</p><p>two</p>", &mut arena);
+        let first_count = arena.nodes.len();
+
+        arena.clear();
+        assert!(arena.nodes.is_empty());
+
+        let second = Dom::parse("<b>three</b>", &mut arena);
+        assert_eq!(second, first);
+        assert!(arena.nodes.len() < first_count);
+        assert_eq!(
+            arena.inner_html(second),
+            "<html><head></head><body><b>three</b></body></html>"
+        );
+    }
+
+    #[test]
+    fn counts_ancestors_for_the_depth() {
+        let mut arena = NodeArena::new();
+        let [div, p, b] = ["div", "p", "b"].map(|tag_name| arena.create_element(tag_name));
+        arena.append_child(div, p).unwrap();
+        arena.append_child(p, b).unwrap();
+        assert_eq!([div, p, b].map(|node| arena.depth(node)), [0, 1, 2]);
+
+        let (dom, html) = parse("", "html");
+        assert_eq!(dom.arena().depth(dom.document()), 0);
+        assert_eq!(dom.arena().depth(html), 1);
+    }
+
+    #[test]
+    fn inserts_before_a_child() {
+        let mut arena = NodeArena::new();
+        let ul = arena.create_element("ul");
+        let [a, b, c] = ["a", "b", "c"].map(|_| arena.create_element("li"));
+        arena.append_child(ul, a).unwrap();
+        arena.append_child(ul, c).unwrap();
+
+        assert_eq!(arena.insert_before(ul, b, Some(c)), Ok(b));
+        assert_eq!(arena.get_node(ul).children(), [a, b, c]);
+        assert_eq!(arena.next_sibling(a), Some(b));
+        assert_eq!(arena.previous_sibling(c), Some(b));
+
+        // Inserting before itself leaves the node in place.
+        assert_eq!(arena.insert_before(ul, b, Some(b)), Ok(b));
+        assert_eq!(arena.get_node(ul).children(), [a, b, c]);
+
+        // The reference child has to be a child of the parent.
+        let orphan = arena.create_element("li");
+        assert_eq!(
+            arena.insert_before(ul, orphan, Some(orphan)),
+            Err(DomException::NotFound)
+        );
+    }
+
+    #[test]
+    fn stores_user_data_per_type() {
+        #[derive(Debug, PartialEq)]
+        struct LayoutBox(u32);
+
+        let mut arena = NodeArena::new();
+        let div = arena.create_element("div");
+        let span = arena.create_element("span");
+
+        arena.set_user_data(div, LayoutBox(1));
+        arena.set_user_data(div, "source map");
+        assert_eq!(arena.user_data::<LayoutBox>(div), Some(&LayoutBox(1)));
+        assert_eq!(arena.user_data::<&str>(div), Some(&"source map"));
+        assert_eq!(arena.user_data::<LayoutBox>(span), None);
+
+        arena.set_user_data(div, LayoutBox(2));
+        assert_eq!(arena.user_data::<LayoutBox>(div), Some(&LayoutBox(2)));
+
+        assert!(arena.remove_user_data::<LayoutBox>(div));
+        assert!(!arena.remove_user_data::<LayoutBox>(div));
+        assert_eq!(arena.user_data::<LayoutBox>(div), None);
+        assert_eq!(arena.user_data::<&str>(div), Some(&"source map"));
+    }
+
+    #[test]
+    fn detached_subtrees_can_be_reinserted() {
+        let dom = Dom::parse_owned("<div id=from><ul><li>a<li>b</ul></div><div id=to></div>");
+        let document = dom.document();
+        let mut arena = dom.into_arena();
+        let from = arena.find_by_attribute(document, "id", "from").unwrap();
+        let to = arena.find_by_attribute(document, "id", "to").unwrap();
+        let ul = arena.get_node(from).children()[0];
+        let items = arena.get_node(ul).children().to_vec();
+
+        assert_eq!(arena.detach(ul), ul);
+        assert_eq!(arena.get_node(ul).parent(), None);
+        assert!(arena.get_node(from).children().is_empty());
+        // Detaching a node without a parent does nothing.
+        assert_eq!(arena.detach(ul), ul);
+
+        arena.append_child(to, ul).unwrap();
+        assert_eq!(arena.get_node(ul).parent(), Some(to));
+        assert_eq!(arena.get_node(ul).children(), items);
+        for item in items {
+            assert_eq!(arena.get_node(item).parent(), Some(ul));
+        }
+        assert_eq!(arena.inner_html(to), "<ul><li>a</li><li>b</li></ul>");
+    }
+
+    #[test]
+    fn rendered_text_breaks_lines_between_blocks() {
+        let rendered = |html: &str| {
+            let dom = Dom::parse_owned(html);
+            let body = dom
+                .arena()
+                .elements_by_tag(dom.document(), "body")
+                .next()
+                .unwrap();
+            dom.arena().rendered_text(body)
+        };
+        assert_eq!(rendered("<p>one</p><p>two</p>"), "one\ntwo");
+        assert_eq!(rendered("<div>  a\n  <b>b</b>   c </div>"), "a b c");
+        assert_eq!(rendered("<ul><li>a<li>b</ul><h1>c</h1>"), "a\nb\nc");
+    }
+
+    #[test]
+    fn rendered_text_breaks_at_br_but_not_at_wbr() {
+        let dom = Dom::parse_owned("<span>a<br>b<wbr>c</span>");
+        let arena = dom.arena();
+        let span = arena
+            .elements_by_tag(dom.document(), "span")
+            .next()
+            .unwrap();
+        assert_eq!(arena.rendered_text(span), "a\nbc");
+    }
+
+    #[test]
+    fn renames_elements_in_place() {
+        let dom = Dom::parse_owned("<b class=x>bold <i>text</i></b>");
+        let document = dom.document();
+        let mut arena = dom.into_arena();
+        let b = arena.elements_by_tag(document, "b").next().unwrap();
+        let body = arena.get_node(b).parent().unwrap();
+
+        assert_eq!(arena.rename_element(b, "STRONG"), Ok(()));
+        assert_eq!(
+            arena.inner_html(body),
+            "<strong class=\"x\">bold <i>text</i></strong>"
+        );
+
+        let text = arena.get_node(b).children()[0];
+        assert_eq!(
+            arena.rename_element(text, "em"),
+            Err(DomException::InvalidNodeType)
+        );
+    }
+
+    #[test]
+    fn wraps_a_node_in_a_new_element() {
+        let dom = Dom::parse_owned("<p>before <span>link</span> after</p>");
+        let document = dom.document();
+        let mut arena = dom.into_arena();
+        let span = arena.elements_by_tag(document, "span").next().unwrap();
+        let p = arena.get_node(span).parent().unwrap();
+        let [before, _, after] = arena.get_node(p).children().to_vec()[..] else {
+            panic!("expected three children");
+        };
+
+        let a = arena.wrap(span, "a").unwrap();
+        assert_eq!(arena.get_node(p).children(), [before, a, after]);
+        assert_eq!(arena.get_node(a).children(), [span]);
+        assert_eq!(arena.get_node(span).parent(), Some(a));
+        assert_eq!(arena.inner_html(p), "before <a><span>link</span></a> after");
+
+        // A document element can't be wrapped.
+        let html = arena.document_element(document).unwrap();
+        assert!(arena.wrap(html, "div").is_err());
+        assert_eq!(arena.document_element(document), Some(html));
+    }
+
+    #[test]
+    fn failed_wraps_leave_the_arena_unchanged() {
+        let dom = Dom::parse_owned("<!DOCTYPE html><!-- c --><p>text</p>");
+        let document = dom.document();
+        let mut arena = dom.into_arena();
+        let [doctype, comment, html] = arena.get_node(document).children().to_vec()[..] else {
+            panic!("expected a doctype, a comment and the document element");
+        };
+        let node_count = arena.nodes.len();
+
+        // The wrapper can't be a second element child of the document.
+        for node in [document, doctype, comment, html] {
+            assert_eq!(arena.wrap(node, "div"), Err(DomException::HierarchyRequest));
+            assert_eq!(arena.nodes.len(), node_count);
+        }
+        assert_eq!(
+            arena.get_node(document).children(),
+            [doctype, comment, html]
+        );
+    }
+
+    #[test]
+    fn sets_text_content() {
+        let dom = Dom::parse_owned("<div>mixed <b>bold</b><!-- c --> text</div>");
+        let document = dom.document();
+        let mut arena = dom.into_arena();
+        let div = arena.elements_by_tag(document, "div").next().unwrap();
+        let b = arena.elements_by_tag(div, "b").next().unwrap();
+
+        arena.set_text_content(div, "a < b");
+        assert_eq!(arena.inner_html(div), "a &lt; b");
+        assert_eq!(arena.get_node(div).children().len(), 1);
+        assert_eq!(arena.get_node(b).parent(), None);
+
+        let text = arena.get_node(div).children()[0];
+        arena.set_text_content(text, "replaced");
+        assert_eq!(arena.get_node(div).children(), [text]);
+        assert_eq!(arena.inner_html(div), "replaced");
+
+        arena.set_text_content(div, "");
+        assert!(arena.get_node(div).children().is_empty());
+    }
+
+    #[test]
+    fn inserts_adjacent_html() {
+        let dom = Dom::parse_owned("<section><div><i>old</i></div></section>");
+        let document = dom.document();
+        let mut arena = dom.into_arena();
+        let div = arena.elements_by_tag(document, "div").next().unwrap();
+        let section = arena.get_node(div).parent().unwrap();
+
+        let inserted = arena
+            .insert_adjacent_html(div, AdjacentPosition::AfterBegin, "<b>x</b>")
+            .unwrap();
+        assert_eq!(inserted.len(), 1);
+        assert_eq!(arena.get_node(div).children()[0], inserted[0]);
+        assert_eq!(arena.inner_html(div), "<b>x</b><i>old</i>");
+
+        arena
+            .insert_adjacent_html(div, AdjacentPosition::BeforeEnd, "end")
+            .unwrap();
+        arena
+            .insert_adjacent_html(div, AdjacentPosition::BeforeBegin, "<hr>")
+            .unwrap();
+        arena
+            .insert_adjacent_html(div, AdjacentPosition::AfterEnd, "<!-- after -->")
+            .unwrap();
+        assert_eq!(
+            arena.inner_html(section),
+            "<hr><div><b>x</b><i>old</i>end</div><!-- after -->"
+        );
+
+        let html = arena.document_element(document).unwrap();
+        assert_eq!(
+            arena.insert_adjacent_html(html, AdjacentPosition::BeforeBegin, "x"),
+            Err(DomException::NoModificationAllowed)
+        );
+    }
+
+    #[test]
+    fn stores_user_data_that_is_not_send() {
+        use std::rc::Rc;
+
+        struct Font {
+            family: String,
+        }
+
+        let mut arena = NodeArena::new();
+        let p = arena.create_element("p");
+        let font = Rc::new(Font {
+            family: "serif".to_string(),
+        });
+        arena.set_user_data(p, Rc::clone(&font));
+
+        let stored = arena.user_data::<Rc<Font>>(p).unwrap();
+        assert!(Rc::ptr_eq(stored, &font));
+        assert_eq!(stored.family, "serif");
+
+        // Clones of the arena don't share the values with the original.
+        let clone = arena.clone();
+        assert!(clone.user_data::<Rc<Font>>(p).is_none());
+        assert_eq!(Rc::strong_count(&font), 2);
+    }
+
+    #[test]
+    fn failed_adjacent_html_insertions_leave_the_arena_unchanged() {
+        let dom = Dom::parse_owned("<div>text</div>");
+        let document = dom.document();
+        let mut arena = dom.into_arena();
+        let div = arena.elements_by_tag(document, "div").next().unwrap();
+        let text = arena.get_node(div).children()[0];
+        let html = arena.document_element(document).unwrap();
+        let node_count = arena.nodes.len();
+
+        // Only elements can be the reference, so nothing is parsed or inserted.
+        assert_eq!(
+            arena.insert_adjacent_html(text, AdjacentPosition::BeforeEnd, "<b>a</b>b<i>c</i>"),
+            Err(DomException::HierarchyRequest)
+        );
+        assert_eq!(arena.nodes.len(), node_count);
+        assert_eq!(arena.inner_html(div), "text");
+
+        // Parsing in the context of the html element doesn't leave the body
+        // element used as the context behind.
+        let inserted = arena
+            .insert_adjacent_html(html, AdjacentPosition::BeforeEnd, "<p>x</p>")
+            .unwrap();
+        assert_eq!(arena.nodes.len(), node_count + 2);
+        assert_eq!(arena.get_node(inserted[0]).parent(), Some(html));
+        assert_eq!(arena.get_node(inserted[0]).node_document(), document);
+        assert_eq!(arena.outer_html(inserted[0]), "<p>x</p>");
+    }
+}
@@ -0,0 +1,486 @@
+//! A minimal implementation of the encoding sniffing algorithm.
+//!
+//! https://html.spec.whatwg.org/multipage/parsing.html#encoding-sniffing-algorithm
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+impl Encoding {
+    /// https://encoding.spec.whatwg.org/#concept-encoding-get
+    pub fn for_label(label: &str) -> Option<Self> {
+        // Remove any leading and trailing ASCII whitespace from label.
+        let label = label.trim_matches(|c: char| c.is_ascii_whitespace());
+
+        // If label is an ASCII case-insensitive match for any of the labels
+        // listed in the table below, then return the corresponding encoding;
+        // otherwise return failure.
+        match label.to_ascii_lowercase().as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Some(Encoding::Utf8),
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Some(Encoding::Utf16Le),
+            "unicodefffe" | "utf-16be" => Some(Encoding::Utf16Be),
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" => {
+                Some(Encoding::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    /// Decodes `bytes`, replacing malformed sequences with U+FFFD REPLACEMENT
+    /// CHARACTER.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Encoding::Windows1252 => bytes
+                .iter()
+                .map(|byte| decode_windows_1252(*byte))
+                .collect(),
+        }
+    }
+}
+
+/// Determines the encoding of `bytes` and decodes them into a string, without
+/// the byte order mark if there was one.
+pub fn sniff_and_decode(bytes: &[u8]) -> String {
+    let (encoding, bom_length) = sniff(bytes);
+    encoding.decode(&bytes[bom_length..])
+}
+
+/// Returns the sniffed encoding and the length of the byte order mark.
+fn sniff(bytes: &[u8]) -> (Encoding, usize) {
+    // https://encoding.spec.whatwg.org/#bom-sniff
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return (Encoding::Utf8, 3);
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return (Encoding::Utf16Be, 2);
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return (Encoding::Utf16Le, 2);
+    }
+
+    // Optionally prescan the byte stream to determine its encoding, with the
+    // end condition being when the user agent decides that scanning further
+    // bytes would not be efficient.
+    if let Some(encoding) = prescan(&bytes[..bytes.len().min(1024)]) {
+        return (encoding, 0);
+    }
+
+    // Otherwise, return an implementation-defined or user-specified default
+    // character encoding. UTF-8 is used when the bytes are valid UTF-8, as
+    // most documents without a declaration are.
+    match std::str::from_utf8(bytes) {
+        Ok(_) => (Encoding::Utf8, 0),
+        Err(_) => (Encoding::Windows1252, 0),
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    // Let position be a pointer to a byte in the input byte stream, initially
+    // pointing at the first byte.
+    let mut position = 0;
+
+    // Prescan for UTF-16 XML declarations: If position points to:
+    // A sequence of bytes starting with: 0x3C, 0x0, 0x3F, 0x0, 0x78, 0x0
+    if bytes.starts_with(&[0x3C, 0x00, 0x3F, 0x00, 0x78, 0x00]) {
+        return Some(Encoding::Utf16Le);
+    }
+    // A sequence of bytes starting with: 0x0, 0x3C, 0x0, 0x3F, 0x0, 0x78
+    if bytes.starts_with(&[0x00, 0x3C, 0x00, 0x3F, 0x00, 0x78]) {
+        return Some(Encoding::Utf16Be);
+    }
+
+    // Loop: If position points to:
+    while position < bytes.len() {
+        let rest = &bytes[position..];
+
+        if rest.starts_with(b"<!--") {
+            // Advance the position pointer so that it points at the first 0x3E
+            // byte which is preceded by two 0x2D bytes (i.e. at the end of an
+            // ASCII '-->' sequence) and comes after the 0x3C byte that was
+            // found.
+            match find(&rest[2..], b"-->") {
+                Some(end) => position += 2 + end + 2,
+                None => return None,
+            }
+        } else if rest.len() > 5
+            && rest[..5].eq_ignore_ascii_case(b"<meta")
+            && matches!(rest[5], 0x09 | 0x0A | 0x0C | 0x0D | 0x20 | 0x2F)
+        {
+            // Advance the position pointer so that it points at the next byte
+            // after the 0x20 or 0x2F byte.
+            position += 6;
+
+            if let Some(encoding) = prescan_meta(bytes, &mut position) {
+                return Some(encoding);
+            }
+        } else if rest.len() > 2
+            && rest[0] == b'<'
+            && (rest[1].is_ascii_alphabetic() || rest[1] == b'/' && rest[2].is_ascii_alphabetic())
+        {
+            // Advance the position pointer so that it points at the next 0x09,
+            // 0x0A, 0x0C, 0x0D, 0x20, or 0x3E byte.
+            while position < bytes.len()
+                && !matches!(bytes[position], 0x09 | 0x0A | 0x0C | 0x0D | 0x20 | 0x3E)
+            {
+                position += 1;
+            }
+
+            // Repeatedly get an attribute until no further attributes can be
+            // found.
+            while get_attribute(bytes, &mut position).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            // Advance the position pointer so that it points at the first 0x3E
+            // byte that comes after the 0x3C byte that was found.
+            match find(rest, b">") {
+                Some(end) => position += end,
+                None => return None,
+            }
+        }
+
+        // Next byte: Move position so it points at the next byte in the input
+        // byte stream, and return to the step above labeled loop.
+        position += 1;
+    }
+
+    None
+}
+
+/// The steps for a `<meta` tag in the prescan, starting right after the tag
+/// name.
+fn prescan_meta(bytes: &[u8], position: &mut usize) -> Option<Encoding> {
+    // Let attribute list be an empty list of strings.
+    let mut attribute_list: Vec<Vec<u8>> = vec![];
+
+    // Let got pragma be false.
+    let mut got_pragma = false;
+
+    // Let need pragma be null.
+    let mut need_pragma = None;
+
+    // Let charset be the null value.
+    let mut charset = None;
+
+    // Attributes: Get an attribute and its value. If no attribute was
+    // sniffed, then jump to the processing step below.
+    while let Some((name, value)) = get_attribute(bytes, position) {
+        // If the attribute's name is already in attribute list, then return to
+        // the step labeled attributes.
+        if attribute_list.contains(&name) {
+            continue;
+        }
+
+        // Add the attribute's name to attribute list.
+        attribute_list.push(name.clone());
+
+        // Run the appropriate step from the following list, if one applies:
+        match name.as_slice() {
+            // If the attribute's name is "http-equiv": If the attribute's
+            // value is "content-type", then set got pragma to true.
+            b"http-equiv" if value == b"content-type" => got_pragma = true,
+            // If the attribute's name is "content": Apply the algorithm for
+            // extracting a character encoding from a meta element, giving the
+            // attribute's value as the string to parse. If a character
+            // encoding is returned, and if charset is still set to null, let
+            // charset be the encoding returned, and set need pragma to true.
+            b"content" if charset.is_none() => {
+                if let Some(encoding) = extract_encoding_from_content(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+            b"charset" => {
+                // Let charset be the result of getting an encoding from the
+                // attribute's value, and set need pragma to false.
+                charset = Encoding::for_label(&String::from_utf8_lossy(&value));
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+    }
+
+    // Processing: If need pragma is null, then jump to the step below labeled
+    // next byte.
+    let need_pragma = need_pragma?;
+
+    // If need pragma is true but got pragma is false, then jump to the step
+    // below labeled next byte.
+    if need_pragma && !got_pragma {
+        return None;
+    }
+
+    // If charset is failure, then jump to the step below labeled next byte.
+    // If charset is UTF-16BE/LE, then set charset to UTF-8.
+    // TODO: If charset is x-user-defined, then set charset to windows-1252.
+    match charset? {
+        Encoding::Utf16Le | Encoding::Utf16Be => Some(Encoding::Utf8),
+        encoding => Some(encoding),
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+fn get_attribute(bytes: &[u8], position: &mut usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    let is_whitespace = |byte: u8| matches!(byte, 0x09 | 0x0A | 0x0C | 0x0D | 0x20);
+    let byte_at = |position: usize| bytes.get(position).copied();
+
+    // If the byte at position is one of 0x09, 0x0A, 0x0C, 0x0D, 0x20, or 0x2F
+    // then advance position to the next byte and redo this step.
+    while byte_at(*position).is_some_and(|byte| is_whitespace(byte) || byte == 0x2F) {
+        *position += 1;
+    }
+
+    // If the byte at position is 0x3E, then abort the get an attribute
+    // algorithm. There isn't one.
+    if byte_at(*position) == Some(0x3E) {
+        return None;
+    }
+
+    // Otherwise, the byte at position is the start of the attribute name. Let
+    // attribute name and attribute value be the empty string.
+    let mut name = vec![];
+    let mut value = vec![];
+
+    // Process the byte at position as follows:
+    loop {
+        match byte_at(*position)? {
+            // If it is 0x3D, and the attribute name is longer than the empty
+            // string: Advance position to the next byte and jump to the step
+            // below labeled value.
+            0x3D if !name.is_empty() => {
+                *position += 1;
+                break;
+            }
+            // If it is 0x09, 0x0A, 0x0C, 0x0D, or 0x20: Jump to the step below
+            // labeled spaces.
+            byte if is_whitespace(byte) => {
+                // Spaces: If the byte at position is one of 0x09, 0x0A, 0x0C,
+                // 0x0D, or 0x20 then advance position to the next byte, then,
+                // repeat this step.
+                while byte_at(*position).is_some_and(is_whitespace) {
+                    *position += 1;
+                }
+
+                // If the byte at position is not 0x3D, abort the get an
+                // attribute algorithm. The attribute's name is the value of
+                // attribute name, its value is the empty string.
+                if byte_at(*position) != Some(0x3D) {
+                    return Some((name, value));
+                }
+
+                // Advance position past the 0x3D byte.
+                *position += 1;
+                break;
+            }
+            // If it is 0x2F or 0x3E: Abort the get an attribute algorithm. The
+            // attribute's name is the value of attribute name, its value is
+            // the empty string.
+            0x2F | 0x3E => return Some((name, value)),
+            // If it is in the range 0x41 to 0x5A: Append the code point b+0x20
+            // to attribute name. Anything else: Append the code point with the
+            // same value as the byte at position to attribute name.
+            byte => name.push(byte.to_ascii_lowercase()),
+        }
+
+        // Advance position to the next byte and return to the previous step.
+        *position += 1;
+    }
+
+    // Value: If the byte at position is one of 0x09, 0x0A, 0x0C, 0x0D, or
+    // 0x20 then advance position to the next byte, then, repeat this step.
+    while byte_at(*position).is_some_and(is_whitespace) {
+        *position += 1;
+    }
+
+    // Process the byte at position as follows:
+    match byte_at(*position)? {
+        // If it is 0x22 or 0x27:
+        quote @ (0x22 | 0x27) => loop {
+            // Advance position to the next byte.
+            *position += 1;
+
+            // If the value of the byte at position is the value of b, then
+            // advance position to the next byte and abort the "get an
+            // attribute" algorithm. The attribute's name is the value of
+            // attribute name, and its value is the value of attribute value.
+            // Otherwise, append the lowercased byte to attribute value.
+            match byte_at(*position)? {
+                byte if byte == quote => {
+                    *position += 1;
+                    return Some((name, value));
+                }
+                byte => value.push(byte.to_ascii_lowercase()),
+            }
+        },
+        // If it is 0x3E: Abort the get an attribute algorithm. The attribute's
+        // name is the value of attribute name, its value is the empty string.
+        0x3E => return Some((name, value)),
+        // Anything else: Append the lowercased byte to attribute value. Advance
+        // position to the next byte.
+        byte => {
+            value.push(byte.to_ascii_lowercase());
+            *position += 1;
+        }
+    }
+
+    // Process the byte at position as follows:
+    loop {
+        match byte_at(*position)? {
+            // If it is 0x09, 0x0A, 0x0C, 0x0D, 0x20, or 0x3E: Abort the get an
+            // attribute algorithm. The attribute's name is the value of
+            // attribute name and its value is the value of attribute value.
+            byte if is_whitespace(byte) || byte == 0x3E => return Some((name, value)),
+            // Anything else: Append the lowercased byte to attribute value.
+            byte => value.push(byte.to_ascii_lowercase()),
+        }
+
+        // Advance position to the next byte and return to the previous step.
+        *position += 1;
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+fn extract_encoding_from_content(content: &[u8]) -> Option<Encoding> {
+    Encoding::for_label(&String::from_utf8_lossy(extract_label_from_content(
+        content,
+    )?))
+}
+
+/// Returns the encoding label declared in the `content` attribute of a `<meta
+/// http-equiv="content-type">` element, without getting an encoding for it.
+pub fn extract_label_from_content(content: &[u8]) -> Option<&[u8]> {
+    let mut position = 0;
+
+    // Loop: Find the first seven characters in s after position that are an
+    // ASCII case-insensitive match for the word "charset". If no such match is
+    // found, return nothing.
+    loop {
+        position += find(&content[position..], b"charset")? + "charset".len();
+
+        // Skip any ASCII whitespace that immediately follow the word
+        // "charset" (there might not be any).
+        while content
+            .get(position)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            position += 1;
+        }
+
+        // If the next character is not a U+003D EQUALS SIGN (=), then move
+        // position to point just before that next character, and jump back to
+        // the step labeled loop.
+        if content.get(position) == Some(&b'=') {
+            position += 1;
+            break;
+        }
+    }
+
+    // Skip any ASCII whitespace that immediately follow the equals sign (there
+    // might not be any).
+    while content
+        .get(position)
+        .is_some_and(|byte| byte.is_ascii_whitespace())
+    {
+        position += 1;
+    }
+
+    // Process the next character as follows:
+    let rest = &content[position..];
+    match rest.first()? {
+        // If it is a U+0022 (") and there is a later U+0022 (") in s, or if it
+        // is a U+0027 (') and there is a later U+0027 (') in s: Return the
+        // result of getting an encoding from the substring that is between
+        // this character and the next earliest occurrence of this character.
+        quote @ (b'"' | b'\'') => {
+            let end = rest[1..].iter().position(|byte| byte == quote)?;
+            Some(&rest[1..1 + end])
+        }
+        // Otherwise: Return the result of getting an encoding from the
+        // substring that consists of this character up to but not including
+        // the first ASCII whitespace or U+003B SEMICOLON character (;), or the
+        // end of s, whichever comes first.
+        _ => {
+            let end = rest
+                .iter()
+                .position(|byte| byte.is_ascii_whitespace() || *byte == b';')
+                .unwrap_or(rest.len());
+            Some(&rest[..end])
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let code_units = bytes.chunks(2).map(|chunk| match chunk {
+        [first, second] => from_bytes([*first, *second]),
+        // A trailing odd byte can never be a valid code unit.
+        _ => 0xFFFD,
+    });
+
+    char::decode_utf16(code_units)
+        .map(|character| character.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// https://encoding.spec.whatwg.org/index-windows-1252.txt
+fn decode_windows_1252(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}',
+        '\u{2021}', '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}',
+        '\u{017D}', '\u{008F}', '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}',
+        '\u{2022}', '\u{2013}', '\u{2014}', '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}',
+        '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+    ];
+
+    match byte {
+        0x80..=0x9F => HIGH[(byte - 0x80) as usize],
+        // All other bytes map to the code point with the same value.
+        _ => byte as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_utf16_with_a_byte_order_mark() {
+        let html = "<p>caf\u{e9} \u{201c}</p>";
+        let little_endian: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(html.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let big_endian: Vec<u8> = [0xFE, 0xFF]
+            .into_iter()
+            .chain(html.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        assert_eq!(sniff_and_decode(&little_endian), html);
+        assert_eq!(sniff_and_decode(&big_endian), html);
+    }
+
+    #[test]
+    fn decodes_windows_1252_from_a_meta_charset() {
+        let bytes = b"<meta charset=windows-1252><p>caf\xE9 \x93</p>";
+        assert_eq!(
+            sniff_and_decode(bytes),
+            "<meta charset=windows-1252><p>caf\u{e9} \u{201c}</p>"
+        );
+        // Without a declaration, invalid UTF-8 falls back to Windows-1252.
+        assert_eq!(sniff_and_decode(b"caf\xE9"), "caf\u{e9}");
+    }
+}
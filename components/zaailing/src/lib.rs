@@ -1,22 +1,521 @@
-use arena::NodeArena;
-use node::Node;
+//! An HTML parser that builds a DOM tree inside a [NodeArena].
+//!
+//! # Stability
+//!
+//! The items re-exported from the crate root are the public API of this
+//! crate: [Dom], [NodeArena], [NodeId], [DomException], [AdjacentPosition],
+//! [Node], [NodeKind], [DocumentMode], [Attribute], [Namespace],
+//! [ParseError], [ParserOptions], [ErrorCallback], [SerializerOptions],
+//! [Token], [TokenAttribute], [tokenize] and the escaping helpers. Prefer
+//! importing them from here instead of through the modules that define them.
+//!
+//! The `arena`, `node` and `traversal` modules are public as well, but their
+//! layout may change between versions.
+
+use std::cell::RefCell;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
+pub use arena::{AdjacentPosition, DomException, NodeArena, NodeId};
+pub use node::{Attribute, DocumentMode, Node, NodeKind};
+pub use parser::{ErrorCallback, Namespace, ParseError, ParserOptions};
+pub use serializer::{escape_attribute, escape_text, SerializerOptions};
+pub use tokenizer::{Attribute as TokenAttribute, Token};
 
 pub mod arena;
+mod encoding;
+mod entities;
 pub mod node;
 mod parser;
+mod selector;
+mod serializer;
+#[cfg(test)]
+mod test_util;
 mod tokenizer;
+pub mod traversal;
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Dom {}
+/// A parsed document together with the [NodeArena] that owns its nodes.
+///
+/// Use [Dom::parse_owned] to get one. The associated parsing functions that
+/// take a `&mut NodeArena` leave managing the arena up to the caller instead.
+#[derive(Debug, Clone)]
+pub struct Dom {
+    arena: NodeArena,
+    document: NodeId,
+}
+
+thread_local! {
+    /// An arena kept around between calls to [Dom::parse_pooled], so its
+    /// allocation can be reused.
+    static POOLED_ARENA: RefCell<Option<NodeArena>> = const { RefCell::new(None) };
+}
 
 impl Dom {
-    pub fn parse(html: &str, arena: &mut NodeArena) -> Node {
-        let document = parser::Parser::new(html, arena).parse();
-        document
+    /// Parses `html` as a complete document into a new arena owned by the
+    /// returned [Dom].
+    pub fn parse_owned(html: &str) -> Dom {
+        Dom::parse_into_arena(html, NodeArena::new())
+    }
+
+    /// Parses `html` like [Dom::parse_owned] and calls `f` with the result,
+    /// reusing an arena that is kept per thread instead of allocating a new
+    /// one for every document.
+    ///
+    /// The arena is cleared and returned to the pool once `f` returns, so
+    /// nothing from the document can outlive the call. Nested calls each get
+    /// their own arena.
+    pub fn parse_pooled<R>(html: &str, f: impl FnOnce(&Dom) -> R) -> R {
+        let arena = POOLED_ARENA
+            .with(|pooled| pooled.borrow_mut().take())
+            .unwrap_or_default();
+
+        let dom = Dom::parse_into_arena(html, arena);
+        let result = f(&dom);
+
+        let mut arena = dom.into_arena();
+        arena.clear();
+        POOLED_ARENA.with(|pooled| *pooled.borrow_mut() = Some(arena));
+
+        result
+    }
+
+    fn parse_into_arena(html: &str, mut arena: NodeArena) -> Dom {
+        arena.clear();
+        let document = Dom::parse(html, &mut arena);
+        Dom { arena, document }
+    }
+
+    /// Returns the arena that owns the nodes of this document.
+    pub fn arena(&self) -> &NodeArena {
+        &self.arena
+    }
+
+    pub fn arena_mut(&mut self) -> &mut NodeArena {
+        &mut self.arena
+    }
+
+    /// Returns the id of the document node.
+    pub fn document(&self) -> NodeId {
+        self.document
+    }
+
+    /// Consumes the [Dom] and returns its arena, so it can be reused.
+    pub fn into_arena(self) -> NodeArena {
+        self.arena
+    }
+
+    /// Parses `html` as a complete document and returns the id of the
+    /// document node.
+    ///
+    /// Like in a browser, the `html`, `head` and `body` elements are implied
+    /// when they are missing, so even an empty or whitespace-only string
+    /// yields a document containing `html > head, body`. Whitespace before
+    /// the DOCTYPE and the `html` element is dropped.
+    pub fn parse(html: &str, arena: &mut NodeArena) -> NodeId {
+        Dom::parse_with_options(html, arena, ParserOptions::default())
+            .expect("Parsing without limits should never fail")
+    }
+
+    pub fn parse_with_options(
+        html: &str,
+        arena: &mut NodeArena,
+        options: ParserOptions,
+    ) -> Result<NodeId, ParseError> {
+        parser::Parser::new(html, arena, options).parse()
+    }
+
+    /// Parses `html` into `document`, which was created by an earlier parse,
+    /// as if it was written to the end of that document's input. Content
+    /// normally ends up at the end of the body.
+    ///
+    /// The parser state from the earlier parse is not kept, so markup that
+    /// was left unfinished there (like an unclosed `<p>` or `<table>`) is not
+    /// continued.
+    pub fn parse_into(html: &str, arena: &mut NodeArena, document: NodeId) -> NodeId {
+        parser::Parser::new_into(html, arena, document, ParserOptions::default())
+            .parse()
+            .expect("Parsing without limits should never fail")
     }
 
-    pub fn parse_file(path: &str, arena: &mut NodeArena) -> Node {
-        let file_content = std::fs::read_to_string(path).unwrap();
-        Dom::parse(&file_content, arena)
+    /// Parses `html` as the contents of a `context_tag` element in the HTML
+    /// namespace, like setting `innerHTML` on such an element would.
+    ///
+    /// Returns the arena containing the parsed nodes, together with the
+    /// top-level nodes of the fragment in tree order.
+    pub fn parse_fragment(html: &str, context_tag: &str) -> (NodeArena, Vec<NodeId>) {
+        let mut arena = NodeArena::new();
+        let document = arena.create_node(Node::create_document());
+        let context = arena.create_node(Node::create_element(
+            document,
+            context_tag.to_ascii_lowercase(),
+            Namespace::Html,
+            None,
+            None,
+            false,
+        ));
+
+        let children = Dom::parse_fragment_with_context(html, &mut arena, context);
+        (arena, children)
+    }
+
+    /// Parses `html` as the contents of the `context` element, using the HTML
+    /// fragment parsing algorithm. The nodes are created in `arena`, but
+    /// `context` itself is not modified.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-parsing-algorithm
+    pub fn parse_fragment_with_context(
+        html: &str,
+        arena: &mut NodeArena,
+        context: NodeId,
+    ) -> Vec<NodeId> {
+        parser::Parser::new_fragment(html, arena, context, ParserOptions::default())
+            .parse_fragment()
+            .expect("Parsing without limits should never fail")
+    }
+
+    /// Parses a document from raw bytes, determining its encoding from a byte
+    /// order mark or a `<meta charset>` declaration.
+    pub fn parse_bytes(bytes: &[u8], arena: &mut NodeArena) -> NodeId {
+        let html = encoding::sniff_and_decode(bytes);
+        Dom::parse(&html, arena)
+    }
+
+    /// Parses `html` as a complete document, and returns the id of the
+    /// document node together with every error that was found, in the order
+    /// they were found.
+    ///
+    /// Parsing never fails, as any input can be parsed. Features the parser
+    /// does not implement yet are reported as [ParseError::Unsupported] and
+    /// recovered from as well as possible, which makes this the entry point
+    /// to use for untrusted input, like when fuzzing.
+    ///
+    /// This is not guaranteed to be free of panics, though: the tree builder
+    /// still asserts its own invariants, like the stack of open elements not
+    /// being empty, so a bug in the parser panics instead of producing a
+    /// broken tree. Wrap the call in [std::panic::catch_unwind] when a
+    /// process must survive such a bug.
+    pub fn parse_lossy(html: &str, arena: &mut NodeArena) -> (NodeId, Vec<ParseError>) {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let on_error = {
+            let errors = Rc::clone(&errors);
+            move |error: &ParseError| {
+                errors.borrow_mut().push(error.clone());
+                ControlFlow::Continue(())
+            }
+        };
+        let options = ParserOptions {
+            on_error: Some(Box::new(on_error)),
+            ..Default::default()
+        };
+
+        let document = Dom::parse_with_options(html, arena, options)
+            .expect("Parsing without limits and without aborting should never fail");
+        let errors = errors.take();
+        (document, errors)
+    }
+
+    /// Like [Dom::parse_lossy], but parses a document from raw bytes, whose
+    /// encoding is determined like in [Dom::parse_bytes].
+    pub fn parse_lossy_bytes(bytes: &[u8], arena: &mut NodeArena) -> (NodeId, Vec<ParseError>) {
+        let html = encoding::sniff_and_decode(bytes);
+        Dom::parse_lossy(&html, arena)
+    }
+
+    pub fn parse_file(path: &str, arena: &mut NodeArena) -> NodeId {
+        let file_content = std::fs::read(path).unwrap();
+        Dom::parse_bytes(&file_content, arena)
+    }
+}
+
+/// Splits `html` into tokens, ending with [Token::EndOfFile].
+///
+/// The tokenizer runs on its own here, so it never switches to the RCDATA,
+/// RAWTEXT or script data states like it does when driven by the tree
+/// construction stage. The contents of elements like `<title>` and `<style>`
+/// are therefore tokenized as regular markup.
+pub fn tokenize(html: &str) -> impl Iterator<Item = Token> + '_ {
+    let mut tokenizer = tokenizer::Tokenizer::new(html);
+    let mut finished = false;
+
+    std::iter::from_fn(move || {
+        if finished {
+            return None;
+        }
+
+        let token = tokenizer.next()?;
+        finished = token == Token::EndOfFile;
+        Some(token)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crate_root_exports_the_public_api() {
+        let mut arena = NodeArena::new();
+        let options = ParserOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let error: ParseError = Dom::parse_with_options("<p>", &mut arena, options).unwrap_err();
+        assert_eq!(error, ParseError::MaxDepthExceeded { max_depth: 1 });
+
+        let dom = Dom::parse_owned("<p id=x>a</p>");
+        let p: NodeId = dom
+            .arena()
+            .elements_by_tag(dom.document(), "p")
+            .next()
+            .unwrap();
+        let node: &Node = dom.arena().get_node(p);
+        assert!(matches!(&node.kind, NodeKind::Element { attributes, .. }
+            if attributes == &[Attribute::new("id".to_string(), "x".to_string())]));
+
+        let tokens: Vec<Token> = tokenize("<p id=x>").collect();
+        assert_eq!(
+            tokens[0],
+            Token::Tag {
+                start: true,
+                tag_name: "p".to_string(),
+                attributes: vec![TokenAttribute {
+                    name: "id".to_string(),
+                    value: "x".to_string(),
+                }],
+                self_closing: false,
+            }
+        );
+        assert_eq!(escape_text("<"), "&lt;");
+    }
+
+    #[test]
+    fn parses_a_fragment_in_a_context() {
+        let (arena, nodes) = Dom::parse_fragment("<li>a<li>b", "ul");
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(arena.outer_html(nodes[0]), "<li>a</li>");
+        assert_eq!(arena.outer_html(nodes[1]), "<li>b</li>");
+    }
+
+    #[test]
+    fn parse_lossy_only_sniffs_bytes() {
+        let mut arena = NodeArena::new();
+        let (document, _) = Dom::parse_lossy(
+            "<meta charset=windows-1252><p>caf\u{e9} \u{201c}",
+            &mut arena,
+        );
+        assert_eq!(arena.text_content(document), "caf\u{e9} \u{201c}");
+
+        let mut arena = NodeArena::new();
+        let (document, _) =
+            Dom::parse_lossy_bytes(b"<meta charset=windows-1252><p>caf\xE9 \x93", &mut arena);
+        assert_eq!(arena.text_content(document), "caf\u{e9} \u{201c}");
+    }
+
+    #[test]
+    fn parse_lossy_never_panics() {
+        const FRAGMENTS: &[&str] = &[
+            "<!DOCTYPE html>",
+            "<!doctype",
+            "<!--",
+            "-->",
+            "<!-",
+            "<![CDATA[",
+            "]]>",
+            "<?xml?>",
+            "<html>",
+            "</html>",
+            "<head>",
+            "</head>",
+            "<body>",
+            "</body>",
+            "<frameset>",
+            "</frameset>",
+            "<frame>",
+            "<noframes>",
+            "<table>",
+            "</table>",
+            "<caption>",
+            "<colgroup>",
+            "<col>",
+            "<tbody>",
+            "<tr>",
+            "</tr>",
+            "<td>",
+            "</td>",
+            "<th>",
+            "<select>",
+            "</select>",
+            "<option>",
+            "<optgroup>",
+            "<template>",
+            "</template>",
+            "<svg>",
+            "</svg>",
+            "<math>",
+            "<mi>",
+            "<annotation-xml encoding=text/html>",
+            "<foreignObject>",
+            "<desc>",
+            "<title>",
+            "</title>",
+            "<textarea>",
+            "</textarea>",
+            "<script>",
+            "</script>",
+            "<style>",
+            "</style>",
+            "<plaintext>",
+            "<xmp>",
+            "<iframe>",
+            "<noscript>",
+            "<a>",
+            "</a>",
+            "<b>",
+            "</b>",
+            "<a href=x>",
+            "<i>",
+            "</i>",
+            "<u>",
+            "</u>",
+            "<s>",
+            "<em>",
+            "</em>",
+            "<strong>",
+            "<big>",
+            "<font face=x>",
+            "<font size=1>",
+            "</font>",
+            "<nobr>",
+            "</nobr>",
+            "<p>",
+            "</p>",
+            "<div>",
+            "</div>",
+            "<ul>",
+            "<li>",
+            "<rb>",
+            "<dd>",
+            "<h1>",
+            "</h2>",
+            "<form>",
+            "</form>",
+            "<button>",
+            "<input type=hidden>",
+            "<img>",
+            "<image>",
+            "<br/>",
+            "</br>",
+            "<hr>",
+            "<ruby>",
+            "<rt>",
+            "<pre>\n",
+            "<listing>",
+            "<isindex>",
+            "<keygen>",
+            "<dialog>",
+            "text",
+            " ",
+            "\n",
+            "\r\n",
+            "\0",
+            "&",
+            "&amp",
+            "&#",
+            "&#x110000;",
+            "&#0;",
+            "&notin",
+            "&notit;",
+            "<",
+            ">",
+            "</",
+            "<a href='",
+            "\"",
+            "'",
+            "=",
+            "<x y z=>",
+            "<p/ >",
+            "</ p>",
+            "<\u{fffd}>",
+            "\u{1f600}",
+        ];
+
+        // Inputs that panicked before, where the adoption agency algorithm
+        // ran its inner loop more than three times.
+        for html in [
+            "<i><b><u><s><em><p></i>x",
+            "<i><b><font face=x><b><rb><dd></i>",
+            "<a href=x><i><font size=1><foreignObject><ruby><ul><a>",
+        ] {
+            Dom::parse_lossy(html, &mut NodeArena::new());
+            Dom::parse_fragment(html, "ul");
+        }
+
+        // A small xorshift generator keeps the inputs the same between runs.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..5000 {
+            let length = next() % 64;
+            let html: String = (0..length)
+                .map(|_| FRAGMENTS[(next() % FRAGMENTS.len() as u64) as usize])
+                .collect();
+            Dom::parse_lossy(&html, &mut NodeArena::new());
+            Dom::parse_fragment(&html, ["body", "ul", "td"][(next() % 3) as usize]);
+
+            let bytes: Vec<u8> = (0..length * 4).map(|_| next() as u8).collect();
+            Dom::parse_lossy_bytes(&bytes, &mut NodeArena::new());
+        }
+    }
+
+    #[test]
+    fn parses_an_empty_string() {
+        let mut arena = NodeArena::new();
+        let document = Dom::parse("", &mut arena);
+        assert_eq!(
+            arena.inner_html(document),
+            "<html><head></head><body></body></html>"
+        );
+    }
+
+    #[test]
+    fn parses_documents_one_after_another() {
+        let first = Dom::parse_owned("<p>first");
+        let second = Dom::parse_owned("<p>second");
+        assert_eq!(first.arena().text_content(first.document()), "first");
+        assert_eq!(second.arena().text_content(second.document()), "second");
+
+        // The pooled arena is cleared between documents.
+        for text in ["first", "second"] {
+            let html = Dom::parse_pooled(&format!("<p>{text}"), |dom| {
+                dom.arena().inner_html(dom.document())
+            });
+            assert_eq!(
+                html,
+                format!("<html><head></head><body><p>{text}</p></body></html>")
+            );
+        }
+    }
+
+    #[test]
+    fn returns_the_id_of_each_parsed_document() {
+        // The two documents are structurally equal, but have their own ids.
+        let mut arena = NodeArena::new();
+        let first = Dom::parse("<p>same", &mut arena);
+        let second = Dom::parse("<p>same", &mut arena);
+        assert_ne!(first, second);
+        assert!(arena.nodes_structurally_equal(first, second));
+
+        for document in [first, second] {
+            assert!(arena.get_node(document).is_document());
+            assert_eq!(arena.get_node(document).node_document(), document);
+            let html = arena.document_element(document).unwrap();
+            assert_eq!(arena.get_node(html).parent(), Some(document));
+            assert_eq!(arena.get_node(html).node_document(), document);
+        }
     }
 }
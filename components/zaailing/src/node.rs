@@ -1,18 +1,30 @@
 use crate::arena::{NodeArena, NodeId};
-use crate::parser::Namespace;
+use crate::parser::{Namespace, SPECIAL_MATHML_TAGS, SPECIAL_SVG_TAGS, SPECIAL_TAGS};
+
+/// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+pub static VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "keygen", "link", "meta", "param",
+    "source", "track", "wbr",
+];
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum NodeKind {
-    Document,
+    Document {
+        mode: DocumentMode,
+    },
     Element {
         namespace_uri: Option<String>,
         prefix: Option<String>,
         local_name: String,
         tag_name: String,
+        attributes: Vec<Attribute>,
     },
     Text {
         data: String,
     },
+    Comment {
+        data: String,
+    },
     DocumentType {
         name: String,
         public_id: String,
@@ -20,12 +32,51 @@ pub enum NodeKind {
     },
 }
 
+/// https://dom.spec.whatwg.org/#concept-document-mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocumentMode {
+    #[default]
+    NoQuirks,
+    Quirks,
+    LimitedQuirks,
+}
+
+/// https://dom.spec.whatwg.org/#concept-attribute
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub namespace_uri: Option<String>,
+    pub prefix: Option<String>,
+    pub local_name: String,
+    pub value: String,
+}
+
+impl Attribute {
+    pub fn new(local_name: String, value: String) -> Self {
+        Self {
+            namespace_uri: None,
+            prefix: None,
+            local_name,
+            value,
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-attribute-qualified-name
+    pub fn qualified_name(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}:{}", prefix, self.local_name),
+            None => self.local_name.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Node {
     pub kind: NodeKind,
     pub(crate) document: Option<NodeId>,
     pub(crate) children: Vec<NodeId>,
     pub(crate) parent: Option<NodeId>,
+    pub(crate) previous_sibling: Option<NodeId>,
+    pub(crate) next_sibling: Option<NodeId>,
 }
 
 impl Node {
@@ -46,20 +97,27 @@ impl Node {
                 prefix,
                 local_name: local_name.clone(),
                 tag_name: local_name,
+                attributes: vec![],
             },
             document: Some(document),
             children: vec![],
             parent: None,
+            previous_sibling: None,
+            next_sibling: None,
         }
     }
 
     pub fn create_document() -> Self {
         // TODO: This is not spec compliant
         Self {
-            kind: NodeKind::Document,
+            kind: NodeKind::Document {
+                mode: DocumentMode::NoQuirks,
+            },
             document: None,
             children: vec![],
             parent: None,
+            previous_sibling: None,
+            next_sibling: None,
         }
     }
 
@@ -69,6 +127,19 @@ impl Node {
             document: Some(document),
             children: vec![],
             parent: None,
+            previous_sibling: None,
+            next_sibling: None,
+        }
+    }
+
+    pub fn create_comment(document: NodeId, data: String) -> Self {
+        Self {
+            kind: NodeKind::Comment { data },
+            document: Some(document),
+            children: vec![],
+            parent: None,
+            previous_sibling: None,
+            next_sibling: None,
         }
     }
 
@@ -87,6 +158,8 @@ impl Node {
             document: Some(document),
             children: vec![],
             parent: None,
+            previous_sibling: None,
+            next_sibling: None,
         }
     }
 
@@ -98,15 +171,29 @@ impl Node {
         self.parent
     }
 
-    pub fn node_document(&self, arena: &NodeArena) -> NodeId {
-        match self.document {
-            Some(document) => document,
-            None => arena.get_node_id(self),
-        }
+    /// https://dom.spec.whatwg.org/#concept-node-document
+    pub fn node_document(&self) -> NodeId {
+        self.document
+            .expect("Nodes created in an arena should always have a node document")
     }
 
     pub fn is_document(&self) -> bool {
-        self.kind == NodeKind::Document
+        matches!(self.kind, NodeKind::Document { .. })
+    }
+
+    /// Returns the mode of this node if it is a document, which the parser
+    /// sets based on the DOCTYPE.
+    pub fn document_mode(&self) -> Option<DocumentMode> {
+        match self.kind {
+            NodeKind::Document { mode } => Some(mode),
+            _ => None,
+        }
+    }
+
+    pub fn set_document_mode(&mut self, document_mode: DocumentMode) {
+        if let NodeKind::Document { mode } = &mut self.kind {
+            *mode = document_mode;
+        }
     }
 
     pub fn is_element(&self) -> bool {
@@ -137,18 +224,241 @@ impl Node {
         false
     }
 
+    /// The element's qualified name, which is its local name prefixed by its
+    /// namespace prefix and a colon if it has one. Returns an empty string
+    /// for nodes that aren't elements.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-element-qualified-name
+    pub fn qualified_name(&self) -> String {
+        match &self.kind {
+            NodeKind::Element {
+                prefix: Some(prefix),
+                local_name,
+                ..
+            } => format!("{}:{}", prefix, local_name),
+            NodeKind::Element { local_name, .. } => local_name.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Whether this node is an HTML element that can't have any contents.
+    /// Void elements only have a start tag; end tags must not be specified.
+    ///
+    /// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+    pub fn is_void_element(&self) -> bool {
+        self.is_element_in_namespace(Namespace::Html)
+            && self.is_element_with_one_of_tag_names(VOID_ELEMENTS)
+    }
+
+    /// Whether this node is an element in the special parsing category, which
+    /// the tree construction stage treats differently when closing elements.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#special
+    pub fn is_special(&self) -> bool {
+        (self.is_element_in_namespace(Namespace::Html)
+            && self.is_element_with_one_of_tag_names(SPECIAL_TAGS))
+            || (self.is_element_in_namespace(Namespace::MathMl)
+                && self.is_element_with_one_of_tag_names(SPECIAL_MATHML_TAGS))
+            || (self.is_element_in_namespace(Namespace::Svg)
+                && self.is_element_with_one_of_tag_names(SPECIAL_SVG_TAGS))
+    }
+
+    /// Whether this node is a MathML text integration point, in which text
+    /// and most start tags are parsed as HTML again.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#mathml-text-integration-point
+    pub fn is_mathml_text_integration_point(&self) -> bool {
+        // A node is a MathML text integration point if it is one of the
+        // following elements: A MathML mi element, A MathML mo element, A
+        // MathML mn element, A MathML ms element, A MathML mtext element.
+        self.is_element_in_namespace(Namespace::MathMl)
+            && self.is_element_with_one_of_tag_names(&["mi", "mo", "mn", "ms", "mtext"])
+    }
+
+    /// Whether this node is an HTML integration point, in which text and
+    /// start tags are parsed as HTML again.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#html-integration-point
+    pub fn is_html_integration_point(&self) -> bool {
+        // A node is an HTML integration point if it is one of the following
+        // elements:
+        // * A MathML annotation-xml element whose start tag token had an
+        //   attribute with the name "encoding" whose value was an ASCII
+        //   case-insensitive match for the string "text/html"
+        // * A MathML annotation-xml element whose start tag token had an
+        //   attribute with the name "encoding" whose value was an ASCII
+        //   case-insensitive match for the string "application/xhtml+xml"
+        // NOTE: The start tag token isn't kept, but the attributes of the
+        //       element are the ones of its start tag token.
+        if self.is_element_in_namespace(Namespace::MathMl)
+            && self.is_element_with_tag_name("annotation-xml")
+        {
+            return self.get_attribute("encoding").is_some_and(|encoding| {
+                encoding.eq_ignore_ascii_case("text/html")
+                    || encoding.eq_ignore_ascii_case("application/xhtml+xml")
+            });
+        }
+
+        // * An SVG foreignObject element
+        // * An SVG desc element
+        // * An SVG title element
+        self.is_element_in_namespace(Namespace::Svg)
+            && self.is_element_with_one_of_tag_names(&["foreignObject", "desc", "title"])
+    }
+
+    /// Whether this node is a text node whose data consists of nothing but
+    /// ASCII whitespace, such as the indentation between elements.
+    ///
+    /// https://infra.spec.whatwg.org/#ascii-whitespace
+    pub fn is_whitespace_only_text(&self) -> bool {
+        match &self.kind {
+            NodeKind::Text { data } => data.chars().all(|c| c.is_ascii_whitespace()),
+            _ => false,
+        }
+    }
+
+    /// The element's attribute list, in the order the attributes were
+    /// authored. The serializer relies on this order, so it must stay a list
+    /// rather than a map.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-element-attribute
+    pub fn attributes(&self) -> &[Attribute] {
+        match &self.kind {
+            NodeKind::Element { attributes, .. } => attributes,
+            _ => &[],
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-element-attributes-append
+    pub fn append_attribute(&mut self, attribute: Attribute) {
+        // TODO: Handle attribute changes.
+
+        // Append attribute to element’s attribute list.
+        if let NodeKind::Element { attributes, .. } = &mut self.kind {
+            attributes.push(attribute);
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-element-attributes-get-by-name
+    pub fn get_attribute(&self, qualified_name: &str) -> Option<&str> {
+        // TODO: If element is in the HTML namespace and its node document is
+        // an HTML document, then set qualifiedName to qualifiedName in ASCII
+        // lowercase.
+
+        // Return the first attribute in element’s attribute list whose
+        // qualified name is qualifiedName; otherwise null.
+        self.attributes()
+            .iter()
+            .find(|attribute| attribute.qualified_name() == qualified_name)
+            .map(|attribute| attribute.value.as_str())
+    }
+
+    /// The tokens of the `class` attribute, split on ASCII whitespace.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-element-classlist
+    pub fn classes(&self) -> impl Iterator<Item = &str> {
+        self.get_attribute("class")
+            .unwrap_or_default()
+            .split_ascii_whitespace()
+    }
+
+    pub fn has_class(&self, class_name: &str) -> bool {
+        self.classes().any(|class| class == class_name)
+    }
+
+    /// The custom data attributes of the element as `(key, value)` pairs in
+    /// attribute order, where the key is the attribute name without its
+    /// `data-` prefix. Unlike `dataset` in the DOM, keys are not converted to
+    /// camel case.
+    ///
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-dataset
+    pub fn dataset(&self) -> impl Iterator<Item = (&str, &str)> {
+        // For each content attribute on the DOMStringMap's associated
+        // element whose first five characters are the string "data-" and
+        // whose remaining characters (if any) do not include any ASCII upper
+        // alphas, in the order that those attributes are listed in the
+        // element's attribute list, add a name-value pair to list whose name
+        // is the attribute's name with the first five characters removed and
+        // whose value is the attribute's value.
+        self.attributes()
+            .iter()
+            .filter(|attribute| attribute.namespace_uri.is_none())
+            .filter_map(|attribute| {
+                let key = attribute.local_name.strip_prefix("data-")?;
+                if key.chars().any(|c| c.is_ascii_uppercase()) {
+                    return None;
+                }
+                Some((key, attribute.value.as_str()))
+            })
+    }
+
+    /// The number of columns spanned by a `col` or `colgroup` element, from
+    /// its `span` attribute. Like the `span` IDL attribute, this is 1 when the
+    /// attribute is missing or invalid, and clamped to the range 1 to 1000.
+    ///
+    /// https://html.spec.whatwg.org/multipage/tables.html#dom-colgroup-span
+    pub fn col_span(&self) -> u32 {
+        self.get_attribute("span")
+            .and_then(parse_non_negative_integer)
+            .map_or(1, |span| span.clamp(1, 1000))
+    }
+
+    /// A short, selector-like description of the node for logs and
+    /// debugging, e.g. `div#main.container` for an element. Other nodes are
+    /// described by their node name, like `#text`.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-node-nodename
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            NodeKind::Element { .. } => {
+                let mut description = self.qualified_name();
+                if let Some(id) = self.get_attribute("id").filter(|id| !id.is_empty()) {
+                    description.push('#');
+                    description.push_str(id);
+                }
+                for class in self.classes() {
+                    description.push('.');
+                    description.push_str(class);
+                }
+                description
+            }
+            NodeKind::Document { .. } => "#document".to_string(),
+            NodeKind::Text { .. } => "#text".to_string(),
+            NodeKind::Comment { .. } => "#comment".to_string(),
+            NodeKind::DocumentType { name, .. } => name.clone(),
+        }
+    }
+
     pub fn dump(&self, arena: &NodeArena) {
-        self.internal_dump(arena, 0);
+        self.internal_dump(arena, 0, &mut std::io::stdout().lock())
+            .expect("failed printing to stdout");
     }
 
-    fn internal_dump(&self, arena: &NodeArena, indent: NodeId) {
-        let indent_string = " ".repeat(indent * 2);
+    fn internal_dump(
+        &self,
+        arena: &NodeArena,
+        indent: usize,
+        output: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        writeln!(output, "{}{}", " ".repeat(indent * 2), self)?;
+
+        // Walk the tree with an explicit stack instead of recursing, so deeply
+        // nested documents can't overflow the call stack. Children are pushed
+        // in reverse to pop them in tree order.
+        let mut stack: Vec<(NodeId, usize)> = self
+            .children
+            .iter()
+            .rev()
+            .map(|child| (*child, indent + 1))
+            .collect();
 
-        println!("{indent_string}{}", self);
-        for child in self.children.iter() {
-            let child = arena.get_node(*child);
-            child.internal_dump(arena, indent + 1);
+        while let Some((node, indent)) = stack.pop() {
+            let node = arena.get_node(node);
+            writeln!(output, "{}{}", " ".repeat(indent * 2), node)?;
+            stack.extend(node.children.iter().rev().map(|child| (*child, indent + 1)));
         }
+
+        Ok(())
     }
 }
 
@@ -159,7 +469,7 @@ impl std::fmt::Display for Node {
         let reset = "\x1b[0m";
 
         match &self.kind {
-            NodeKind::Document => write!(f, "Document"),
+            NodeKind::Document { .. } => write!(f, "Document"),
             NodeKind::Element { tag_name, .. } => write!(f, "{yellow}<{}>{reset}", tag_name),
             NodeKind::Text { data } => {
                 let data = data.replace("\r", "\\r");
@@ -168,7 +478,187 @@ impl std::fmt::Display for Node {
                 let data = data.replace(" ", "\u{00B7}");
                 write!(f, "#text {white}{}{reset}", data.trim())
             }
+            NodeKind::Comment { data } => write!(f, "<!-- {} -->", data),
             NodeKind::DocumentType { name, .. } => write!(f, "<!DOCTYPE {}>", name),
         }
     }
 }
+
+/// Parses `input` with the rules for parsing non-negative integers: leading
+/// ASCII whitespace is skipped, and the number ends at the first character
+/// that is not an ASCII digit. Values that don't fit in a `u32` saturate.
+///
+/// https://html.spec.whatwg.org/multipage/common-microsyntaxes.html#rules-for-parsing-non-negative-integers
+fn parse_non_negative_integer(input: &str) -> Option<u32> {
+    // Skip ASCII whitespace within input given position.
+    let input = input.trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+    // If the character indicated by position is a U+002B PLUS SIGN character
+    // (+), advance position to the next character. A U+002D HYPHEN-MINUS
+    // character (-) is only allowed for a value of zero.
+    let (negative, input) = match input.as_bytes().first() {
+        Some(b'-') => (true, &input[1..]),
+        Some(b'+') => (false, &input[1..]),
+        _ => (false, input),
+    };
+
+    // If the character indicated by position is not an ASCII digit, then
+    // return an error.
+    let digits = input
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(input, |end| &input[..end]);
+    if digits.is_empty() {
+        return None;
+    }
+
+    // Collect a sequence of code points that are ASCII digits from input given
+    // position, and interpret the resulting sequence as a base-ten integer.
+    let value = digits.bytes().fold(0u32, |value, digit| {
+        value
+            .saturating_mul(10)
+            .saturating_add(u32::from(digit - b'0'))
+    });
+
+    // If value is less than zero, then return an error.
+    if negative && value != 0 {
+        return None;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(attributes: &[(&str, &str)]) -> Node {
+        let mut arena = NodeArena::new();
+        let document = arena.create_node(Node::create_document());
+        let mut element = Node::create_element(
+            document,
+            "div".to_string(),
+            Namespace::Html,
+            None,
+            None,
+            false,
+        );
+        for (name, value) in attributes {
+            element.append_attribute(Attribute::new(name.to_string(), value.to_string()));
+        }
+        element
+    }
+
+    #[test]
+    fn classes_are_split_on_ascii_whitespace() {
+        let element = element(&[("class", "  foo   bar ")]);
+        assert_eq!(element.classes().collect::<Vec<_>>(), ["foo", "bar"]);
+        assert!(element.has_class("foo"));
+        assert!(element.has_class("bar"));
+        assert!(!element.has_class("foo bar"));
+        assert!(!element.has_class(""));
+    }
+
+    #[test]
+    fn dump_handles_deep_nesting() {
+        let mut arena = NodeArena::new();
+        let mut node = arena.create_element("div");
+        for _ in 0..50_000 {
+            let parent = arena.create_element("div");
+            arena.append_child(parent, node).unwrap();
+            node = parent;
+        }
+        // Write to a sink, the indentation alone would be gigabytes of output.
+        arena
+            .get_node(node)
+            .internal_dump(&arena, 0, &mut std::io::sink())
+            .unwrap();
+    }
+
+    #[test]
+    fn qualified_name_includes_the_prefix() {
+        let mut arena = NodeArena::new();
+        let document = arena.create_node(Node::create_document());
+        let prefixed = Node::create_element(
+            document,
+            "rect".to_string(),
+            Namespace::Svg,
+            Some("svg".to_string()),
+            None,
+            false,
+        );
+        assert_eq!(prefixed.qualified_name(), "svg:rect");
+        assert_eq!(element(&[]).qualified_name(), "div");
+
+        let attribute = Attribute {
+            namespace_uri: Some(Namespace::XLink.url().to_string()),
+            prefix: Some("xlink".to_string()),
+            local_name: "href".to_string(),
+            value: "#a".to_string(),
+        };
+        assert_eq!(attribute.qualified_name(), "xlink:href");
+    }
+
+    #[test]
+    fn special_elements_depend_on_the_namespace() {
+        let mut arena = NodeArena::new();
+        let document = arena.create_node(Node::create_document());
+        let create = |local_name: &str, namespace| {
+            Node::create_element(
+                document,
+                local_name.to_string(),
+                namespace,
+                None,
+                None,
+                false,
+            )
+        };
+
+        assert!(create("div", Namespace::Html).is_special());
+        assert!(!create("span", Namespace::Html).is_special());
+        assert!(create("foreignObject", Namespace::Svg).is_special());
+        assert!(!create("div", Namespace::Svg).is_special());
+        assert!(create("mi", Namespace::MathMl).is_special());
+    }
+
+    #[test]
+    fn describes_elements_with_id_and_classes() {
+        assert_eq!(
+            element(&[("id", "x"), ("class", "a b")]).describe(),
+            "div#x.a.b"
+        );
+        assert_eq!(element(&[("id", "")]).describe(), "div");
+
+        let mut arena = NodeArena::new();
+        let document = arena.create_node(Node::create_document());
+        assert_eq!(
+            Node::create_text(document, "x".to_string()).describe(),
+            "#text"
+        );
+        assert_eq!(arena.get_node(document).describe(), "#document");
+    }
+
+    #[test]
+    fn dataset_strips_the_data_prefix() {
+        let element = element(&[
+            ("data-id", "42"),
+            ("title", "x"),
+            ("data-role", "x"),
+            ("data-Upper", "y"),
+        ]);
+        assert_eq!(
+            element.dataset().collect::<Vec<_>>(),
+            [("id", "42"), ("role", "x")]
+        );
+    }
+
+    #[test]
+    fn col_span_defaults_and_clamps() {
+        assert_eq!(element(&[]).col_span(), 1);
+        assert_eq!(element(&[("span", "3")]).col_span(), 3);
+        assert_eq!(element(&[("span", " +4px")]).col_span(), 4);
+        assert_eq!(element(&[("span", "0")]).col_span(), 1);
+        assert_eq!(element(&[("span", "-2")]).col_span(), 1);
+        assert_eq!(element(&[("span", "wide")]).col_span(), 1);
+        assert_eq!(element(&[("span", "1001")]).col_span(), 1000);
+        assert_eq!(element(&[("span", "99999999999999")]).col_span(), 1000);
+    }
+}
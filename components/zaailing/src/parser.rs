@@ -1,19 +1,294 @@
 use crate::arena::{NodeArena, NodeId};
-use crate::node::{Node, NodeKind};
+use crate::node::{Attribute, DocumentMode, Node, NodeKind};
 use crate::tokenizer::{self, Token};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Namespace {
     Html,
+    MathMl,
+    Svg,
+    XLink,
+    Xml,
+    Xmlns,
 }
 
 impl Namespace {
+    /// https://infra.spec.whatwg.org/#namespaces
     pub fn url(&self) -> &str {
         match self {
             Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::XLink => "http://www.w3.org/1999/xlink",
+            Namespace::Xml => "http://www.w3.org/XML/1998/namespace",
+            Namespace::Xmlns => "http://www.w3.org/2000/xmlns/",
         }
     }
 }
 
+/// The tag names of SVG elements that are not all lowercase, indexed by their
+/// lowercased name as emitted by the tokenizer.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+pub static SVG_TAG_NAME_ADJUSTMENTS: &[(&str, &str)] = &[
+    ("altglyph", "altGlyph"),
+    ("altglyphdef", "altGlyphDef"),
+    ("altglyphitem", "altGlyphItem"),
+    ("animatecolor", "animateColor"),
+    ("animatemotion", "animateMotion"),
+    ("animatetransform", "animateTransform"),
+    ("clippath", "clipPath"),
+    ("feblend", "feBlend"),
+    ("fecolormatrix", "feColorMatrix"),
+    ("fecomponenttransfer", "feComponentTransfer"),
+    ("fecomposite", "feComposite"),
+    ("feconvolvematrix", "feConvolveMatrix"),
+    ("fediffuselighting", "feDiffuseLighting"),
+    ("fedisplacementmap", "feDisplacementMap"),
+    ("fedistantlight", "feDistantLight"),
+    ("fedropshadow", "feDropShadow"),
+    ("feflood", "feFlood"),
+    ("fefunca", "feFuncA"),
+    ("fefuncb", "feFuncB"),
+    ("fefuncg", "feFuncG"),
+    ("fefuncr", "feFuncR"),
+    ("fegaussianblur", "feGaussianBlur"),
+    ("feimage", "feImage"),
+    ("femerge", "feMerge"),
+    ("femergenode", "feMergeNode"),
+    ("femorphology", "feMorphology"),
+    ("feoffset", "feOffset"),
+    ("fepointlight", "fePointLight"),
+    ("fespecularlighting", "feSpecularLighting"),
+    ("fespotlight", "feSpotLight"),
+    ("fetile", "feTile"),
+    ("feturbulence", "feTurbulence"),
+    ("foreignobject", "foreignObject"),
+    ("glyphref", "glyphRef"),
+    ("lineargradient", "linearGradient"),
+    ("radialgradient", "radialGradient"),
+    ("textpath", "textPath"),
+];
+
+/// The names of SVG attributes that are not all lowercase, indexed by their
+/// lowercased name as emitted by the tokenizer.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#adjust-svg-attributes
+pub static SVG_ATTRIBUTE_ADJUSTMENTS: &[(&str, &str)] = &[
+    ("attributename", "attributeName"),
+    ("attributetype", "attributeType"),
+    ("basefrequency", "baseFrequency"),
+    ("baseprofile", "baseProfile"),
+    ("calcmode", "calcMode"),
+    ("clippathunits", "clipPathUnits"),
+    ("diffuseconstant", "diffuseConstant"),
+    ("edgemode", "edgeMode"),
+    ("filterunits", "filterUnits"),
+    ("glyphref", "glyphRef"),
+    ("gradienttransform", "gradientTransform"),
+    ("gradientunits", "gradientUnits"),
+    ("kernelmatrix", "kernelMatrix"),
+    ("kernelunitlength", "kernelUnitLength"),
+    ("keypoints", "keyPoints"),
+    ("keysplines", "keySplines"),
+    ("keytimes", "keyTimes"),
+    ("lengthadjust", "lengthAdjust"),
+    ("limitingconeangle", "limitingConeAngle"),
+    ("markerheight", "markerHeight"),
+    ("markerunits", "markerUnits"),
+    ("markerwidth", "markerWidth"),
+    ("maskcontentunits", "maskContentUnits"),
+    ("maskunits", "maskUnits"),
+    ("numoctaves", "numOctaves"),
+    ("pathlength", "pathLength"),
+    ("patterncontentunits", "patternContentUnits"),
+    ("patterntransform", "patternTransform"),
+    ("patternunits", "patternUnits"),
+    ("pointsatx", "pointsAtX"),
+    ("pointsaty", "pointsAtY"),
+    ("pointsatz", "pointsAtZ"),
+    ("preservealpha", "preserveAlpha"),
+    ("preserveaspectratio", "preserveAspectRatio"),
+    ("primitiveunits", "primitiveUnits"),
+    ("refx", "refX"),
+    ("refy", "refY"),
+    ("repeatcount", "repeatCount"),
+    ("repeatdur", "repeatDur"),
+    ("requiredextensions", "requiredExtensions"),
+    ("requiredfeatures", "requiredFeatures"),
+    ("specularconstant", "specularConstant"),
+    ("specularexponent", "specularExponent"),
+    ("spreadmethod", "spreadMethod"),
+    ("startoffset", "startOffset"),
+    ("stddeviation", "stdDeviation"),
+    ("stitchtiles", "stitchTiles"),
+    ("surfacescale", "surfaceScale"),
+    ("systemlanguage", "systemLanguage"),
+    ("tablevalues", "tableValues"),
+    ("targetx", "targetX"),
+    ("targety", "targetY"),
+    ("textlength", "textLength"),
+    ("viewbox", "viewBox"),
+    ("viewtarget", "viewTarget"),
+    ("xchannelselector", "xChannelSelector"),
+    ("ychannelselector", "yChannelSelector"),
+    ("zoomandpan", "zoomAndPan"),
+];
+
+/// The attribute names that are split into a prefix and local name in a
+/// namespace on foreign elements, as (attribute name, prefix, local name,
+/// namespace).
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#adjust-foreign-attributes
+pub static FOREIGN_ATTRIBUTE_ADJUSTMENTS: &[(&str, Option<&str>, &str, Namespace)] = &[
+    ("xlink:actuate", Some("xlink"), "actuate", Namespace::XLink),
+    ("xlink:arcrole", Some("xlink"), "arcrole", Namespace::XLink),
+    ("xlink:href", Some("xlink"), "href", Namespace::XLink),
+    ("xlink:role", Some("xlink"), "role", Namespace::XLink),
+    ("xlink:show", Some("xlink"), "show", Namespace::XLink),
+    ("xlink:title", Some("xlink"), "title", Namespace::XLink),
+    ("xlink:type", Some("xlink"), "type", Namespace::XLink),
+    ("xml:lang", Some("xml"), "lang", Namespace::Xml),
+    ("xml:space", Some("xml"), "space", Namespace::Xml),
+    ("xmlns", None, "xmlns", Namespace::Xmlns),
+    ("xmlns:xlink", Some("xmlns"), "xlink", Namespace::Xmlns),
+];
+
+/// The public identifier prefixes that put a document in quirks mode.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+static QUIRKS_PUBLIC_IDENTIFIER_PREFIXES: &[&str] = &[
+    "+//silmaril//dtd html pro v0r11 19970101//",
+    "-//as//dtd html 3.0 aswedit + extensions//",
+    "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+    "-//ietf//dtd html 2.0 level 1//",
+    "-//ietf//dtd html 2.0 level 2//",
+    "-//ietf//dtd html 2.0 strict level 1//",
+    "-//ietf//dtd html 2.0 strict level 2//",
+    "-//ietf//dtd html 2.0 strict//",
+    "-//ietf//dtd html 2.0//",
+    "-//ietf//dtd html 2.1e//",
+    "-//ietf//dtd html 3.0//",
+    "-//ietf//dtd html 3.2 final//",
+    "-//ietf//dtd html 3.2//",
+    "-//ietf//dtd html 3//",
+    "-//ietf//dtd html level 0//",
+    "-//ietf//dtd html level 1//",
+    "-//ietf//dtd html level 2//",
+    "-//ietf//dtd html level 3//",
+    "-//ietf//dtd html strict level 0//",
+    "-//ietf//dtd html strict level 1//",
+    "-//ietf//dtd html strict level 2//",
+    "-//ietf//dtd html strict level 3//",
+    "-//ietf//dtd html strict//",
+    "-//ietf//dtd html//",
+    "-//metrius//dtd metrius presentational//",
+    "-//microsoft//dtd internet explorer 2.0 html strict//",
+    "-//microsoft//dtd internet explorer 2.0 html//",
+    "-//microsoft//dtd internet explorer 2.0 tables//",
+    "-//microsoft//dtd internet explorer 3.0 html strict//",
+    "-//microsoft//dtd internet explorer 3.0 html//",
+    "-//microsoft//dtd internet explorer 3.0 tables//",
+    "-//netscape comm. corp.//dtd html//",
+    "-//netscape comm. corp.//dtd strict html//",
+    "-//o'reilly and associates//dtd html 2.0//",
+    "-//o'reilly and associates//dtd html extended 1.0//",
+    "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+    "-//sq//dtd html 2.0 hotmetal + extensions//",
+    "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+    "-//softquad//dtd hotmetal pro 4.0::19971010::extensions to html 4.0//",
+    "-//spyglass//dtd html 2.0 extended//",
+    "-//sun microsystems corp.//dtd hotjava html//",
+    "-//sun microsystems corp.//dtd hotjava strict html//",
+    "-//w3c//dtd html 3 1995-03-24//",
+    "-//w3c//dtd html 3.2 draft//",
+    "-//w3c//dtd html 3.2 final//",
+    "-//w3c//dtd html 3.2//",
+    "-//w3c//dtd html 3.2s draft//",
+    "-//w3c//dtd html 4.0 frameset//",
+    "-//w3c//dtd html 4.0 transitional//",
+    "-//w3c//dtd html experimental 19960712//",
+    "-//w3c//dtd html experimental 970421//",
+    "-//w3c//dtd w3 html//",
+    "-//w3o//dtd w3 html 3.0//",
+    "-//webtechs//dtd mozilla html 2.0//",
+    "-//webtechs//dtd mozilla html//",
+];
+
+/// Returns the mode a document should be set to for a DOCTYPE token with the
+/// given fields, as described by the "initial" insertion mode. The
+/// identifiers are compared ASCII case-insensitively.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+fn document_mode_for_doctype(
+    name: &str,
+    public_identifier: Option<&str>,
+    system_identifier: Option<&str>,
+    force_quirks: bool,
+) -> DocumentMode {
+    let public_identifier = public_identifier.map(|id| id.to_ascii_lowercase());
+    let system_identifier = system_identifier.map(|id| id.to_ascii_lowercase());
+    let public_identifier_starts_with = |prefixes: &[&str]| {
+        public_identifier
+            .as_deref()
+            .is_some_and(|id| prefixes.iter().any(|prefix| id.starts_with(prefix)))
+    };
+
+    // The force-quirks flag is set to on.
+    // The name is not "html".
+    // The public identifier is set to: "-//W3O//DTD W3 HTML Strict 3.0//EN//"
+    // The public identifier is set to: "-/W3C/DTD HTML 4.0 Transitional/EN"
+    // The public identifier is set to: "HTML"
+    // The system identifier is set to:
+    // "http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd"
+    // The public identifier starts with one of the quirks prefixes.
+    // The system identifier is missing and the public identifier starts with:
+    // "-//W3C//DTD HTML 4.01 Frameset//" or
+    // "-//W3C//DTD HTML 4.01 Transitional//"
+    if force_quirks
+        || name != "html"
+        || matches!(
+            public_identifier.as_deref(),
+            Some(
+                "-//w3o//dtd w3 html strict 3.0//en//"
+                    | "-/w3c/dtd html 4.0 transitional/en"
+                    | "html"
+            )
+        )
+        || system_identifier.as_deref()
+            == Some("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        || public_identifier_starts_with(QUIRKS_PUBLIC_IDENTIFIER_PREFIXES)
+        || system_identifier.is_none()
+            && public_identifier_starts_with(&[
+                "-//w3c//dtd html 4.01 frameset//",
+                "-//w3c//dtd html 4.01 transitional//",
+            ])
+    {
+        return DocumentMode::Quirks;
+    }
+
+    // The public identifier starts with: "-//W3C//DTD XHTML 1.0 Frameset//"
+    // The public identifier starts with: "-//W3C//DTD XHTML 1.0 Transitional//"
+    // The system identifier is not missing and the public identifier starts
+    // with: "-//W3C//DTD HTML 4.01 Frameset//" or
+    // "-//W3C//DTD HTML 4.01 Transitional//"
+    if public_identifier_starts_with(&[
+        "-//w3c//dtd xhtml 1.0 frameset//",
+        "-//w3c//dtd xhtml 1.0 transitional//",
+    ]) || system_identifier.is_some()
+        && public_identifier_starts_with(&[
+            "-//w3c//dtd html 4.01 frameset//",
+            "-//w3c//dtd html 4.01 transitional//",
+        ])
+    {
+        return DocumentMode::LimitedQuirks;
+    }
+
+    DocumentMode::NoQuirks
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum InsertionMode {
@@ -74,64 +349,749 @@ pub enum ParsingAlgorithm {
     RcData,
 }
 
+/// Called for every recoverable parse error, see [ParserOptions::on_error].
+pub type ErrorCallback = Box<dyn FnMut(&ParseError) -> ControlFlow<()>>;
+
+#[derive(Default)]
+pub struct ParserOptions {
+    /// The maximum number of elements that can be open at the same time. When
+    /// a document nests deeper than this, parsing is aborted with
+    /// [ParseError::MaxDepthExceeded]. `None` means there is no limit.
+    pub max_depth: Option<usize>,
+    /// Whether the document is parsed as if scripting is enabled. This
+    /// changes how `<noscript>` elements are parsed: with scripting enabled
+    /// their contents are raw text, otherwise they are parsed as markup.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#scripting-flag
+    pub scripting: bool,
+    /// Parse the contents of `<noscript>` elements as markup even when
+    /// [ParserOptions::scripting] is enabled, for consumers that want the
+    /// fallback content as a DOM tree.
+    ///
+    /// This deviates from the specification, which parses `<noscript>` as
+    /// raw text when scripting is enabled, so the resulting tree may differ
+    /// from what a browser builds.
+    pub noscript_as_markup: bool,
+    /// Called for every recoverable parse error as soon as it is found. When
+    /// the callback returns [ControlFlow::Break], parsing is aborted with
+    /// [ParseError::Aborted]. Without a callback, parse errors are ignored.
+    pub on_error: Option<ErrorCallback>,
+    /// The maximum number of errors that are reported to
+    /// [ParserOptions::on_error]. Later errors are only counted, and reported
+    /// as a single [ParseError::TooManyErrors] once parsing stops, so
+    /// pathological documents can't produce an unbounded amount of errors.
+    /// `None` means there is no limit.
+    ///
+    /// To stop parsing once the limit is hit instead, count the errors in
+    /// the callback and return [ControlFlow::Break] for the last one.
+    pub max_errors: Option<usize>,
+    /// Whether [ParseError::Recoverable] errors include the stack of open
+    /// elements at the point the error was found, to help find out why a
+    /// document ended up nested differently than expected.
+    pub report_open_elements: bool,
+}
+
+impl std::fmt::Debug for ParserOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParserOptions")
+            .field("max_depth", &self.max_depth)
+            .field("scripting", &self.scripting)
+            .field("noscript_as_markup", &self.noscript_as_markup)
+            .field("on_error", &self.on_error.as_ref().map(|_| ".."))
+            .field("max_errors", &self.max_errors)
+            .field("report_open_elements", &self.report_open_elements)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// More elements were open at the same time than
+    /// [ParserOptions::max_depth] allows. Parsing stops as soon as the limit
+    /// is exceeded. The nodes that were inserted up to that point stay in the
+    /// arena as a partial tree, but no end tags are implied for the open
+    /// elements and the document is not otherwise finished.
+    MaxDepthExceeded { max_depth: usize },
+    /// A parse error as defined by the HTML standard. The parser recovers
+    /// from these, so they are only reported to [ParserOptions::on_error].
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    ///
+    /// When [ParserOptions::report_open_elements] is enabled,
+    /// `open_elements` contains the qualified names of the elements on the
+    /// stack of open elements when the error was found, from the `html`
+    /// element to the current node. Otherwise it is empty.
+    Recoverable {
+        message: String,
+        open_elements: Vec<String>,
+    },
+    /// Parsing was aborted because [ParserOptions::on_error] returned
+    /// [ControlFlow::Break].
+    Aborted,
+    /// The input uses a feature that the parser does not implement yet. The
+    /// parser recovers like it does from a parse error, so the resulting tree
+    /// may differ from the one a browser would build.
+    Unsupported { feature: String },
+    /// More errors were found than [ParserOptions::max_errors] allows.
+    /// `omitted` is the number of errors that were not reported.
+    TooManyErrors { omitted: usize },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::MaxDepthExceeded { max_depth } => {
+                write!(f, "Maximum nesting depth of {max_depth} exceeded")
+            }
+            ParseError::Recoverable {
+                message,
+                open_elements,
+            } => {
+                write!(f, "{message}")?;
+                if !open_elements.is_empty() {
+                    write!(f, " (open elements: {})", open_elements.join(" > "))?;
+                }
+                Ok(())
+            }
+            ParseError::Aborted => write!(f, "Parsing was aborted by the error callback"),
+            ParseError::Unsupported { feature } => write!(f, "Unsupported: {feature}"),
+            ParseError::TooManyErrors { omitted } => {
+                write!(f, "Too many errors, {omitted} more were omitted")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Returns the local name for an element in `namespace` created for a token
+/// with `tag_name`. The tokenizer lowercases tag names, so the mixed-case
+/// names of SVG elements like `feBlend` are restored here.
+fn adjust_svg_tag_name(namespace: Namespace, tag_name: &str) -> String {
+    // If the adjusted current node is an element in the SVG namespace, and the
+    // token's tag name is one of the ones in the first column of the following
+    // table, change the tag name to the name given in the corresponding cell
+    // in the second column.
+    if namespace == Namespace::Svg {
+        if let Some((_, adjusted)) = SVG_TAG_NAME_ADJUSTMENTS
+            .iter()
+            .find(|(lowercase, _)| *lowercase == tag_name)
+        {
+            return adjusted.to_string();
+        }
+    }
+    tag_name.to_string()
+}
+
+/// Creates the attribute for a token's attribute on an element in
+/// `namespace`. For elements in the MathML and SVG namespaces the attribute
+/// is adjusted like the tree construction stage adjusts the token before
+/// inserting a foreign element.
+fn adjust_foreign_attributes(namespace: Namespace, attribute: &tokenizer::Attribute) -> Attribute {
+    let mut name = attribute.name.as_str();
+
+    match namespace {
+        Namespace::Html => return Attribute::new(name.to_string(), attribute.value.clone()),
+        // https://html.spec.whatwg.org/multipage/parsing.html#adjust-mathml-attributes
+        // When the steps below require the user agent to adjust MathML
+        // attributes for a token, then, if the token has an attribute named
+        // definitionurl, change its name to definitionURL (note the case
+        // difference).
+        Namespace::MathMl if name == "definitionurl" => name = "definitionURL",
+        // https://html.spec.whatwg.org/multipage/parsing.html#adjust-svg-attributes
+        // When the steps below require the user agent to adjust SVG attributes
+        // for a token, then, for each attribute on the token whose attribute
+        // name is one of the ones in the first column of the following table,
+        // change the attribute's name to the name given in the corresponding
+        // cell in the second column.
+        Namespace::Svg => {
+            if let Some((_, adjusted)) = SVG_ATTRIBUTE_ADJUSTMENTS
+                .iter()
+                .find(|(lowercase, _)| *lowercase == name)
+            {
+                name = adjusted;
+            }
+        }
+        _ => {}
+    }
+
+    // When the steps below require the user agent to adjust foreign attributes
+    // for a token, then, if any of the attributes on the token match the
+    // strings given in the first column of the following table, let the
+    // attribute be a namespaced attribute, with the prefix being the string
+    // given in the corresponding cell in the second column, the local name
+    // being the string given in the corresponding cell in the third column,
+    // and the namespace being the namespace given in the corresponding cell in
+    // the fourth column.
+    if let Some((_, prefix, local_name, namespace)) = FOREIGN_ATTRIBUTE_ADJUSTMENTS
+        .iter()
+        .find(|(attribute_name, ..)| *attribute_name == name)
+    {
+        return Attribute {
+            namespace_uri: Some(namespace.url().to_string()),
+            prefix: prefix.map(str::to_string),
+            local_name: local_name.to_string(),
+            value: attribute.value.clone(),
+        };
+    }
+
+    Attribute::new(name.to_string(), attribute.value.clone())
+}
+
 #[derive(Debug)]
 pub struct Parser<'input, 'arena> {
     arena: &'arena mut NodeArena,
     tokenizer: tokenizer::Tokenizer<'input>,
     insertion_mode: InsertionMode,
     original_insertion_mode: InsertionMode,
+    pending_table_character_tokens: Vec<char>,
     should_reprocess_token: bool,
+    should_ignore_next_line_feed: bool,
+    /// Whether the self-closing flag of the token that is currently being
+    /// processed has been acknowledged.
+    self_closing_flag_acknowledged: bool,
     document: NodeId,
     stack_of_open_elements: StackOfOpenElements,
     active_formatting_elements: ActiveFormattingElements,
     head_element: Option<NodeId>,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#form-element-pointer
+    form_element_pointer: Option<NodeId>,
     should_stop_parsing: bool,
+    /// Set when [ParserOptions::on_error] asked to abort parsing.
+    aborted: bool,
+    /// The number of errors found so far, including the ones that were not
+    /// reported because of [ParserOptions::max_errors].
+    error_count: usize,
     scripting: bool,
     frameset_ok: bool,
+    /// Whether nodes that would be inserted into a table, tbody, tfoot, thead
+    /// or tr element are inserted before the last table instead. Only
+    /// enabled while the "in table" and "in table text" insertion modes
+    /// process misplaced content, like the div in `<table><div>x</div>`.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#foster-parent
     foster_parenting: bool,
+    options: ParserOptions,
+    /// The context element when the parser was created as part of the HTML
+    /// fragment parsing algorithm, or `None` when parsing a whole document.
+    context_element: Option<NodeId>,
 }
 
 impl<'input, 'arena> Parser<'input, 'arena> {
-    pub fn new(html: &'input str, arena: &'arena mut NodeArena) -> Self {
+    pub fn new(html: &'input str, arena: &'arena mut NodeArena, options: ParserOptions) -> Self {
+        let document = arena.create_node(Node::create_document());
+        Self::new_with_document(html, arena, document, options)
+    }
+
+    /// Creates a parser that continues tree construction in `document`, which
+    /// may already contain nodes from an earlier parse, instead of creating a
+    /// new document.
+    ///
+    /// As the state of the earlier parser is gone, the stack of open elements
+    /// is re-derived from the document: it gets the document element, and the
+    /// last `body` or `frameset` child of it if there is one. The insertion
+    /// mode is then reset appropriately, so new content ends up at the end of
+    /// the body.
+    pub fn new_into(
+        html: &'input str,
+        arena: &'arena mut NodeArena,
+        document: NodeId,
+        options: ParserOptions,
+    ) -> Self {
+        let mut parser = Self::new_with_document(html, arena, document, options);
+
+        let Some(html_element) = parser.arena.document_element(document) else {
+            // Without a document element, only a DOCTYPE or comments could have
+            // been parsed before. Skip the "initial" insertion mode if there
+            // is anything in the document, so a second DOCTYPE is not
+            // appended.
+            if !parser.arena.get_node(document).children().is_empty() {
+                parser.switch_insertion_mode(InsertionMode::BeforeHtml);
+            }
+            return parser;
+        };
+        parser
+            .stack_of_open_elements
+            .push(parser.arena, html_element);
+
+        let children = parser.arena.get_node(html_element).children().to_vec();
+        parser.head_element = children.iter().copied().find(|child| {
+            parser
+                .arena
+                .get_node(*child)
+                .is_element_with_tag_name("head")
+        });
+        if let Some(body) = children.iter().copied().rev().find(|child| {
+            parser
+                .arena
+                .get_node(*child)
+                .is_element_with_one_of_tag_names(&["body", "frameset"])
+        }) {
+            parser.stack_of_open_elements.push(parser.arena, body);
+            parser.frameset_ok = false;
+        }
+
+        parser.reset_insertion_mode_appropriately();
+        parser
+    }
+
+    fn new_with_document(
+        html: &'input str,
+        arena: &'arena mut NodeArena,
+        document: NodeId,
+        options: ParserOptions,
+    ) -> Self {
         Self {
             tokenizer: tokenizer::Tokenizer::new(html),
             insertion_mode: InsertionMode::Initial,
             original_insertion_mode: InsertionMode::Initial,
+            pending_table_character_tokens: vec![],
             should_reprocess_token: false,
-            document: arena.create_node(Node::create_document()),
+            should_ignore_next_line_feed: false,
+            self_closing_flag_acknowledged: false,
+            document,
             stack_of_open_elements: StackOfOpenElements::new(),
             active_formatting_elements: ActiveFormattingElements::new(),
             head_element: None,
+            form_element_pointer: None,
             should_stop_parsing: false,
-            scripting: false,
+            aborted: false,
+            error_count: 0,
+            scripting: options.scripting,
             frameset_ok: true,
             foster_parenting: false,
+            options,
+            context_element: None,
             arena,
         }
     }
 
-    pub fn parse(mut self) -> Node {
-        while let Some(token) = match self.should_reprocess_token {
-            true => self.tokenizer.peek().cloned(),
-            false => self.tokenizer.next(),
-        } {
+    /// Creates a parser for the HTML fragment parsing algorithm, which parses
+    /// `html` as if it were the contents of `context`.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-parsing-algorithm
+    pub fn new_fragment(
+        html: &'input str,
+        arena: &'arena mut NodeArena,
+        context: NodeId,
+        options: ParserOptions,
+    ) -> Self {
+        // Create a new Document node, and mark it as being an HTML document.
+        // TODO: If context's node document is in quirks mode, then set
+        // document's mode to "quirks". Otherwise, if context's node document
+        // is in limited-quirks mode, then set document's mode to
+        // "limited-quirks".
+
+        // Create a new HTML parser, and associate it with document.
+        let mut parser = Self::new(html, arena, options);
+        parser.context_element = Some(context);
+
+        // Set the state of the HTML parser's tokenization stage as follows,
+        // switching on the context element:
+        let context_node = parser.arena.get_node(context);
+        let state = if context_node.is_element_with_one_of_tag_names(&["title", "textarea"]) {
+            tokenizer::State::RcData
+        } else if context_node
+            .is_element_with_one_of_tag_names(&["style", "xmp", "iframe", "noembed", "noframes"])
+            || (context_node.is_element_with_tag_name("noscript")
+                && parser.parses_noscript_as_raw_text())
+        {
+            tokenizer::State::RawText
+        } else if context_node.is_element_with_tag_name("script") {
+            tokenizer::State::ScriptData
+        } else if context_node.is_element_with_tag_name("plaintext") {
+            tokenizer::State::PlainText
+        } else {
+            tokenizer::State::Data
+        };
+        parser.tokenizer.switch_to(state);
+
+        // Let root be the result of creating an element given document, "html",
+        // and the HTML namespace.
+        let root = parser.arena.create_node(Node::create_element(
+            parser.document,
+            "html".to_string(),
+            Namespace::Html,
+            None,
+            None,
+            false,
+        ));
+
+        // Append root to document.
+        parser.arena.append(root, parser.document);
+
+        // Set up the HTML parser's stack of open elements so that it contains
+        // just the single element root.
+        parser.stack_of_open_elements.push(parser.arena, root);
+
+        // TODO: If context is a template element, then push "in template" onto
+        // the stack of template insertion modes so that it is the new current
+        // template insertion mode.
+
+        // TODO: Create a start tag token whose name is the local name of
+        // context and whose attributes are the attributes of context. Let
+        // this start tag token be the start tag token of context; e.g. for the
+        // purposes of determining if it is an HTML integration point.
+
+        // Reset the parser's insertion mode appropriately.
+        parser.reset_insertion_mode_appropriately();
+
+        // Set the HTML parser's form element pointer to the nearest node to
+        // context that is a form element (going straight up the ancestor
+        // chain, and including the element itself, if it is one), or, if there
+        // is no such form element, to null.
+        let mut node = Some(context);
+        while let Some(current) = node {
+            if parser
+                .arena
+                .get_node(current)
+                .is_element_with_tag_name("form")
+            {
+                parser.form_element_pointer = Some(current);
+                break;
+            }
+            node = parser.arena.get_node(current).parent();
+        }
+
+        parser
+    }
+
+    pub fn parse(mut self) -> Result<NodeId, ParseError> {
+        self.run()?;
+        Ok(self.document)
+    }
+
+    /// Runs the parser created with [Parser::new_fragment] and returns the
+    /// child nodes of root in tree order.
+    pub fn parse_fragment(mut self) -> Result<Vec<NodeId>, ParseError> {
+        self.run()?;
+
+        let root = self
+            .arena
+            .document_element(self.document)
+            .expect("Fragment parsing should always create a root element");
+        Ok(self.arena.get_node(root).children().to_vec())
+    }
+
+    fn run(&mut self) -> Result<(), ParseError> {
+        let result = self.process_tokens();
+
+        if let Some(max_errors) = self.options.max_errors {
+            if self.error_count > max_errors {
+                let omitted = self.error_count - max_errors;
+                self.report(ParseError::TooManyErrors { omitted });
+            }
+        }
+
+        result
+    }
+
+    fn process_tokens(&mut self) -> Result<(), ParseError> {
+        // The token that is currently being processed. It is kept around so
+        // it can be handed to the dispatcher again when an insertion mode
+        // asks for the token to be reprocessed.
+        let mut current_token = None;
+
+        loop {
+            if !self.should_reprocess_token {
+                // The tokenizer needs to know whether the adjusted current node is a
+                // foreign element to tell CDATA sections apart from bogus comments.
+                let adjusted_current_node_is_foreign = !self.stack_of_open_elements.is_empty()
+                    && !self
+                        .arena
+                        .get_node(self.adjusted_current_node())
+                        .is_element_in_namespace(Namespace::Html);
+                self.tokenizer
+                    .set_adjusted_current_node_is_foreign(adjusted_current_node_is_foreign);
+
+                current_token = self.tokenizer.next();
+                self.self_closing_flag_acknowledged = false;
+
+                // Some start tags ask for a line feed right after them to be
+                // ignored.
+                if std::mem::take(&mut self.should_ignore_next_line_feed)
+                    && current_token == Some(Token::Character('\n'))
+                {
+                    continue;
+                }
+            }
+
+            let Some(token) = &current_token else {
+                break;
+            };
+
             if self.should_stop_parsing {
                 break;
             }
 
             self.should_reprocess_token = false;
-            self.dispatch(&token)
+            self.dispatch(token);
+
+            // When a start tag token is emitted with its self-closing flag set,
+            // if the flag is not acknowledged when it is processed by the tree
+            // construction stage, that is a
+            // non-void-html-element-start-tag-with-trailing-solidus parse error.
+            if !self.should_reprocess_token
+                && matches!(
+                    token,
+                    Token::Tag {
+                        start: true,
+                        self_closing: true,
+                        ..
+                    }
+                )
+                && !self.self_closing_flag_acknowledged
+            {
+                self.error("Non-void HTML element start tag with trailing solidus");
+            }
+
+            if self.aborted {
+                return Err(ParseError::Aborted);
+            }
+
+            if let Some(max_depth) = self.options.max_depth {
+                if self.stack_of_open_elements.elements.len() > max_depth {
+                    return Err(ParseError::MaxDepthExceeded { max_depth });
+                }
+            }
         }
 
-        self.arena.get_node(self.document).clone()
+        Ok(())
     }
 
     /// https://html.spec.whatwg.org/multipage/parsing.html#tree-construction-dispatcher
     fn dispatch(&mut self, token: &Token) {
-        if !self.is_in_foreign_content(&token) {
+        if !self.is_in_foreign_content(token) {
             self.process_token(self.insertion_mode, token);
         } else {
-            todo!("Implement foreign content parsing algorithm");
+            self.process_token_in_foreign_content(token);
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+    fn process_token_in_foreign_content(&mut self, token: &Token) {
+        match token {
+            Token::Character('\0') => {
+                // Parse error. Insert a U+FFFD REPLACEMENT CHARACTER character.
+                self.error("Unexpected null character");
+                self.insert_character('\u{FFFD}');
+            }
+            Token::Character(
+                character @ ('\u{0009}' | '\u{000A}' | '\u{000C}' | '\u{000D}' | '\u{0020}'),
+            ) => {
+                // Insert the token's character.
+                self.insert_character(*character);
+            }
+            Token::Character(character) => {
+                // Insert the token's character.
+                self.insert_character(*character);
+
+                // Set the frameset-ok flag to "not ok".
+                self.frameset_ok = false;
+            }
+            Token::Comment { .. } => {
+                // Insert a comment.
+                self.insert_comment(token, None);
+            }
+            Token::Doctype { .. } => {
+                // Parse error. Ignore the token.
+                self.error("Unexpected DOCTYPE");
+            }
+            Token::Tag { attributes, .. }
+                if token.is_start_tag_with_name(&[
+                    "b",
+                    "big",
+                    "blockquote",
+                    "body",
+                    "br",
+                    "center",
+                    "code",
+                    "dd",
+                    "div",
+                    "dl",
+                    "dt",
+                    "em",
+                    "embed",
+                    "h1",
+                    "h2",
+                    "h3",
+                    "h4",
+                    "h5",
+                    "h6",
+                    "head",
+                    "hr",
+                    "i",
+                    "img",
+                    "li",
+                    "listing",
+                    "menu",
+                    "meta",
+                    "nobr",
+                    "ol",
+                    "p",
+                    "pre",
+                    "ruby",
+                    "s",
+                    "small",
+                    "span",
+                    "strong",
+                    "strike",
+                    "sub",
+                    "sup",
+                    "table",
+                    "tt",
+                    "u",
+                    "ul",
+                    "var",
+                ]) || (token.is_start_tag_with_name(&["font"])
+                    && attributes.iter().any(|attribute| {
+                        ["color", "face", "size"].contains(&attribute.name.as_str())
+                    }))
+                    || token.is_end_tag_with_name(&["br", "p"]) =>
+            {
+                // Parse error.
+                self.error("Unexpected HTML tag in foreign content");
+
+                // While the current node is not a MathML text integration
+                // point, an HTML integration point, or an element in the HTML
+                // namespace, pop elements from the stack of open elements.
+                loop {
+                    let current_node = self
+                        .arena
+                        .get_node(self.stack_of_open_elements.current_node());
+                    if current_node.is_mathml_text_integration_point()
+                        || current_node.is_html_integration_point()
+                        || current_node.is_element_in_namespace(Namespace::Html)
+                    {
+                        break;
+                    }
+                    self.stack_of_open_elements.pop();
+                }
+
+                // Reprocess the token according to the rules given in the
+                // section corresponding to the current insertion mode in HTML
+                // content.
+                self.process_token(self.insertion_mode, token);
+            }
+            Token::Tag {
+                tag_name,
+                self_closing,
+                ..
+            } if token.is_start_tag() => {
+                let adjusted_current_node = self.arena.get_node(self.adjusted_current_node());
+                let namespace = if adjusted_current_node.is_element_in_namespace(Namespace::Svg) {
+                    Namespace::Svg
+                } else {
+                    Namespace::MathMl
+                };
+
+                // If the adjusted current node is an element in the MathML
+                // namespace, adjust MathML attributes for the token. (This is
+                // done when the element is created for the token.)
+
+                // If the adjusted current node is an element in the SVG
+                // namespace, and the token's tag name is one of the ones in the
+                // first column of the following table, change the tag name to
+                // the name given in the corresponding cell in the second
+                // column. (This is done when the element is created for the
+                // token.)
+
+                // If the adjusted current node is an element in the SVG
+                // namespace, adjust SVG attributes for the token.
+                // Adjust foreign attributes for the token.
+                // (Both are done when the element is created for the token.)
+
+                // Insert a foreign element for the token, with adjusted current
+                // node's namespace and false.
+                self.insert_foreign_element(token, namespace, false);
+
+                // If the token has its self-closing flag set, then run the
+                // appropriate steps from the following list:
+                if *self_closing {
+                    // If the token's tag name is "script", and the new
+                    // current node is in the SVG namespace:
+                    // Acknowledge the token's self-closing flag, and then act
+                    // as described in the steps for a "script" end tag below.
+                    //
+                    // Otherwise: Pop the current node off the stack of open
+                    // elements and acknowledge the token's self-closing flag.
+                    //
+                    // Scripts are not run, so both come down to the same steps.
+                    self.stack_of_open_elements.pop();
+                    self.acknowledge_self_closing_flag();
+                }
+            }
+            Token::Tag { .. }
+                if token.is_end_tag_with_name(&["script"])
+                    && self
+                        .arena
+                        .get_node(self.stack_of_open_elements.current_node())
+                        .is_element_in_namespace(Namespace::Svg) =>
+            {
+                // Pop the current node off the stack of open elements.
+                self.stack_of_open_elements.pop();
+
+                // TODO: Process the SVG script element.
+            }
+            Token::Tag { tag_name, .. } => {
+                // Initialize node to be the current node (the bottommost node
+                // of the stack).
+                let elements = self.stack_of_open_elements.elements.clone();
+                let mut index = elements.len() - 1;
+
+                // If node's tag name, converted to ASCII lowercase, is not the
+                // same as the tag name of the token, then this is a parse
+                // error.
+                if !self.element_has_lowercase_tag_name(elements[index], tag_name) {
+                    self.error("Unexpected end tag in foreign content");
+                }
+
+                loop {
+                    // Loop: If node is the topmost element in the stack of open
+                    // elements, then return. (fragment case)
+                    if index == 0 {
+                        return;
+                    }
+
+                    // If node's tag name, converted to ASCII lowercase, is the
+                    // same as the tag name of the token, pop elements from the
+                    // stack of open elements until node has been popped from
+                    // the stack, and then return.
+                    if self.element_has_lowercase_tag_name(elements[index], tag_name) {
+                        self.stack_of_open_elements
+                            .pop_elements_until_element_has_been_popped(elements[index]);
+                        return;
+                    }
+
+                    // Set node to the previous entry in the stack of open
+                    // elements.
+                    index -= 1;
+
+                    // If node is not an element in the HTML namespace, return
+                    // to the step labeled loop.
+                    if !self
+                        .arena
+                        .get_node(elements[index])
+                        .is_element_in_namespace(Namespace::Html)
+                    {
+                        continue;
+                    }
+
+                    // Otherwise, process the token according to the rules given
+                    // in the section corresponding to the current insertion
+                    // mode in HTML content.
+                    self.process_token(self.insertion_mode, token);
+                    return;
+                }
+            }
+            Token::EndOfFile => unreachable!("End of file is never processed as foreign content"),
+        }
+    }
+
+    fn element_has_lowercase_tag_name(&self, element: NodeId, tag_name: &str) -> bool {
+        match &self.arena.get_node(element).kind {
+            NodeKind::Element { tag_name: name, .. } => name.to_ascii_lowercase() == tag_name,
+            _ => false,
         }
     }
 
@@ -150,12 +1110,20 @@ impl<'input, 'arena> Parser<'input, 'arena> {
             InsertionMode::Initial => match token {
                 whitespace!() => {}
                 Token::Comment { .. } => {
-                    todo!("Insert a comment as the last child of the Document object.");
+                    // Insert a comment as the last child of the Document object.
+                    self.insert_comment(
+                        token,
+                        Some(InsertionLocation {
+                            parent: self.document,
+                            after_child: None,
+                        }),
+                    );
                 }
                 Token::Doctype {
                     name,
                     public_identifier,
                     system_identifier,
+                    force_quirks,
                 } => {
                     // If the DOCTYPE token's name is not "html", or the token's
                     // public identifier is not missing, or the token's system
@@ -186,31 +1154,37 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     let doctype = self.arena.create_node(doctype);
                     self.arena.append(doctype, self.document);
 
-                    // TODO: Then, if the document is not an iframe srcdoc
-                    // document, and the parser cannot
-                    // change the mode flag is false, and
+                    // Then, if the document is not an iframe srcdoc document,
+                    // and the parser cannot change the mode flag is false, and
                     // the DOCTYPE token matches one of the conditions in the
-                    // following list, then set the Document to quirks mode:
-
-                    // TODO: Otherwise, if the document is not an iframe srcdoc
-                    // document, and the parser cannot change the mode flag is
-                    // false, and the DOCTYPE token matches one of the
-                    // conditions in the following list, then then set the
-                    // Document to limited-quirks mode:
-                    //
-                    // The system identifier and public identifier strings must
-                    // be compared to the values given in the lists above in an
-                    // ASCII case-insensitive manner. A system identifier whose
-                    // value is the empty string is not considered missing for
-                    // the purposes of the conditions above.
+                    // following list, then set the Document to quirks mode.
+                    // Otherwise, if the DOCTYPE token matches one of the
+                    // conditions in the second list, then set the Document to
+                    // limited-quirks mode.
+                    // TODO: Check for iframe srcdoc documents and the parser
+                    // cannot change the mode flag once they exist.
+                    let mode = document_mode_for_doctype(
+                        name,
+                        public_identifier.as_deref(),
+                        system_identifier.as_deref(),
+                        *force_quirks,
+                    );
+                    self.arena
+                        .get_node_mut(self.document)
+                        .set_document_mode(mode);
 
                     // Then, switch the insertion mode to "before html".
                     self.switch_insertion_mode(InsertionMode::BeforeHtml);
                 }
                 _ => {
-                    // TODO: If the document is not an iframe srcdoc document, then this is a parse
+                    // If the document is not an iframe srcdoc document, then this is a parse
                     // error; if the parser cannot change the mode flag is false, set the Document
                     // to quirks mode.
+                    // TODO: Skip this for iframe srcdoc documents once they exist.
+                    self.error("Missing DOCTYPE");
+                    self.arena
+                        .get_node_mut(self.document)
+                        .set_document_mode(DocumentMode::Quirks);
 
                     self.switch_insertion_mode_and_reprocess_token(InsertionMode::BeforeHtml);
                 }
@@ -221,29 +1195,50 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                         self.error("Unexpected DOCTYPE");
                     }
                     Token::Comment { .. } => {
-                        todo!("Insert a comment as the last child of the Document object.");
+                        // Insert a comment as the last child of the Document object.
+                        self.insert_comment(
+                            token,
+                            Some(InsertionLocation {
+                                parent: self.document,
+                                after_child: None,
+                            }),
+                        );
                     }
                     whitespace!() => {}
                     Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
                         let html_element =
                             self.create_element_for_token(token, Namespace::Html, self.document);
                         self.arena.append(html_element, self.document);
-                        self.stack_of_open_elements.push(html_element);
+                        self.stack_of_open_elements.push(self.arena, html_element);
                         self.switch_insertion_mode(InsertionMode::BeforeHead);
                     }
                     Token::Tag { .. }
-                        if token.is_end_tag_with_name(&["head", "body", "html", "br"]) =>
+                        if token.is_end_tag()
+                            && !token.is_end_tag_with_name(&["head", "body", "html", "br"]) =>
                     {
-                        todo!("Act as described in the 'anything else' entry below.");
-                    }
-                    Token::Tag { .. } if token.is_end_tag() => {
+                        // An end tag whose tag name is one of: "head", "body", "html", "br" acts
+                        // as described in the "anything else" entry below.
+                        // Parse error. Ignore the token.
                         self.error("Unexpected end tag");
                     }
                     _ => {
-                        // TODO: Create an html element whose node document is the Document object.
+                        // Create an html element whose node document is the Document object.
                         // Append it to the Document object. Put this element in the stack of open
                         // elements.
+                        let html_element = self.create_element_for_token(
+                            &Token::Tag {
+                                start: true,
+                                tag_name: "html".to_string(),
+                                attributes: vec![],
+                                self_closing: false,
+                            },
+                            Namespace::Html,
+                            self.document,
+                        );
+                        self.arena.append(html_element, self.document);
+                        self.stack_of_open_elements.push(self.arena, html_element);
 
+                        // Switch the insertion mode to "before head", then reprocess the token.
                         self.switch_insertion_mode_and_reprocess_token(InsertionMode::BeforeHead);
                     }
                 }
@@ -251,7 +1246,8 @@ impl<'input, 'arena> Parser<'input, 'arena> {
             InsertionMode::BeforeHead => match token {
                 whitespace!() => {}
                 Token::Comment { .. } => {
-                    todo!("Insert a comment.");
+                    // Insert a comment.
+                    self.insert_comment(token, None);
                 }
                 Token::Doctype { .. } => {
                     self.error("Unexpected DOCTYPE");
@@ -260,20 +1256,34 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     self.process_token(InsertionMode::InBody, token);
                 }
                 Token::Tag { .. } if token.is_start_tag_with_name(&["head"]) => {
-                    let head = self.insert_html_element(&token);
+                    let head = self.insert_html_element(token);
                     self.head_element = Some(head);
                     self.switch_insertion_mode(InsertionMode::InHead);
                 }
                 Token::Tag { .. }
-                    if token.is_end_tag_with_name(&["head", "body", "html", "br"]) =>
+                    if token.is_end_tag()
+                        && !token.is_end_tag_with_name(&["head", "body", "html", "br"]) =>
                 {
-                    todo!("Act as described in the 'anything else' entry below.");
-                }
-                Token::Tag { .. } if token.is_end_tag() => {
+                    // An end tag whose tag name is one of: "head", "body", "html", "br" acts as
+                    // described in the "anything else" entry below.
+                    // Parse error. Ignore the token.
                     self.error("Unexpected end tag");
                 }
                 _ => {
-                    todo!();
+                    // Insert an HTML element for a "head" start tag token with no attributes.
+                    let head = self.insert_html_element(&Token::Tag {
+                        start: true,
+                        tag_name: "head".to_string(),
+                        attributes: vec![],
+                        self_closing: false,
+                    });
+
+                    // Set the head element pointer to the newly created head element.
+                    self.head_element = Some(head);
+
+                    // Switch the insertion mode to "in head".
+                    // Reprocess the current token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InHead);
                 }
             },
             InsertionMode::InHead => match token {
@@ -287,7 +1297,8 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     self.insert_character(*character);
                 }
                 Token::Comment { .. } => {
-                    todo!("Insert a comment.");
+                    // Insert a comment.
+                    self.insert_comment(token, None);
                 }
                 Token::Doctype { .. } => {
                     self.error("Unexpected DOCTYPE");
@@ -305,17 +1316,14 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     self.stack_of_open_elements.pop();
 
                     // Acknowledge the token's self-closing flag, if it is set.
-                    token.acknowledge_self_closing_flag();
+                    self.acknowledge_self_closing_flag();
                 }
                 Token::Tag { .. } if token.is_start_tag_with_name(&["meta"]) => {
                     // Insert an HTML element for the token. Immediately pop the
                     // current node off the stack of open elements.
-                    self.insert_html_element(token);
-                    self.stack_of_open_elements.pop();
-
                     // Acknowledge the token's self-closing flag, if it is
                     // set.
-                    token.acknowledge_self_closing_flag();
+                    self.insert_void_html_element(token);
 
                     // TODO: If the active speculative HTML parser is null,
                     // then:
@@ -325,14 +1333,63 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     self.follow_generic_parsing_algorithm(token, ParsingAlgorithm::RcData);
                 }
                 Token::Tag { .. }
-                    if (token.is_start_tag_with_name(&["noscript"]) && self.scripting)
+                    if (token.is_start_tag_with_name(&["noscript"])
+                        && self.parses_noscript_as_raw_text())
                         || token.is_start_tag_with_name(&["noframes", "style"]) =>
                 {
                     // Follow the generic raw text element parsing algorithm.
                     self.follow_generic_parsing_algorithm(token, ParsingAlgorithm::RawText);
                 }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&["noscript"])
+                        && !self.parses_noscript_as_raw_text() =>
+                {
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+
+                    // Switch the insertion mode to "in head noscript".
+                    self.switch_insertion_mode(InsertionMode::InHeadNoScript);
+                }
                 Token::Tag { .. } if token.is_start_tag_with_name(&["script"]) => {
-                    todo!();
+                    // Let the adjusted insertion location be the appropriate place for inserting
+                    // a node.
+                    let adjusted_insertion_location =
+                        self.appropriate_place_for_inserting_node(None);
+
+                    // Create an element for the token in the HTML namespace, with the intended
+                    // parent being the element in which the adjusted insertion location finds
+                    // itself.
+                    let element = self.create_element_for_token(
+                        token,
+                        Namespace::Html,
+                        adjusted_insertion_location.parent,
+                    );
+
+                    // TODO: Set the element's parser document to the Document, and set the
+                    // element's force async to false.
+
+                    // TODO: If the parser was created as part of the HTML fragment parsing
+                    // algorithm, then set the script element's already started to true.
+
+                    // TODO: If the parser was invoked via the document.write() or
+                    // document.writeln() methods, then optionally set the script element's
+                    // already started to true.
+
+                    // Insert the newly created element at the adjusted insertion location.
+                    adjusted_insertion_location.insert_element(self.arena, element);
+
+                    // Push the element onto the stack of open elements so that it is the new
+                    // current node.
+                    self.stack_of_open_elements.push(self.arena, element);
+
+                    // Switch the tokenizer to the script data state.
+                    self.tokenizer.switch_to(tokenizer::State::ScriptData);
+
+                    // Let the original insertion mode be the current insertion mode.
+                    self.original_insertion_mode = self.insertion_mode;
+
+                    // Switch the insertion mode to "text".
+                    self.switch_insertion_mode(InsertionMode::Text);
                 }
                 Token::Tag { .. } if token.is_end_tag_with_name(&["head"]) => {
                     // Pop the current node (which will be the head element) off the stack of
@@ -341,28 +1398,111 @@ impl<'input, 'arena> Parser<'input, 'arena> {
 
                     self.switch_insertion_mode(InsertionMode::AfterHead);
                 }
-                Token::Tag { .. } if token.is_end_tag_with_name(&["body", "html", "br"]) => {
-                    todo!("Act as described in the 'anything else' entry below.");
-                }
                 Token::Tag { .. } if token.is_start_tag_with_name(&["template"]) => {
-                    todo!();
+                    // TODO: Implement template elements. Until then the start tag is
+                    //       ignored, and the contents of the template are parsed as if
+                    //       they were not inside one.
+                    self.unsupported("template elements");
                 }
                 Token::Tag { .. } if token.is_end_tag_with_name(&["template"]) => {
-                    todo!();
+                    // If there is no template element on the stack of open elements, then
+                    // this is a parse error; ignore the token.
+                    if !self.stack_of_open_elements.has_template_element(self.arena) {
+                        self.error("Unexpected template end tag");
+                        return;
+                    }
+
+                    // Generate all implied end tags thoroughly.
+                    // TODO: Also generate the implied end tags for caption, colgroup,
+                    //       tbody, td, tfoot, th, thead and tr elements.
+                    self.generate_implied_end_tags_except_for(None);
+
+                    // If the current node is not a template element, then this is a parse
+                    // error.
+                    if !self.current_node_is_element_with_tag_name("template") {
+                        self.error("Expected current node to be a template element");
+                    }
+
+                    // Pop elements from the stack of open elements until a template element
+                    // has been popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "template");
+
+                    // Clear the list of active formatting elements up to the last marker.
+                    self.active_formatting_elements.clear_up_to_last_marker();
+
+                    // TODO: Pop the current template insertion mode off the stack of
+                    //       template insertion modes.
+
+                    // Reset the insertion mode appropriately.
+                    self.reset_insertion_mode_appropriately();
                 }
                 Token::Tag { .. }
-                    if token.is_start_tag_with_name(&["head"]) || token.is_end_tag() =>
+                    if token.is_start_tag_with_name(&["head"])
+                        || (token.is_end_tag()
+                            && !token.is_end_tag_with_name(&["body", "html", "br"])) =>
                 {
+                    // An end tag whose tag name is one of: "body", "html", "br" acts as described
+                    // in the "anything else" entry below.
+                    // Parse error. Ignore the token.
                     self.error("Unexpected tag");
                 }
                 _ => {
-                    // TODO: Pop the current node (which will be the head element) off the stack of
+                    // Pop the current node (which will be the head element) off the stack of
                     // open elements.
+                    self.stack_of_open_elements.pop();
 
                     self.switch_insertion_mode_and_reprocess_token(InsertionMode::AfterHead);
                 }
             },
-            InsertionMode::InHeadNoScript => todo!("InHeadNoScript"),
+            InsertionMode::InHeadNoScript => match token {
+                Token::Doctype { .. } => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected DOCTYPE");
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["noscript"]) => {
+                    // Pop the current node (which will be a noscript element) from the stack of
+                    // open elements; the new current node will be a head element.
+                    self.stack_of_open_elements.pop();
+
+                    // Switch the insertion mode to "in head".
+                    self.switch_insertion_mode(InsertionMode::InHead);
+                }
+                whitespace!() | Token::Comment { .. } => {
+                    self.process_token(InsertionMode::InHead, token);
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&[
+                        "basefont", "bgsound", "link", "meta", "noframes", "style",
+                    ]) =>
+                {
+                    self.process_token(InsertionMode::InHead, token);
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&["head", "noscript"])
+                        || (token.is_end_tag() && !token.is_end_tag_with_name(&["br"])) =>
+                {
+                    // An end tag whose tag name is "br" acts as described in the "anything
+                    // else" entry below.
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected tag in noscript");
+                }
+                _ => {
+                    // Parse error.
+                    self.error(format!("Unexpected token in noscript: {}", token).as_str());
+
+                    // Pop the current node (which will be a noscript element) from the stack of
+                    // open elements; the new current node will be a head element.
+                    self.stack_of_open_elements.pop();
+
+                    // Switch the insertion mode to "in head".
+                    // Reprocess the token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InHead);
+                }
+            },
             InsertionMode::AfterHead => match token {
                 whitespace!() => {
                     // Insert the character.
@@ -373,7 +1513,8 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     self.insert_character(*character);
                 }
                 Token::Comment { .. } => {
-                    todo!("Insert a comment.");
+                    // Insert a comment.
+                    self.insert_comment(token, None);
                 }
                 Token::Doctype { .. } => {
                     self.error("Unexpected DOCTYPE");
@@ -396,26 +1537,49 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                         "style", "template", "title",
                     ]) =>
                 {
-                    todo!();
+                    // Parse error.
+                    self.error("Unexpected head element after head");
+
+                    // Push the node pointed to by the head element pointer onto the stack of open
+                    // elements.
+                    let head = self
+                        .head_element
+                        .expect("head element pointer should be set after head");
+                    self.stack_of_open_elements.push(self.arena, head);
+
+                    // Process the token using the rules for the "in head" insertion mode.
+                    self.process_token(InsertionMode::InHead, token);
+
+                    // Remove the node pointed to by the head element pointer from the stack of
+                    // open elements. (It might not be the current node at this point.)
+                    self.stack_of_open_elements.remove_element(head);
                 }
                 Token::Tag { .. } if token.is_end_tag_with_name(&["template"]) => {
                     self.process_token(InsertionMode::InHead, token);
                 }
-                Token::Tag { .. } if token.is_end_tag_with_name(&["body", "html", "br"]) => {
-                    todo!("Act as described in the 'anything else' entry below.")
-                }
                 Token::Tag { .. }
-                    if token.is_start_tag_with_name(&["head"]) || token.is_end_tag() =>
+                    if token.is_start_tag_with_name(&["head"])
+                        || (token.is_end_tag()
+                            && !token.is_end_tag_with_name(&["body", "html", "br"])) =>
                 {
+                    // An end tag whose tag name is one of: "body", "html", "br" acts as described
+                    // in the "anything else" entry below.
+                    // Parse error. Ignore the token.
                     self.error("Unexpected tag");
                 }
                 _ => {
+                    // Insert an HTML element for a "body" start tag token with no attributes.
+                    // Attributes on a later explicit <body> tag are added to this element by the
+                    // "in body" insertion mode.
                     self.insert_html_element(&Token::Tag {
                         start: true,
                         tag_name: "body".to_string(),
                         attributes: vec![],
                         self_closing: false,
                     });
+
+                    // Switch the insertion mode to "in body".
+                    // Reprocess the current token.
                     self.switch_insertion_mode_and_reprocess_token(InsertionMode::InBody);
                 }
             },
@@ -426,8 +1590,7 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                 }
                 whitespace!() => {
                     // Reconstruct the active formatting elements, if any.
-                    self.active_formatting_elements
-                        .reconstruct(&self.stack_of_open_elements);
+                    self.reconstruct_active_formatting_elements();
 
                     let character = match token {
                         Token::Character(character) => character,
@@ -439,8 +1602,7 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                 }
                 Token::Character(character) => {
                     // Reconstruct the active formatting elements, if any.
-                    self.active_formatting_elements
-                        .reconstruct(&self.stack_of_open_elements);
+                    self.reconstruct_active_formatting_elements();
 
                     // Insert the token's character.
                     self.insert_character(*character);
@@ -448,56 +1610,178 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     // Set the frameset-ok flag to "not ok".
                     self.frameset_ok = false;
                 }
-                Token::Comment { .. } => todo!(),
+                Token::Comment { .. } => {
+                    // Insert a comment.
+                    self.insert_comment(token, None);
+                }
                 Token::Doctype { .. } => {
                     // Parse error. Ignore the token.
                     self.error("Unexpected DOCTYPE");
                 }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => todo!(),
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    // Parse error.
+                    self.error("Unexpected html start tag");
+
+                    // If there is a template element on the stack of open elements, then
+                    // ignore the token.
+                    if self.stack_of_open_elements.has_template_element(self.arena) {
+                        return;
+                    }
+
+                    // Otherwise, for each attribute on the token, check to see if the
+                    // attribute is already present on the top element of the stack of open
+                    // elements. If it is not, add the attribute and its corresponding value to
+                    // that element.
+                    let html = self.stack_of_open_elements.elements[0];
+                    self.add_missing_attributes(html, token);
+                }
                 Token::Tag { .. }
                     if token.is_start_tag_with_name(&[
                         "base", "basefont", "bgsound", "link", "meta", "noframes", "script",
                         "style", "template", "title",
-                    ]) =>
+                    ]) || token.is_end_tag_with_name(&["template"]) =>
                 {
-                    todo!()
+                    // Process the token using the rules for the "in head" insertion mode.
+                    self.process_token(InsertionMode::InHead, token);
                 }
-                Token::Tag { .. } if token.is_end_tag_with_name(&["template"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["body"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["frameset"]) => todo!(),
-                Token::EndOfFile => {
-                    // TODO: If the stack of template insertion modes is not empty, then process the
+                Token::Tag { .. } if token.is_start_tag_with_name(&["body"]) => {
+                    // Parse error.
+                    self.error("Unexpected body start tag");
+
+                    // If the stack of open elements has only one node on it, if the second
+                    // element on the stack of open elements is not a body element, or if there
+                    // is a template element on the stack of open elements, then ignore the
+                    // token. (fragment case or there is a template element on the stack)
+                    let Some(&body) = self.stack_of_open_elements.elements.get(1) else {
+                        return;
+                    };
+                    if !self.arena.get_node(body).is_element_with_tag_name("body")
+                        || self.stack_of_open_elements.has_template_element(self.arena)
+                    {
+                        return;
+                    }
+
+                    // Otherwise, set the frameset-ok flag to "not ok"; then, for each
+                    // attribute on the token, check to see if the attribute is already present
+                    // on the body element (the second element) on the stack of open elements,
+                    // and if it is not, add the attribute and its corresponding value to that
+                    // element.
+                    self.frameset_ok = false;
+                    self.add_missing_attributes(body, token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["frameset"]) => {
+                    // Parse error.
+                    self.error("Unexpected frameset start tag");
+
+                    // If the stack of open elements has only one node on it, or if the second
+                    // element on the stack of open elements is not a body element, then ignore
+                    // the token. (fragment case or there is a template element on the stack)
+                    let second_element = match self.stack_of_open_elements.elements.get(1) {
+                        Some(element) => *element,
+                        None => return,
+                    };
+                    if !self
+                        .arena
+                        .get_node(second_element)
+                        .is_element_with_tag_name("body")
+                    {
+                        return;
+                    }
+
+                    // If the frameset-ok flag is set to "not ok", ignore the token.
+                    if !self.frameset_ok {
+                        return;
+                    }
+
+                    // Otherwise, run the following steps:
+
+                    // 1. Remove the second element on the stack of open elements from its parent
+                    //    node, if it has one.
+                    if self.arena.get_node(second_element).parent().is_some() {
+                        self.arena.remove(second_element);
+                    }
+
+                    // 2. Pop all the nodes from the bottom of the stack of open elements, from
+                    //    the current node up to, but not including, the root html element.
+                    while self.stack_of_open_elements.elements.len() > 1 {
+                        self.stack_of_open_elements.pop();
+                    }
+
+                    // 3. Insert an HTML element for the token.
+                    self.insert_html_element(token);
+
+                    // 4. Switch the insertion mode to "in frameset".
+                    self.switch_insertion_mode(InsertionMode::InFrameset);
+                }
+                Token::EndOfFile => {
+                    // TODO: If the stack of template insertion modes is not empty, then process the
                     // token using the rules for the "in template" insertion
                     // mode.
 
                     // TODO: Otherwise, follow these steps:
 
-                    // TODO: 1. If there is a node in the stack of open elements that is not either
-                    // a dd element, a dt element, an li element, an optgroup element, an option
-                    // element, a p element, an rb element, an rp element, an rt element, an rtc
-                    // element, a tbody element, a td element, a tfoot element, a th element, a
-                    // thead element, a tr element, the body element, or the html element, then this
-                    // is a parse error.
+                    // 1. If there is a node in the stack of open elements that is not either a dd
+                    //    element, a dt element, an li element, an optgroup element, an option
+                    //    element, a p element, an rb element, an rp element, an rt element, an
+                    //    rtc element, a tbody element, a td element, a tfoot element, a th
+                    //    element, a thead element, a tr element, the body element, or the html
+                    //    element, then this is a parse error.
+                    if self.has_open_element_that_is_not_implicitly_closed() {
+                        self.error("Unexpected end of file with open elements");
+                    }
 
+                    // 2. Stop parsing.
                     self.stop_parsing();
                 }
                 Token::Tag { .. } if token.is_end_tag_with_name(&["body"]) => {
-                    // TODO: If the stack of open elements does not have a body
-                    // element in scope, this is a parse error; ignore the
-                    // token.
+                    // If the stack of open elements does not have a body element in scope, this
+                    // is a parse error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_scope(self.arena, "body")
+                    {
+                        self.error("Unexpected body end tag without body in scope");
+                        return;
+                    }
 
-                    // TODO: Otherwise, if there is a node in the stack of open
-                    // elements that is not either a dd element, a dt element,
-                    // an li element, an optgroup element, an option element, a
-                    // p element, an rb element, an rp element, an rt element,
-                    // an rtc element, a tbody element, a td element, a tfoot
-                    // element, a th element, a thead element, a tr element, the
-                    // body element, or the html element, then this is a parse
-                    // error.
+                    // Otherwise, if there is a node in the stack of open elements that is not
+                    // either a dd element, a dt element, an li element, an optgroup element, an
+                    // option element, a p element, an rb element, an rp element, an rt element,
+                    // an rtc element, a tbody element, a td element, a tfoot element, a th
+                    // element, a thead element, a tr element, the body element, or the html
+                    // element, then this is a parse error.
+                    if self.has_open_element_that_is_not_implicitly_closed() {
+                        self.error("Unexpected open element at body end tag");
+                    }
 
+                    // Switch the insertion mode to "after body".
                     self.switch_insertion_mode(InsertionMode::AfterBody);
                 }
-                Token::Tag { .. } if token.is_end_tag_with_name(&["html"]) => todo!(),
+                Token::Tag { .. } if token.is_end_tag_with_name(&["html"]) => {
+                    // If the stack of open elements does not have a body element in scope, this
+                    // is a parse error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_scope(self.arena, "body")
+                    {
+                        self.error("Unexpected html end tag without body in scope");
+                        return;
+                    }
+
+                    // Otherwise, if there is a node in the stack of open elements that is not
+                    // either a dd element, a dt element, an li element, an optgroup element, an
+                    // option element, a p element, an rb element, an rp element, an rt element,
+                    // an rtc element, a tbody element, a td element, a tfoot element, a th
+                    // element, a thead element, a tr element, the body element, or the html
+                    // element, then this is a parse error.
+                    if self.has_open_element_that_is_not_implicitly_closed() {
+                        self.error("Unexpected open element at html end tag");
+                    }
+
+                    // Switch the insertion mode to "after body".
+                    // Reprocess the token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::AfterBody);
+                }
                 Token::Tag { .. }
                     if token.is_start_tag_with_name(&[
                         "address",
@@ -529,12 +1813,7 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                 {
                     // If the stack of open elements has a p element in
                     // button scope, then close a p element.
-                    if self
-                        .stack_of_open_elements
-                        .has_element_in_button_scope(&self.arena, "p")
-                    {
-                        self.close_p_element();
-                    }
+                    self.close_p_element_if_in_button_scope();
 
                     // Insert an HTML element for the token.
                     self.insert_html_element(token);
@@ -544,12 +1823,7 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                 {
                     // If the stack of open elements has a p element in button
                     // scope, then close a p element.
-                    if self
-                        .stack_of_open_elements
-                        .has_element_in_button_scope(&self.arena, "p")
-                    {
-                        self.close_p_element();
-                    }
+                    self.close_p_element_if_in_button_scope();
 
                     // If the current node is an HTML element whose tag name is
                     // one of "h1", "h2", "h3", "h4", "h5", or "h6", then this
@@ -567,8 +1841,46 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     // Insert an HTML element for the token.
                     self.insert_html_element(token);
                 }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["pre", "listing"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["form"]) => todo!(),
+                Token::Tag { .. } if token.is_start_tag_with_name(&["pre", "listing"]) => {
+                    // If the stack of open elements has a p element in button scope, then close a
+                    // p element.
+                    self.close_p_element_if_in_button_scope();
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+
+                    // If the next token is a U+000A LINE FEED (LF) character token, then ignore
+                    // that token and move on to the next one. (Newlines at the start of pre
+                    // blocks are ignored as an authoring convenience.)
+                    self.should_ignore_next_line_feed = true;
+
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["form"]) => {
+                    // If the form element pointer is not null, and there is no template element on
+                    // the stack of open elements, then this is a parse error; ignore the token.
+                    let has_template_element =
+                        self.stack_of_open_elements.has_template_element(self.arena);
+                    if self.form_element_pointer.is_some() && !has_template_element {
+                        self.error("Unexpected nested form start tag");
+                        return;
+                    }
+
+                    // Otherwise:
+
+                    // If the stack of open elements has a p element in button scope, then close a
+                    // p element.
+                    self.close_p_element_if_in_button_scope();
+
+                    // Insert an HTML element for the token, and, if there is no template element
+                    // on the stack of open elements, set the form element pointer to point to the
+                    // element created.
+                    let element = self.insert_html_element(token);
+                    if !has_template_element {
+                        self.form_element_pointer = Some(element);
+                    }
+                }
                 Token::Tag { .. } if token.is_start_tag_with_name(&["li"]) => {
                     // Set the frameset-ok flag to "not ok".
                     self.frameset_ok = false;
@@ -593,7 +1905,7 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                             // Pop elements from the stack of open elements until an li element
                             // has been popped from the stack.
                             self.stack_of_open_elements
-                                .pop_until_element_with_tag_name(&self.arena, "li");
+                                .pop_until_element_with_tag_name(self.arena, "li");
 
                             // Jump to the step labeled done below.
                             break;
@@ -601,7 +1913,7 @@ impl<'input, 'arena> Parser<'input, 'arena> {
 
                         // If node is in the special category, but is not an address, div, or p
                         //    element, then jump to the step labeled done below.
-                        if node.is_element_with_one_of_tag_names(SPECIAL_TAGS)
+                        if node.is_special()
                             && !node.is_element_with_one_of_tag_names(&["address", "div", "p"])
                         {
                             break;
@@ -614,19 +1926,101 @@ impl<'input, 'arena> Parser<'input, 'arena> {
 
                     // Done: If the stack of open elements has a p element in button scope, then
                     //    close a p element.
+                    self.close_p_element_if_in_button_scope();
+
+                    // Finally, insert an HTML element for the token.
+                    self.insert_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["dd", "dt"]) => {
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
+
+                    // Initialize node to be the current node (the bottommost node of the stack).
+                    for node in self.stack_of_open_elements.elements.iter().rev() {
+                        let node = self.arena.get_node(*node);
+
+                        // Loop: If node is a dd or dt element, then run these substeps:
+                        if let Some(tag_name) = ["dd", "dt"]
+                            .into_iter()
+                            .find(|tag_name| node.is_element_with_tag_name(tag_name))
+                        {
+                            // Generate implied end tags, except for elements with the same tag
+                            // name as node.
+                            self.generate_implied_end_tags_except_for(Some(tag_name));
+
+                            // If the current node is not an element with the same tag name as
+                            // node, then this is a parse error.
+                            if !self.current_node_is_element_with_tag_name(tag_name) {
+                                self.error(format!("Expected {} element", tag_name).as_str());
+                            }
+
+                            // Pop elements from the stack of open elements until an element with
+                            // the same tag name as node has been popped from the stack.
+                            self.stack_of_open_elements
+                                .pop_until_element_with_tag_name(self.arena, tag_name);
+
+                            // Jump to the step labeled done below.
+                            break;
+                        }
+
+                        // If node is in the special category, but is not an address, div, or p
+                        // element, then jump to the step labeled done below.
+                        if node.is_special()
+                            && !node.is_element_with_one_of_tag_names(&["address", "div", "p"])
+                        {
+                            break;
+                        }
+
+                        // Otherwise, set node to the previous entry in the stack of open
+                        // elements and return to the step labeled loop.
+                    }
+
+                    // Done: If the stack of open elements has a p element in button scope, then
+                    // close a p element.
+                    self.close_p_element_if_in_button_scope();
+
+                    // Finally, insert an HTML element for the token.
+                    self.insert_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["plaintext"]) => {
+                    // If the stack of open elements has a p element in button scope, then close
+                    // a p element.
+                    self.close_p_element_if_in_button_scope();
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+
+                    // Switch the tokenizer to the PLAINTEXT state.
+                    self.tokenizer.switch_to(tokenizer::State::PlainText);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["button"]) => {
+                    // If the stack of open elements has a button element in scope, then run these
+                    // substeps:
                     if self
                         .stack_of_open_elements
-                        .has_element_in_button_scope(&self.arena, "p")
+                        .has_element_in_scope(self.arena, "button")
                     {
-                        self.close_p_element();
+                        // Parse error.
+                        self.error("Unexpected button start tag");
+
+                        // Generate implied end tags.
+                        self.generate_implied_end_tags_except_for(None);
+
+                        // Pop elements from the stack of open elements until a button element
+                        // has been popped from the stack.
+                        self.stack_of_open_elements
+                            .pop_until_element_with_tag_name(self.arena, "button");
                     }
 
-                    // Finally, insert an HTML element for the token.
+                    // Reconstruct the active formatting elements, if any.
+                    self.reconstruct_active_formatting_elements();
+
+                    // Insert an HTML element for the token.
                     self.insert_html_element(token);
+
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
                 }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["dd", "dt"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["plaintext"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["button"]) => todo!(),
                 Token::Tag { tag_name, .. }
                     if token.is_end_tag_with_name(&[
                         "address",
@@ -658,37 +2052,111 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                         "ul",
                     ]) =>
                 {
-                    // TODO: If the stack of open elements does not have an
-                    // element in scope that is an HTML element with the same
-                    // tag name as that of the token, then this is a parse
-                    // error; ignore the token.
+                    // If the stack of open elements does not have an element in
+                    // scope that is an HTML element with the same tag name as
+                    // that of the token,
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_scope(self.arena, tag_name)
+                    {
+                        // then this is a parse error; ignore the token.
+                        self.error("Unexpected end tag");
+                        return;
+                    }
 
                     // Otherwise, run these steps:
 
                     // Generate implied end tags.
                     self.generate_implied_end_tags_except_for(None);
 
-                    // TODO: If the current node is not an HTML element with the
+                    // If the current node is not an HTML element with the
                     // same tag name as that of the token,
-                    // then this is a parse error.
+                    if !self.current_node_is_element_with_tag_name(tag_name) {
+                        // then this is a parse error.
+                        self.error("Unexpected end tag");
+                    }
 
                     // Pop elements from the stack of open elements until an
                     // HTML element with the same tag name as the token has been
                     // popped from the stack.
                     self.stack_of_open_elements
-                        .pop_until_element_with_tag_name(&self.arena, tag_name)
+                        .pop_until_element_with_tag_name(self.arena, tag_name)
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["form"]) => {
+                    // If there is no template element on the stack of open elements, then run
+                    // these substeps:
+                    if !self.stack_of_open_elements.has_template_element(self.arena) {
+                        // 1. Let node be the element that the form element pointer is set to,
+                        //    or null if it is not set to an element.
+                        // 2. Set the form element pointer to null.
+                        let node = self.form_element_pointer.take();
+
+                        // 3. If node is null or the stack of open elements does not have node
+                        //    in scope, then this is a parse error; return and ignore the token.
+                        let Some(node) = node.filter(|node| {
+                            self.stack_of_open_elements
+                                .has_node_in_scope(self.arena, *node)
+                        }) else {
+                            self.error("Unexpected form end tag");
+                            return;
+                        };
+
+                        // 4. Generate implied end tags.
+                        self.generate_implied_end_tags_except_for(None);
+
+                        // 5. If the current node is not node, then this is a parse error.
+                        if self.stack_of_open_elements.current_node() != node {
+                            self.error("Expected current node to be the form element");
+                        }
+
+                        // 6. Remove node from the stack of open elements.
+                        //
+                        // This is not necessarily the current node, so the elements that
+                        // were opened inside the form stay open.
+                        self.stack_of_open_elements.remove_element(node);
+                    } else {
+                        // If there is a template element on the stack of open elements, then
+                        // run these substeps instead:
+
+                        // 1. If the stack of open elements does not have a form element in
+                        //    scope, then this is a parse error; return and ignore the token.
+                        if !self
+                            .stack_of_open_elements
+                            .has_element_in_scope(self.arena, "form")
+                        {
+                            self.error("Unexpected form end tag");
+                            return;
+                        }
+
+                        // 2. Generate implied end tags.
+                        self.generate_implied_end_tags_except_for(None);
+
+                        // 3. If the current node is not a form element, then this is a parse
+                        //    error.
+                        if !self.current_node_is_element_with_tag_name("form") {
+                            self.error("Expected current node to be a form element");
+                        }
+
+                        // 4. Pop elements from the stack of open elements until a form element
+                        //    has been popped from the stack.
+                        self.stack_of_open_elements
+                            .pop_until_element_with_tag_name(self.arena, "form");
+                    }
                 }
-                Token::Tag { .. } if token.is_end_tag_with_name(&["form"]) => todo!(),
                 Token::Tag { .. } if token.is_end_tag_with_name(&["p"]) => {
                     // If the stack of open elements does not have a p element in button scope,
                     if !self
                         .stack_of_open_elements
-                        .has_element_in_button_scope(&self.arena, "p")
+                        .has_element_in_button_scope(self.arena, "p")
                     {
                         // then this is a parse error;
                         self.error("Expected p element in button scope");
 
                         // insert an HTML element for a "p" start tag token with no attributes.
+                        //
+                        // The element is inserted at the appropriate place for inserting a node,
+                        // so in a table it is foster parented like any other misplaced content,
+                        // instead of ending up inside the table.
                         self.insert_html_element(&Token::Tag {
                             start: true,
                             tag_name: "p".to_string(),
@@ -700,56 +2168,123 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     // Close a p element.
                     self.close_p_element();
                 }
-                Token::Tag { .. } if token.is_end_tag_with_name(&["lo"]) => todo!(),
-                Token::Tag { .. } if token.is_end_tag_with_name(&["dd", "dt"]) => todo!(),
-                Token::Tag { .. }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["li"]) => {
+                    // If the stack of open elements does not have an li element in list item
+                    // scope, then this is a parse error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_list_scope(self.arena, "li")
+                    {
+                        self.error("Expected li element in list item scope");
+                        return;
+                    }
+
+                    // Otherwise, run these steps:
+
+                    // Generate implied end tags, except for li elements.
+                    self.generate_implied_end_tags_except_for(Some("li"));
+
+                    // If the current node is not an li element, then this is a parse error.
+                    if !self.current_node_is_element_with_tag_name("li") {
+                        self.error("Expected current node to be an li element");
+                    }
+
+                    // Pop elements from the stack of open elements until an li element has been
+                    // popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "li");
+                }
+                Token::Tag { tag_name, .. } if token.is_end_tag_with_name(&["dd", "dt"]) => {
+                    // If the stack of open elements does not have an element in scope that is an
+                    // HTML element with the same tag name as that of the token, then this is a
+                    // parse error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_scope(self.arena, tag_name)
+                    {
+                        self.error("Unexpected end tag");
+                        return;
+                    }
+
+                    // Otherwise, run these steps:
+
+                    // Generate implied end tags, except for HTML elements with the same tag name
+                    // as the token.
+                    self.generate_implied_end_tags_except_for(Some(tag_name));
+
+                    // If the current node is not an HTML element with the same tag name as that
+                    // of the token, then this is a parse error.
+                    if !self.current_node_is_element_with_tag_name(tag_name) {
+                        self.error("Unexpected end tag");
+                    }
+
+                    // Pop elements from the stack of open elements until an HTML element with the
+                    // same tag name as the token has been popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, tag_name);
+                }
+                Token::Tag { tag_name, .. }
                     if token.is_end_tag_with_name(&["h1", "h2", "h3", "h4", "h5", "h6"]) =>
                 {
-                    // TODO: If the stack of open elements does not have an
-                    // element in scope that is an HTML
-                    // element and whose tag name is one
-                    // of "h1", "h2", "h3", "h4", "h5", or "h6", then this is a
-                    // parse error; ignore the token.
+                    // If the stack of open elements does not have an element in scope that is an
+                    // HTML element and whose tag name is one of "h1", "h2", "h3", "h4", "h5", or
+                    // "h6", then this is a parse error; ignore the token.
+                    if !self.stack_of_open_elements.has_one_of_elements_in_scope(
+                        self.arena,
+                        &["h1", "h2", "h3", "h4", "h5", "h6"],
+                    ) {
+                        self.error("Unexpected heading end tag without heading in scope");
+                        return;
+                    }
 
                     // Otherwise, run these steps:
 
                     // Generate implied end tags.
                     self.generate_implied_end_tags_except_for(None);
 
-                    // TODO: If the current node is not an HTML element with the same
-                    // tag name as that of the token, then this is a parse
-                    // error.
+                    // If the current node is not an HTML element with the same tag name as that
+                    // of the token, then this is a parse error.
+                    if !self.current_node_is_element_with_tag_name(tag_name) {
+                        self.error("Expected current node to be the heading that is being closed");
+                    }
 
                     // Pop elements from the stack of open elements until an
                     // HTML element whose tag name is one of "h1", "h2", "h3",
                     // "h4", "h5", or "h6" has been popped from the stack.
                     self.stack_of_open_elements
                         .pop_until_element_with_one_of_tag_names(
-                            &self.arena,
+                            self.arena,
                             &["h1", "h2", "h3", "h4", "h5", "h6"],
                         );
                 }
                 Token::Tag { .. } if token.is_start_tag_with_name(&["a"]) => {
-                    // TODO: If the list of active formatting elements contains
-                    // an a element between the end of the
-                    // list and the last marker on the list
-                    // (or the start of the list if there is no
+                    // If the list of active formatting elements contains an a
+                    // element between the end of the list and the last marker
+                    // on the list (or the start of the list if there is no
                     // marker on the list), then this is a parse error; run the
                     // adoption agency algorithm for the token, then remove that
                     // element from the list of active formatting elements and
                     // the stack of open elements if the adoption agency
                     // algorithm didn't already remove it (it might not have if
                     // the element is not in table scope).
+                    if let Some(a) = self
+                        .active_formatting_elements
+                        .last_element_with_tag_name_before_marker(self.arena, "a")
+                    {
+                        self.error("Unexpected a start tag while an a element is open");
+                        self.run_adoption_agency_algorithm(token);
+                        self.active_formatting_elements.remove(a);
+                        self.stack_of_open_elements.remove_element(a);
+                    }
 
                     // Reconstruct the active formatting elements, if any.
-                    self.active_formatting_elements
-                        .reconstruct(&self.stack_of_open_elements);
+                    self.reconstruct_active_formatting_elements();
 
                     // Insert an HTML element for the token.
                     let element = self.insert_html_element(token);
                     // Push onto the list of active formatting elements that element.
                     self.active_formatting_elements
-                        .push(ActiveFormattingElement::Element(element));
+                        .push_formatting_element(self.arena, element);
                 }
                 Token::Tag { .. }
                     if token.is_start_tag_with_name(&[
@@ -758,16 +2293,36 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     ]) =>
                 {
                     // Reconstruct the active formatting elements, if any.
-                    self.active_formatting_elements
-                        .reconstruct(&self.stack_of_open_elements);
+                    self.reconstruct_active_formatting_elements();
 
                     // Insert an HTML element for the token
                     let element = self.insert_html_element(token);
                     // Push onto the list of active formatting elements that element.
                     self.active_formatting_elements
-                        .push(ActiveFormattingElement::Element(element));
+                        .push_formatting_element(self.arena, element);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["nobr"]) => {
+                    // Reconstruct the active formatting elements, if any.
+                    self.reconstruct_active_formatting_elements();
+
+                    // If the stack of open elements has a nobr element in scope, then this is a
+                    // parse error; run the adoption agency algorithm for the token, then once
+                    // again reconstruct the active formatting elements, if any.
+                    if self
+                        .stack_of_open_elements
+                        .has_element_in_scope(self.arena, "nobr")
+                    {
+                        self.error("Unexpected nobr start tag");
+                        self.run_adoption_agency_algorithm(token);
+                        self.reconstruct_active_formatting_elements();
+                    }
+
+                    // Insert an HTML element for the token.
+                    let element = self.insert_html_element(token);
+                    // Push onto the list of active formatting elements that element.
+                    self.active_formatting_elements
+                        .push_formatting_element(self.arena, element);
                 }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["nobr"]) => todo!(),
                 Token::Tag { .. }
                     if token.is_end_tag_with_name(&[
                         "a", "b", "big", "code", "em", "font", "i", "nobr", "s", "small", "strike",
@@ -780,125 +2335,336 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                 Token::Tag { .. }
                     if token.is_start_tag_with_name(&["applet", "marquee", "object"]) =>
                 {
-                    todo!()
+                    // Reconstruct the active formatting elements, if any.
+                    self.reconstruct_active_formatting_elements();
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+
+                    // Insert a marker at the end of the list of active formatting elements.
+                    self.active_formatting_elements.push_marker();
+
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
                 }
-                Token::Tag { .. }
+                Token::Tag { tag_name, .. }
                     if token.is_end_tag_with_name(&["applet", "marquee", "object"]) =>
                 {
-                    todo!()
+                    // If the stack of open elements does not have an element in
+                    // scope that is an HTML element with the same tag name as
+                    // that of the token,
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_scope(self.arena, tag_name)
+                    {
+                        // then this is a parse error; ignore the token.
+                        self.error("Unexpected end tag");
+                        return;
+                    }
+
+                    // Otherwise, run these steps:
+
+                    // Generate implied end tags.
+                    self.generate_implied_end_tags_except_for(None);
+
+                    // If the current node is not an HTML element with the same
+                    // tag name as that of the token,
+                    if !self.current_node_is_element_with_tag_name(tag_name) {
+                        // then this is a parse error.
+                        self.error("Unexpected end tag");
+                    }
+
+                    // Pop elements from the stack of open elements until an
+                    // HTML element with the same tag name as the token has been
+                    // popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, tag_name);
+
+                    // Clear the list of active formatting elements up to the
+                    // last marker.
+                    self.active_formatting_elements.clear_up_to_last_marker();
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["table"]) => {
+                    // If the Document is not set to quirks mode, and the stack of open
+                    // elements has a p element in button scope, then close a p element.
+                    if self.arena.get_node(self.document).document_mode()
+                        != Some(DocumentMode::Quirks)
+                    {
+                        self.close_p_element_if_in_button_scope();
+                    }
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
+
+                    // Switch the insertion mode to "in table".
+                    self.switch_insertion_mode(InsertionMode::InTable);
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["br"]) => {
+                    // Parse error. Drop the attributes from the token, and act as described in the
+                    // next entry; i.e. act as if this was a "br" start tag token with no
+                    // attributes, rather than the end tag token that it actually is.
+                    self.error("Unexpected br end tag");
+                    self.process_token(
+                        InsertionMode::InBody,
+                        &Token::Tag {
+                            start: true,
+                            tag_name: "br".to_string(),
+                            attributes: vec![],
+                            self_closing: false,
+                        },
+                    );
                 }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["table"]) => todo!(),
-                Token::Tag { .. } if token.is_end_tag_with_name(&["br"]) => todo!(),
                 Token::Tag { .. }
                     if token.is_start_tag_with_name(&[
                         "area", "br", "embed", "img", "keygen", "wbr",
                     ]) =>
                 {
                     // Reconstruct the active formatting elements, if any.
-                    self.active_formatting_elements
-                        .reconstruct(&self.stack_of_open_elements);
-
-                    // Insert an HTML element for the token.
-                    self.insert_html_element(token);
-                    // Immediately pop the current node off the stack of open elements.
-                    self.stack_of_open_elements.pop();
+                    self.reconstruct_active_formatting_elements();
 
+                    // Insert an HTML element for the token. Immediately pop the current node
+                    // off the stack of open elements.
                     // Acknowledge the token's self-closing flag, if it is set.
-                    token.acknowledge_self_closing_flag();
+                    self.insert_void_html_element(token);
 
                     // Set the frameset-ok flag to "not ok".
                     self.frameset_ok = false;
                 }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["input"]) => todo!(),
-                Token::Tag { .. }
-                    if token.is_start_tag_with_name(&["param", "source", "track"]) =>
-                {
-                    todo!()
-                }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["hr"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["image"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["textarea"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["xmp"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["iframe"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["noembed"]) => todo!(),
-                Token::Tag { .. }
-                    if token.is_start_tag_with_name(&["noscript"]) && self.scripting =>
-                {
-                    todo!()
+                Token::Tag { attributes, .. } if token.is_start_tag_with_name(&["input"]) => {
+                    // Reconstruct the active formatting elements, if any.
+                    self.reconstruct_active_formatting_elements();
+
+                    // Insert an HTML element for the token. Immediately pop the current node off
+                    // the stack of open elements.
+                    // Acknowledge the token's self-closing flag, if it is set.
+                    self.insert_void_html_element(token);
+
+                    // If the token does not have an attribute with the name "type", or if it
+                    // does, but that attribute's value is not an ASCII case-insensitive match for
+                    // the string "hidden", then: set the frameset-ok flag to "not ok".
+                    let is_hidden = attributes.iter().any(|attribute| {
+                        attribute.name == "type" && attribute.value.eq_ignore_ascii_case("hidden")
+                    });
+                    if !is_hidden {
+                        self.frameset_ok = false;
+                    }
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&["param", "source", "track"]) =>
+                {
+                    // Insert an HTML element for the token. Immediately pop the current node off
+                    // the stack of open elements.
+                    // Acknowledge the token's self-closing flag, if it is set.
+                    self.insert_void_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["hr"]) => {
+                    // If the stack of open elements has a p element in button scope, then close a
+                    // p element.
+                    self.close_p_element_if_in_button_scope();
+
+                    // Insert an HTML element for the token. Immediately pop the current node off
+                    // the stack of open elements.
+                    // Acknowledge the token's self-closing flag, if it is set.
+                    self.insert_void_html_element(token);
+
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
+                }
+                Token::Tag {
+                    start,
+                    attributes,
+                    self_closing,
+                    ..
+                } if token.is_start_tag_with_name(&["image"]) => {
+                    // Parse error.
+                    self.error("Unexpected image start tag");
+
+                    // Change the token's tag name to "img" and reprocess it. (Don't ask.)
+                    let token = Token::Tag {
+                        start: *start,
+                        tag_name: "img".to_string(),
+                        attributes: attributes.clone(),
+                        self_closing: *self_closing,
+                    };
+                    self.dispatch(&token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["textarea"]) => {
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+
+                    // If the next token is a U+000A LINE FEED (LF) character token, then ignore
+                    // that token and move on to the next one. (Newlines at the start of textarea
+                    // elements are ignored as an authoring convenience.)
+                    self.should_ignore_next_line_feed = true;
+
+                    // Switch the tokenizer to the RCDATA state.
+                    self.tokenizer.switch_to(tokenizer::State::RcData);
+
+                    // Let the original insertion mode be the current insertion mode.
+                    self.original_insertion_mode = self.insertion_mode;
+
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
+
+                    // Switch the insertion mode to "text".
+                    self.switch_insertion_mode(InsertionMode::Text);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["xmp"]) => {
+                    // If the stack of open elements has a p element in button scope, then close a
+                    // p element.
+                    self.close_p_element_if_in_button_scope();
+
+                    // Reconstruct the active formatting elements, if any.
+                    self.reconstruct_active_formatting_elements();
+
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
+
+                    // Follow the generic raw text element parsing algorithm.
+                    self.follow_generic_parsing_algorithm(token, ParsingAlgorithm::RawText);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["iframe"]) => {
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
+
+                    // Follow the generic raw text element parsing algorithm.
+                    self.follow_generic_parsing_algorithm(token, ParsingAlgorithm::RawText);
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&["noembed"])
+                        || (token.is_start_tag_with_name(&["noscript"])
+                            && self.parses_noscript_as_raw_text()) =>
+                {
+                    // Follow the generic raw text element parsing algorithm.
+                    self.follow_generic_parsing_algorithm(token, ParsingAlgorithm::RawText);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["select"]) => {
+                    // Reconstruct the active formatting elements, if any.
+                    self.reconstruct_active_formatting_elements();
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+
+                    // Set the frameset-ok flag to "not ok".
+                    self.frameset_ok = false;
+
+                    // If the insertion mode is one of "in table", "in caption", "in table body",
+                    // "in row", or "in cell", then switch the insertion mode to "in select in
+                    // table". Otherwise, switch the insertion mode to "in select".
+                    match self.insertion_mode {
+                        InsertionMode::InTable
+                        | InsertionMode::InCaption
+                        | InsertionMode::InTableBody
+                        | InsertionMode::InRow
+                        | InsertionMode::InCell => {
+                            self.switch_insertion_mode(InsertionMode::InSelectInTable)
+                        }
+                        _ => self.switch_insertion_mode(InsertionMode::InSelect),
+                    }
                 }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["select"]) => todo!(),
                 Token::Tag { .. } if token.is_start_tag_with_name(&["optgroup", "option"]) => {
-                    todo!()
+                    // If the current node is an option element, then pop the current node off
+                    // the stack of open elements.
+                    if self.current_node_is_element_with_tag_name("option") {
+                        self.stack_of_open_elements.pop();
+                    }
+
+                    // Reconstruct the active formatting elements, if any.
+                    self.reconstruct_active_formatting_elements();
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["rb", "rtc"]) => {
+                    // If the stack of open elements has a ruby element in scope, then generate
+                    // implied end tags. If the current node is not now a ruby element, this is
+                    // a parse error.
+                    if self
+                        .stack_of_open_elements
+                        .has_element_in_scope(self.arena, "ruby")
+                    {
+                        self.generate_implied_end_tags_except_for(None);
+                        if !self.current_node_is_element_with_tag_name("ruby") {
+                            self.error("Expected current node to be a ruby element");
+                        }
+                    }
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["rp", "rt"]) => {
+                    // If the stack of open elements has a ruby element in scope, then generate
+                    // implied end tags, except for rtc elements. If the current node is not now
+                    // a rtc element or a ruby element, this is a parse error.
+                    if self
+                        .stack_of_open_elements
+                        .has_element_in_scope(self.arena, "ruby")
+                    {
+                        self.generate_implied_end_tags_except_for(Some("rtc"));
+                        if !self.current_node_is_element_with_tag_name("rtc")
+                            && !self.current_node_is_element_with_tag_name("ruby")
+                        {
+                            self.error("Expected current node to be a rtc or ruby element");
+                        }
+                    }
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["math", "svg"]) => {
+                    // Reconstruct the active formatting elements, if any.
+                    self.reconstruct_active_formatting_elements();
+
+                    // Adjust MathML attributes for the token. (This fixes
+                    // the case of MathML attributes that are not all
+                    // lowercase.)
+                    // Adjust SVG attributes for the token. (This fixes the
+                    // case of SVG attributes that are not all lowercase.)
+                    // Adjust foreign attributes for the token. (This fixes
+                    // the use of namespaced attributes, in particular XLink.)
+                    // These are done when the element is created for the
+                    // token.
+
+                    // Insert a foreign element for the token, with the MathML
+                    // or SVG namespace respectively and false.
+                    let namespace = match token.is_start_tag_with_name(&["math"]) {
+                        true => Namespace::MathMl,
+                        false => Namespace::Svg,
+                    };
+                    self.insert_foreign_element(token, namespace, false);
+
+                    // If the token has its self-closing flag set, pop the
+                    // current node off the stack of open elements and
+                    // acknowledge the token's self-closing flag.
+                    if let Token::Tag {
+                        self_closing: true, ..
+                    } = token
+                    {
+                        self.stack_of_open_elements.pop();
+                        self.acknowledge_self_closing_flag();
+                    }
                 }
-                Token::Tag { .. } if token.is_start_tag_with_name(&["rb", "rtc"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["rp", "rt"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["math"]) => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["svg"]) => todo!(),
                 Token::Tag { .. }
                     if token.is_start_tag_with_name(&[
                         "caption", "col", "colgroup", "frame", "head", "tbody", "td", "tfoot",
                         "th", "thead", "tr",
                     ]) =>
                 {
-                    todo!()
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected tag in body");
                 }
                 Token::Tag { .. } if token.is_start_tag() => {
                     // Reconstruct the active formatting elements, if any.
-                    self.active_formatting_elements
-                        .reconstruct(&self.stack_of_open_elements);
+                    self.reconstruct_active_formatting_elements();
 
                     // Insert an HTML element for the token.
                     self.insert_html_element(token);
                 }
                 Token::Tag { .. } if token.is_end_tag() => {
-                    // Initialize node to be the current node (the bottommost node of the stack).
-                    for node in self.stack_of_open_elements.elements.clone().iter().rev() {
-                        // 2. Loop: If node is an HTML element with the same tag name as the token,
-                        // then:
-                        let token_tag_name = match token {
-                            Token::Tag { tag_name, .. } => tag_name,
-                            _ => panic!("Expected tag token"),
-                        };
-
-                        if self
-                            .arena
-                            .get_node(*node)
-                            .is_element_with_tag_name(&token_tag_name)
-                        {
-                            // 2.1. Generate implied end tags, except for HTML elements with the
-                            // same tag name as the token.
-                            self.generate_implied_end_tags_except_for(Some(&token_tag_name));
-
-                            // 2.2. If node is not the current node, then this is a parse error.
-                            if *node != self.stack_of_open_elements.current_node() {
-                                self.error("Unexpected tag");
-                            }
-
-                            // 2.3. Pop all the nodes from the current node up to node, including
-                            // node,
-                            self.stack_of_open_elements
-                                .pop_elements_until_element_has_been_popped(*node);
-
-                            // then stop these steps.
-                            break;
-                        } else {
-                            // 3. Otherwise, if node is in the special category,
-                            if self
-                                .arena
-                                .get_node(*node)
-                                .is_element_with_one_of_tag_names(SPECIAL_TAGS)
-                            {
-                                // then this is a parse error; ignore the token,
-                                self.error("Unexpected tag");
-                                // and return.
-                                return;
-                            }
-
-                            // 4. Set node to the previous entry in the stack of
-                            //    open elements.
-                            // 5. Return to the step labeled loop.
-                        }
-                    }
+                    self.handle_any_other_end_tag_in_body(token);
                 }
                 _ => unreachable!(),
             },
@@ -926,7 +2692,18 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                         );
                     }
                     Token::Tag { .. } if token.is_end_tag_with_name(&["script"]) => {
-                        todo!();
+                        // TODO: If the active speculative HTML parser is null and the JavaScript
+                        // execution context stack is empty, then perform a microtask checkpoint.
+
+                        // Let script be the current node (which will be a script element).
+                        // Pop the current node off the stack of open elements.
+                        self.stack_of_open_elements.pop();
+
+                        // Switch the insertion mode to the original insertion mode.
+                        self.switch_insertion_mode(self.original_insertion_mode);
+
+                        // TODO: Prepare the script element and run or postpone it, as scripts
+                        // are not executed yet.
                     }
                     _ => {
                         // Pop the current node off the stack of open elements.
@@ -938,143 +2715,1168 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                     }
                 }
             }
-            InsertionMode::InTable => todo!("InTable"),
-            InsertionMode::InTableText => todo!("InTableText"),
-            InsertionMode::InCaption => todo!("InCaption"),
-            InsertionMode::InColumnGroup => todo!("InColumnGroup"),
-            InsertionMode::InTableBody => todo!("InTableBody"),
-            InsertionMode::InRow => todo!("InRow"),
-            InsertionMode::InCell => todo!("InCell"),
-            InsertionMode::InSelect => todo!("InSelect"),
-            InsertionMode::InSelectInTable => todo!("InSelectInTable"),
-            InsertionMode::InTemplate => todo!("InTemplate"),
-            InsertionMode::AfterBody => match token {
-                whitespace!() => self.process_token(InsertionMode::InBody, token),
-                Token::Comment { .. } => todo!(),
-                Token::Doctype { .. } => todo!(),
-                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
-                    self.process_token(InsertionMode::InBody, token);
+            InsertionMode::InTable => match token {
+                Token::Character(_)
+                    if self
+                        .arena
+                        .get_node(self.stack_of_open_elements.current_node())
+                        .is_element_with_one_of_tag_names(&[
+                            "table", "tbody", "template", "tfoot", "thead", "tr",
+                        ]) =>
+                {
+                    // Let the pending table character tokens be an empty list of tokens.
+                    self.pending_table_character_tokens.clear();
+
+                    // Let the original insertion mode be the current insertion mode.
+                    self.original_insertion_mode = self.insertion_mode;
+
+                    // Switch the insertion mode to "in table text" and reprocess the token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InTableText);
                 }
-                Token::Tag { .. } if token.is_end_tag_with_name(&["html"]) => {
-                    self.process_token(InsertionMode::AfterAfterBody, token);
+                Token::Comment { .. } => {
+                    // Insert a comment.
+                    self.insert_comment(token, None);
                 }
-                Token::EndOfFile => self.stop_parsing(),
-                _ => todo!(),
-            },
-            InsertionMode::InFrameset => todo!("InFrameset"),
-            InsertionMode::AfterFrameset => todo!("AfterFrameset"),
-            InsertionMode::AfterAfterBody => match token {
-                Token::Comment { .. } => todo!(),
-                Token::Doctype { .. } => todo!(),
-                whitespace!() | Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
-                    self.process_token(InsertionMode::InBody, token);
+                Token::Doctype { .. } => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected DOCTYPE");
                 }
-                Token::EndOfFile => self.stop_parsing(),
-                _ => {
-                    self.error(format!("Unexpected token: {:?}", token).as_str());
+                Token::Tag { .. } if token.is_start_tag_with_name(&["caption"]) => {
+                    // Clear the stack back to a table context.
+                    self.clear_stack_back_to_table_context();
 
-                    self.switch_insertion_mode(InsertionMode::InBody);
+                    // Insert a marker at the end of the list of active formatting elements.
+                    self.active_formatting_elements.push_marker();
+
+                    // Insert an HTML element for the token, then switch the insertion mode to "in
+                    // caption".
+                    self.insert_html_element(token);
+                    self.switch_insertion_mode(InsertionMode::InCaption);
                 }
-            },
-            InsertionMode::AfterAfterFrameset => todo!("AfterAfterFrameset"),
-        }
-    }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["colgroup"]) => {
+                    // Clear the stack back to a table context.
+                    self.clear_stack_back_to_table_context();
 
-    /// https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
-    fn run_adoption_agency_algorithm(&mut self, token: &Token) {
-        // 1. Let subject be token's tag name.
-        let subject = match token {
-            Token::Tag { tag_name, .. } => tag_name,
-            _ => panic!("Expected tag token"),
-        };
+                    // Insert an HTML element for the token, then switch the insertion mode to "in
+                    // column group".
+                    self.insert_html_element(token);
+                    self.switch_insertion_mode(InsertionMode::InColumnGroup);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["col"]) => {
+                    // Clear the stack back to a table context.
+                    self.clear_stack_back_to_table_context();
 
-        // If the current node is an HTML element whose tag name is subject, and the
-        // current node is not in the list of active formatting elements, then pop the
-        // current node off the stack of open elements and return.
-        let current_node = self.stack_of_open_elements.current_node();
-        if self
-            .arena
-            .get_node(current_node)
-            .is_element_with_tag_name(&subject)
-            && !self.active_formatting_elements.contains(current_node)
-        {
-            self.stack_of_open_elements.pop();
-            return;
-        }
+                    // Insert an HTML element for a "colgroup" start tag token with no attributes,
+                    // then switch the insertion mode to "in column group".
+                    self.insert_html_element(&Token::Tag {
+                        start: true,
+                        tag_name: "colgroup".to_string(),
+                        attributes: vec![],
+                        self_closing: false,
+                    });
 
-        // Let outer loop counter be 0.
-        let mut outer_loop_counter = 0;
+                    // Reprocess the current token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InColumnGroup);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["tbody", "tfoot", "thead"]) => {
+                    // Clear the stack back to a table context.
+                    self.clear_stack_back_to_table_context();
 
-        // While true:
-        loop {
-            // If outer loop counter is greater than or equal to 8, then return.
-            if outer_loop_counter >= 8 {
-                return;
-            }
+                    // Insert an HTML element for the token, then switch the insertion mode to "in
+                    // table body".
+                    self.insert_html_element(token);
+                    self.switch_insertion_mode(InsertionMode::InTableBody);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["td", "th", "tr"]) => {
+                    // Clear the stack back to a table context.
+                    self.clear_stack_back_to_table_context();
 
-            // Increment outer loop counter by 1.
-            outer_loop_counter += 1;
+                    // Insert an HTML element for a "tbody" start tag token with no attributes,
+                    // then switch the insertion mode to "in table body".
+                    self.insert_html_element(&Token::Tag {
+                        start: true,
+                        tag_name: "tbody".to_string(),
+                        attributes: vec![],
+                        self_closing: false,
+                    });
 
-            // Let formatting element be the last element in the list of active
-            // formatting elements that:
-            //     * is between the end of the list and the last marker in the list, if any,
-            //       or the start of the list otherwise, and
-            //     * has the tag name subject.
-            let formatting_element = self
-                .active_formatting_elements
-                .last_element_with_tag_name_before_marker(&self.arena, &subject);
+                    // Reprocess the current token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InTableBody);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["table"]) => {
+                    // Parse error.
+                    self.error("Unexpected table start tag in table");
 
-            // If there is no such element, then return and instead act as described in the
-            // "any other end tag" entry above.
-            if formatting_element.is_none() {
-                todo!();
-            }
-            let formatting_element = formatting_element.unwrap();
+                    // If the stack of open elements does not have a table element in table scope,
+                    // ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_table_scope(self.arena, "table")
+                    {
+                        return;
+                    }
 
-            // If formatting element is not in the stack of open elements,
-            if !self.stack_of_open_elements.contains(formatting_element) {
-                // then this is a parse error;
-                self.error("Formatting element not in the stack of open elements");
-                // remove the element from the list
-                self.active_formatting_elements.remove(formatting_element);
-                // and return.
-                return;
-            }
+                    // Otherwise: Pop elements from this stack until a table element has been
+                    // popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "table");
 
-            // If formatting element is in the stack of open elements, but the element
-            // is not in scope,
-            let formatting_element_tag_name = match &self.arena.get_node(formatting_element).kind {
-                NodeKind::Element { tag_name, .. } => tag_name,
-                _ => panic!("Formatting element is not an element"),
-            };
-            if !self
-                .stack_of_open_elements
-                .has_element_in_scope(&self.arena, &formatting_element_tag_name)
-            {
-                // then this is a parse error; return.
-                self.error("Formatting element is not in scope");
-                return;
-            }
+                    // Reset the insertion mode appropriately.
+                    self.reset_insertion_mode_appropriately();
 
-            // If formatting element is not the current node,
-            if formatting_element != self.stack_of_open_elements.current_node() {
-                // this is a parse error. (But do not return.)
-                self.error("Formatting element is not the current node");
-            }
+                    // Reprocess the token.
+                    self.should_reprocess_token = true;
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["table"]) => {
+                    // If the stack of open elements does not have a table element in table scope,
+                    // this is a parse error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_table_scope(self.arena, "table")
+                    {
+                        self.error("Unexpected table end tag");
+                        return;
+                    }
 
-            // Let furthest block be the topmost node in the stack of open elements that
-            // is lower in the stack than formatting element, and is an element in the
-            // special category. There might not be one.
-            let furthest_block = self
-                .stack_of_open_elements
-                .topmost_special_node_below(&self.arena, formatting_element);
+                    // Otherwise: Pop elements from this stack until a table element has been
+                    // popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "table");
 
-            // If there is no furthest block, then the UA must first pop all the nodes
-            // from the bottom of the stack of open elements, from the current node up to
-            // and including formatting element,
-            if furthest_block.is_none() {
-                while formatting_element != self.stack_of_open_elements.current_node() {
-                    self.stack_of_open_elements.pop();
+                    // Reset the insertion mode appropriately.
+                    self.reset_insertion_mode_appropriately();
+                }
+                Token::Tag { .. }
+                    if token.is_end_tag_with_name(&[
+                        "body", "caption", "col", "colgroup", "html", "tbody", "td", "tfoot", "th",
+                        "thead", "tr",
+                    ]) =>
+                {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected end tag in table");
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&["style", "script", "template"])
+                        || token.is_end_tag_with_name(&["template"]) =>
+                {
+                    self.process_token(InsertionMode::InHead, token);
+                }
+                Token::Tag { attributes, .. }
+                    if token.is_start_tag_with_name(&["input"])
+                        && attributes.iter().any(|attribute| {
+                            attribute.name == "type"
+                                && attribute.value.eq_ignore_ascii_case("hidden")
+                        }) =>
+                {
+                    // If the token does not have an attribute with the name "type", or if it
+                    // does, but that attribute's value is not an ASCII case-insensitive match
+                    // for the string "hidden", then: act as described in the "anything else"
+                    // entry below.
+
+                    // Otherwise: Parse error.
+                    self.error("Unexpected hidden input in table");
+
+                    // Insert an HTML element for the token.
+                    // Pop that input element off the stack of open elements.
+                    // Acknowledge the token's self-closing flag, if it is set.
+                    self.insert_void_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["form"]) => {
+                    // Parse error.
+                    self.error("Unexpected form start tag in table");
+
+                    // If there is a template element on the stack of open elements, or if the
+                    // form element pointer is not null, ignore the token.
+                    if self.form_element_pointer.is_some()
+                        || self.stack_of_open_elements.has_template_element(self.arena)
+                    {
+                        return;
+                    }
+
+                    // Otherwise: Insert an HTML element for the token, and set the form element
+                    // pointer to point to the element created.
+                    let element = self.insert_html_element(token);
+                    self.form_element_pointer = Some(element);
+
+                    // Pop that form element off the stack of open elements.
+                    self.stack_of_open_elements.pop();
+                }
+                Token::EndOfFile => {
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                _ => {
+                    // Parse error. Enable foster parenting, process the token using the rules
+                    // for the "in body" insertion mode, and then disable foster parenting.
+                    self.error("Unexpected token in table");
+                    self.foster_parenting = true;
+                    self.process_token(InsertionMode::InBody, token);
+                    self.foster_parenting = false;
+                }
+            },
+            InsertionMode::InTableText => match token {
+                Token::Character('\0') => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected null character in table text");
+                }
+                Token::Character(character) => {
+                    // Append the character token to the pending table character tokens list.
+                    self.pending_table_character_tokens.push(*character);
+                }
+                _ => {
+                    let pending_characters =
+                        std::mem::take(&mut self.pending_table_character_tokens);
+
+                    // If any of the tokens in the pending table character tokens list are
+                    // character tokens that are not ASCII whitespace, then this is a parse error:
+                    // reprocess the character tokens in the pending table character tokens list
+                    // using the rules given in the "anything else" entry in the "in table"
+                    // insertion mode.
+                    if pending_characters.iter().any(|c| !c.is_ascii_whitespace()) {
+                        self.error("Unexpected non-whitespace characters in table");
+
+                        // Enable foster parenting, process the token using the rules for the "in
+                        // body" insertion mode, and then disable foster parenting.
+                        self.foster_parenting = true;
+                        for character in pending_characters {
+                            self.process_token(InsertionMode::InBody, &Token::Character(character));
+                        }
+                        self.foster_parenting = false;
+                    } else {
+                        // Otherwise, insert the characters given by the pending table character
+                        // tokens list.
+                        for character in pending_characters {
+                            self.insert_character(character);
+                        }
+                    }
+
+                    // Switch the insertion mode to the original insertion mode and reprocess the
+                    // token.
+                    self.switch_insertion_mode_and_reprocess_token(self.original_insertion_mode);
+                }
+            },
+            InsertionMode::InCaption => match token {
+                Token::Tag { .. } if token.is_end_tag_with_name(&["caption"]) => {
+                    self.close_the_caption();
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&[
+                        "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                    ]) || token.is_end_tag_with_name(&["table"]) =>
+                {
+                    // If the stack of open elements does not have a caption element in table
+                    // scope, this is a parse error; ignore the token. (fragment case)
+                    //
+                    // Otherwise: Generate implied end tags, ..., switch the insertion mode to
+                    // "in table" and reprocess the token.
+                    if self.close_the_caption() {
+                        self.should_reprocess_token = true;
+                    }
+                }
+                Token::Tag { .. }
+                    if token.is_end_tag_with_name(&[
+                        "body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead",
+                        "tr",
+                    ]) =>
+                {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected end tag in caption");
+                }
+                _ => {
+                    // Process the token using the rules for the "in body" insertion mode.
+                    self.process_token(InsertionMode::InBody, token);
+                }
+            },
+            InsertionMode::InColumnGroup => match token {
+                whitespace!() => {
+                    // Insert the character.
+                    let character = match token {
+                        Token::Character(character) => character,
+                        _ => unreachable!(),
+                    };
+                    self.insert_character(*character);
+                }
+                Token::Comment { .. } => {
+                    // Insert a comment.
+                    self.insert_comment(token, None);
+                }
+                Token::Doctype { .. } => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected DOCTYPE");
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    // Process the token using the rules for the "in body" insertion mode.
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["col"]) => {
+                    // Insert an HTML element for the token. Immediately pop the current node off
+                    // the stack of open elements. Acknowledge the token's self-closing flag, if it
+                    // is set.
+                    self.insert_void_html_element(token);
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["colgroup"]) => {
+                    // If the current node is not a colgroup element, then this is a parse error;
+                    // ignore the token.
+                    if !self.current_node_is_element_with_tag_name("colgroup") {
+                        self.error("Expected current node to be a colgroup element");
+                        return;
+                    }
+
+                    // Otherwise, pop the current node from the stack of open elements. Switch the
+                    // insertion mode to "in table".
+                    self.stack_of_open_elements.pop();
+                    self.switch_insertion_mode(InsertionMode::InTable);
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["col"]) => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected col end tag");
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&["template"])
+                        || token.is_end_tag_with_name(&["template"]) =>
+                {
+                    // Process the token using the rules for the "in head" insertion mode.
+                    self.process_token(InsertionMode::InHead, token);
+                }
+                Token::EndOfFile => {
+                    // Process the token using the rules for the "in body" insertion mode.
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                _ => {
+                    // If the current node is not a colgroup element, then this is a parse error;
+                    // ignore the token.
+                    if !self.current_node_is_element_with_tag_name("colgroup") {
+                        self.error("Expected current node to be a colgroup element");
+                        return;
+                    }
+
+                    // Otherwise, pop the current node off the stack of open elements.
+                    self.stack_of_open_elements.pop();
+
+                    // Switch the insertion mode to "in table".
+                    // Reprocess the token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InTable);
+                }
+            },
+            InsertionMode::InTableBody => match token {
+                Token::Tag { .. } if token.is_start_tag_with_name(&["tr"]) => {
+                    // Clear the stack back to a table body context.
+                    self.clear_stack_back_to_table_body_context();
+
+                    // Insert an HTML element for the token, then switch the insertion mode to "in
+                    // row".
+                    self.insert_html_element(token);
+                    self.switch_insertion_mode(InsertionMode::InRow);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["th", "td"]) => {
+                    // Parse error.
+                    self.error("Unexpected cell start tag in table body");
+
+                    // Clear the stack back to a table body context.
+                    self.clear_stack_back_to_table_body_context();
+
+                    // Insert an HTML element for a "tr" start tag token with no attributes, then
+                    // switch the insertion mode to "in row".
+                    self.insert_html_element(&Token::Tag {
+                        start: true,
+                        tag_name: "tr".to_string(),
+                        attributes: vec![],
+                        self_closing: false,
+                    });
+
+                    // Reprocess the current token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InRow);
+                }
+                Token::Tag { tag_name, .. }
+                    if token.is_end_tag_with_name(&["tbody", "tfoot", "thead"]) =>
+                {
+                    // If the stack of open elements does not have an element in table scope that
+                    // is an HTML element with the same tag name as the token, this is a parse
+                    // error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_table_scope(self.arena, tag_name)
+                    {
+                        self.error("Unexpected table body end tag");
+                        return;
+                    }
+
+                    // Otherwise: Clear the stack back to a table body context.
+                    self.clear_stack_back_to_table_body_context();
+
+                    // Pop the current node from the stack of open elements. Switch the insertion
+                    // mode to "in table".
+                    self.stack_of_open_elements.pop();
+                    self.switch_insertion_mode(InsertionMode::InTable);
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&[
+                        "caption", "col", "colgroup", "tbody", "tfoot", "thead",
+                    ]) || token.is_end_tag_with_name(&["table"]) =>
+                {
+                    // If the stack of open elements does not have a tbody, thead, or tfoot element
+                    // in table scope, this is a parse error; ignore the token.
+                    if !["tbody", "thead", "tfoot"].iter().any(|tag_name| {
+                        self.stack_of_open_elements
+                            .has_element_in_table_scope(self.arena, tag_name)
+                    }) {
+                        self.error("Unexpected tag in table body");
+                        return;
+                    }
+
+                    // Otherwise: Clear the stack back to a table body context.
+                    self.clear_stack_back_to_table_body_context();
+
+                    // Pop the current node from the stack of open elements. Switch the insertion
+                    // mode to "in table".
+                    self.stack_of_open_elements.pop();
+
+                    // Reprocess the token.
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InTable);
+                }
+                Token::Tag { .. }
+                    if token.is_end_tag_with_name(&[
+                        "body", "caption", "col", "colgroup", "html", "td", "th", "tr",
+                    ]) =>
+                {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected end tag in table body");
+                }
+                _ => {
+                    self.process_token(InsertionMode::InTable, token);
+                }
+            },
+            InsertionMode::InRow => match token {
+                Token::Tag { .. } if token.is_start_tag_with_name(&["th", "td"]) => {
+                    // Clear the stack back to a table row context.
+                    self.clear_stack_back_to_table_row_context();
+
+                    // Insert an HTML element for the token, then switch the insertion mode to "in
+                    // cell".
+                    self.insert_html_element(token);
+                    self.switch_insertion_mode(InsertionMode::InCell);
+
+                    // Insert a marker at the end of the list of active formatting elements.
+                    self.active_formatting_elements.push_marker();
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["tr"]) => {
+                    // If the stack of open elements does not have a tr element in table scope,
+                    // this is a parse error; ignore the token.
+                    // Otherwise, close the table row.
+                    self.close_the_table_row();
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&[
+                        "caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr",
+                    ]) || token.is_end_tag_with_name(&["table"]) =>
+                {
+                    // If the stack of open elements does not have a tr element in table scope,
+                    // this is a parse error; ignore the token.
+                    // Otherwise: Close the table row. Reprocess the token.
+                    if self.close_the_table_row() {
+                        self.should_reprocess_token = true;
+                    }
+                }
+                Token::Tag { tag_name, .. }
+                    if token.is_end_tag_with_name(&["tbody", "tfoot", "thead"]) =>
+                {
+                    // If the stack of open elements does not have an element in table scope that
+                    // is an HTML element with the same tag name as the token, this is a parse
+                    // error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_table_scope(self.arena, tag_name)
+                    {
+                        self.error("Unexpected table body end tag in row");
+                        return;
+                    }
+
+                    // If the stack of open elements does not have a tr element in table scope,
+                    // ignore the token.
+                    // Otherwise: Close the table row. Reprocess the token.
+                    if self.close_the_table_row() {
+                        self.should_reprocess_token = true;
+                    }
+                }
+                Token::Tag { .. }
+                    if token.is_end_tag_with_name(&[
+                        "body", "caption", "col", "colgroup", "html", "td", "th",
+                    ]) =>
+                {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected end tag in row");
+                }
+                _ => {
+                    self.process_token(InsertionMode::InTable, token);
+                }
+            },
+            InsertionMode::InCell => match token {
+                Token::Tag { tag_name, .. } if token.is_end_tag_with_name(&["td", "th"]) => {
+                    // If the stack of open elements does not have an element in table scope that
+                    // is an HTML element with the same tag name as that of the token, then this is
+                    // a parse error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_table_scope(self.arena, tag_name)
+                    {
+                        self.error("Unexpected cell end tag");
+                        return;
+                    }
+
+                    // Otherwise: Generate implied end tags.
+                    self.generate_implied_end_tags_except_for(None);
+
+                    // Now, if the current node is not an HTML element with the same tag name as
+                    // the token, then this is a parse error.
+                    if !self.current_node_is_element_with_tag_name(tag_name) {
+                        self.error("Expected current node to be the cell that is being closed");
+                    }
+
+                    // Pop elements from the stack of open elements until an HTML element with the
+                    // same tag name as the token has been popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, tag_name);
+
+                    // Clear the list of active formatting elements up to the last marker.
+                    self.active_formatting_elements.clear_up_to_last_marker();
+
+                    // Switch the insertion mode to "in row".
+                    self.switch_insertion_mode(InsertionMode::InRow);
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&[
+                        "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                    ]) =>
+                {
+                    // Assert: The stack of open elements has a td or th element in table scope.
+                    debug_assert!(
+                        self.stack_of_open_elements
+                            .has_element_in_table_scope(self.arena, "td")
+                            || self
+                                .stack_of_open_elements
+                                .has_element_in_table_scope(self.arena, "th")
+                    );
+
+                    // Close the cell and reprocess the token.
+                    self.close_the_cell();
+                    self.should_reprocess_token = true;
+                }
+                Token::Tag { .. }
+                    if token
+                        .is_end_tag_with_name(&["body", "caption", "col", "colgroup", "html"]) =>
+                {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected end tag in cell");
+                }
+                Token::Tag { tag_name, .. }
+                    if token.is_end_tag_with_name(&["table", "tbody", "tfoot", "thead", "tr"]) =>
+                {
+                    // If the stack of open elements does not have an element in table scope that
+                    // is an HTML element with the same tag name as that of the token, then this is
+                    // a parse error; ignore the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_table_scope(self.arena, tag_name)
+                    {
+                        self.error("Unexpected table end tag in cell");
+                        return;
+                    }
+
+                    // Otherwise, close the cell and reprocess the token.
+                    self.close_the_cell();
+                    self.should_reprocess_token = true;
+                }
+                _ => {
+                    self.process_token(InsertionMode::InBody, token);
+                }
+            },
+            InsertionMode::InSelect => match token {
+                Token::Character('\0') => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected null character");
+                }
+                Token::Character(character) => {
+                    // Insert the token's character.
+                    self.insert_character(*character);
+                }
+                Token::Comment { .. } => {
+                    // Insert a comment.
+                    self.insert_comment(token, None);
+                }
+                Token::Doctype { .. } => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected DOCTYPE");
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["option"]) => {
+                    // If the current node is an option element, pop that node from the stack of
+                    // open elements.
+                    if self.current_node_is_element_with_tag_name("option") {
+                        self.stack_of_open_elements.pop();
+                    }
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["optgroup"]) => {
+                    // If the current node is an option element, pop that node from the stack of
+                    // open elements.
+                    if self.current_node_is_element_with_tag_name("option") {
+                        self.stack_of_open_elements.pop();
+                    }
+
+                    // If the current node is an optgroup element, pop that node from the stack of
+                    // open elements.
+                    if self.current_node_is_element_with_tag_name("optgroup") {
+                        self.stack_of_open_elements.pop();
+                    }
+
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["hr"]) => {
+                    // If the current node is an option element, pop that node from the stack of
+                    // open elements.
+                    if self.current_node_is_element_with_tag_name("option") {
+                        self.stack_of_open_elements.pop();
+                    }
+
+                    // If the current node is an optgroup element, pop that node from the stack of
+                    // open elements.
+                    if self.current_node_is_element_with_tag_name("optgroup") {
+                        self.stack_of_open_elements.pop();
+                    }
+
+                    // Insert an HTML element for the token. Immediately pop the current node off
+                    // the stack of open elements.
+                    // Acknowledge the token's self-closing flag, if it is set.
+                    self.insert_void_html_element(token);
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["optgroup"]) => {
+                    // First, if the current node is an option element, and the node immediately
+                    // before it in the stack of open elements is an optgroup element, then pop
+                    // the current node from the stack of open elements.
+                    let current_node = self.stack_of_open_elements.current_node();
+                    if self.current_node_is_element_with_tag_name("option")
+                        && self
+                            .stack_of_open_elements
+                            .element_immediately_above(current_node)
+                            .is_some_and(|node| {
+                                self.arena
+                                    .get_node(node)
+                                    .is_element_with_tag_name("optgroup")
+                            })
+                    {
+                        self.stack_of_open_elements.pop();
+                    }
+
+                    // If the current node is an optgroup element, then pop that node from the
+                    // stack of open elements. Otherwise, this is a parse error; ignore the token.
+                    if self.current_node_is_element_with_tag_name("optgroup") {
+                        self.stack_of_open_elements.pop();
+                    } else {
+                        self.error("Unexpected optgroup end tag");
+                    }
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["option"]) => {
+                    // If the current node is an option element, then pop that node from the stack
+                    // of open elements. Otherwise, this is a parse error; ignore the token.
+                    if self.current_node_is_element_with_tag_name("option") {
+                        self.stack_of_open_elements.pop();
+                    } else {
+                        self.error("Unexpected option end tag");
+                    }
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["select"]) => {
+                    // If the stack of open elements does not have a select element in select
+                    // scope, this is a parse error; ignore the token. (fragment case)
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_select_scope(self.arena, "select")
+                    {
+                        self.error("Unexpected select end tag");
+                        return;
+                    }
+
+                    // Otherwise: Pop elements from the stack of open elements until a select
+                    // element has been popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "select");
+
+                    // Reset the insertion mode appropriately.
+                    self.reset_insertion_mode_appropriately();
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["select"]) => {
+                    // Parse error.
+                    self.error("Unexpected select start tag in select");
+
+                    // If the stack of open elements does not have a select element in select
+                    // scope, ignore the token. (fragment case)
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_select_scope(self.arena, "select")
+                    {
+                        return;
+                    }
+
+                    // Otherwise: Pop elements from the stack of open elements until a select
+                    // element has been popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "select");
+
+                    // Reset the insertion mode appropriately.
+                    self.reset_insertion_mode_appropriately();
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&["input", "keygen", "textarea"]) =>
+                {
+                    // Parse error.
+                    self.error("Unexpected tag in select");
+
+                    // If the stack of open elements does not have a select element in select
+                    // scope, ignore the token. (fragment case)
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_select_scope(self.arena, "select")
+                    {
+                        return;
+                    }
+
+                    // Otherwise: Pop elements from the stack of open elements until a select
+                    // element has been popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "select");
+
+                    // Reset the insertion mode appropriately.
+                    self.reset_insertion_mode_appropriately();
+
+                    // Reprocess the token.
+                    self.should_reprocess_token = true;
+                }
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&["script", "template"])
+                        || token.is_end_tag_with_name(&["template"]) =>
+                {
+                    self.process_token(InsertionMode::InHead, token);
+                }
+                Token::EndOfFile => {
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                _ => {
+                    // Parse error. Ignore the token.
+                    self.error(format!("Unexpected token in select: {:?}", token).as_str());
+                }
+            },
+            InsertionMode::InSelectInTable => match token {
+                Token::Tag { .. }
+                    if token.is_start_tag_with_name(&[
+                        "caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th",
+                    ]) =>
+                {
+                    // Parse error.
+                    self.error("Unexpected table tag in select");
+
+                    // Pop elements from the stack of open elements until a select element has
+                    // been popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "select");
+
+                    // Reset the insertion mode appropriately.
+                    self.reset_insertion_mode_appropriately();
+
+                    // Reprocess the token.
+                    self.should_reprocess_token = true;
+                }
+                Token::Tag { tag_name, .. }
+                    if token.is_end_tag_with_name(&[
+                        "caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th",
+                    ]) =>
+                {
+                    // Parse error.
+                    self.error("Unexpected table end tag in select");
+
+                    // If the stack of open elements does not have an element in table scope that
+                    // is an HTML element with the same tag name as that of the token, then ignore
+                    // the token.
+                    if !self
+                        .stack_of_open_elements
+                        .has_element_in_table_scope(self.arena, tag_name)
+                    {
+                        return;
+                    }
+
+                    // Otherwise: Pop elements from the stack of open elements until a select
+                    // element has been popped from the stack.
+                    self.stack_of_open_elements
+                        .pop_until_element_with_tag_name(self.arena, "select");
+
+                    // Reset the insertion mode appropriately.
+                    self.reset_insertion_mode_appropriately();
+
+                    // Reprocess the token.
+                    self.should_reprocess_token = true;
+                }
+                _ => {
+                    self.process_token(InsertionMode::InSelect, token);
+                }
+            },
+            InsertionMode::InTemplate => {
+                // TODO: Implement the "in template" insertion mode.
+                self.unsupported("template elements");
+                self.process_token(InsertionMode::InBody, token);
+            }
+            InsertionMode::AfterBody => match token {
+                whitespace!() => {
+                    // Process the token using the rules for the "in body" insertion mode.
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Comment { .. } => {
+                    // Insert a comment as the last child of the first element in the stack of
+                    // open elements (the html element).
+                    let html = self.stack_of_open_elements.elements[0];
+                    self.insert_comment(
+                        token,
+                        Some(InsertionLocation {
+                            parent: html,
+                            after_child: None,
+                        }),
+                    );
+                }
+                Token::Doctype { .. } => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected DOCTYPE after body");
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["html"]) => {
+                    // If the parser was created as part of the HTML fragment parsing algorithm,
+                    // this is a parse error; ignore the token. (fragment case)
+                    if self.context_element.is_some() {
+                        self.error("Unexpected html end tag");
+                        return;
+                    }
+
+                    // Otherwise, switch the insertion mode to "after after body".
+                    self.switch_insertion_mode(InsertionMode::AfterAfterBody);
+                }
+                Token::EndOfFile => self.stop_parsing(),
+                _ => {
+                    // Parse error. Switch the insertion mode to "in body" and reprocess the token.
+                    self.error(format!("Unexpected token after body: {}", token).as_str());
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InBody);
+                }
+            },
+            InsertionMode::InFrameset => match token {
+                whitespace!() => {
+                    // Insert the character.
+                    let character = match token {
+                        Token::Character(character) => character,
+                        _ => unreachable!(),
+                    };
+                    self.insert_character(*character);
+                }
+                Token::Comment { .. } => {
+                    // Insert a comment.
+                    self.insert_comment(token, None);
+                }
+                Token::Doctype { .. } => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected DOCTYPE");
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["frameset"]) => {
+                    // Insert an HTML element for the token.
+                    self.insert_html_element(token);
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["frameset"]) => {
+                    // If the current node is the root html element, then this is a parse error;
+                    // ignore the token. (fragment case)
+                    if self.stack_of_open_elements.elements.len() == 1 {
+                        self.error("Unexpected frameset end tag");
+                        return;
+                    }
+
+                    // Otherwise, pop the current node from the stack of open elements.
+                    self.stack_of_open_elements.pop();
+
+                    // If the parser was not created as part of the HTML fragment parsing algorithm
+                    // (fragment case), and the current node is no longer a frameset element, then
+                    // switch the insertion mode to "after frameset".
+                    if self.context_element.is_none()
+                        && !self
+                            .arena
+                            .get_node(self.stack_of_open_elements.current_node())
+                            .is_element_with_tag_name("frameset")
+                    {
+                        self.switch_insertion_mode(InsertionMode::AfterFrameset);
+                    }
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["frame"]) => {
+                    // Insert an HTML element for the token. Immediately pop the current node off
+                    // the stack of open elements.
+                    self.insert_html_element(token);
+                    self.stack_of_open_elements.pop();
+
+                    // Acknowledge the token's self-closing flag, if it is set.
+                    self.acknowledge_self_closing_flag();
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["noframes"]) => {
+                    self.process_token(InsertionMode::InHead, token);
+                }
+                Token::EndOfFile => {
+                    // If the current node is not the root html element, then this is a parse
+                    // error.
+                    if self.stack_of_open_elements.elements.len() != 1 {
+                        self.error("Unexpected end of file in frameset");
+                    }
+
+                    // Stop parsing.
+                    self.stop_parsing();
+                }
+                _ => {
+                    // Parse error. Ignore the token.
+                    self.error(format!("Unexpected token in frameset: {:?}", token).as_str());
+                }
+            },
+            InsertionMode::AfterFrameset => match token {
+                whitespace!() => {
+                    // Insert the character.
+                    let character = match token {
+                        Token::Character(character) => character,
+                        _ => unreachable!(),
+                    };
+                    self.insert_character(*character);
+                }
+                Token::Comment { .. } => {
+                    // Insert a comment.
+                    self.insert_comment(token, None);
+                }
+                Token::Doctype { .. } => {
+                    // Parse error. Ignore the token.
+                    self.error("Unexpected DOCTYPE");
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Tag { .. } if token.is_end_tag_with_name(&["html"]) => {
+                    self.switch_insertion_mode(InsertionMode::AfterAfterFrameset);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["noframes"]) => {
+                    self.process_token(InsertionMode::InHead, token);
+                }
+                Token::EndOfFile => self.stop_parsing(),
+                _ => {
+                    // Parse error. Ignore the token.
+                    self.error(format!("Unexpected token after frameset: {:?}", token).as_str());
+                }
+            },
+            InsertionMode::AfterAfterBody => match token {
+                Token::Comment { .. } => {
+                    // Insert a comment as the last child of the Document object.
+                    self.insert_comment(
+                        token,
+                        Some(InsertionLocation {
+                            parent: self.document,
+                            after_child: None,
+                        }),
+                    );
+                }
+                Token::Doctype { .. } | whitespace!() => {
+                    // Process the token using the rules for the "in body" insertion mode.
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    // Process the token using the rules for the "in body" insertion mode.
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::EndOfFile => self.stop_parsing(),
+                _ => {
+                    // Parse error. Switch the insertion mode to "in body" and reprocess the token.
+                    self.error(format!("Unexpected token: {:?}", token).as_str());
+                    self.switch_insertion_mode_and_reprocess_token(InsertionMode::InBody);
+                }
+            },
+            InsertionMode::AfterAfterFrameset => match token {
+                Token::Comment { .. } => {
+                    // Insert a comment as the last child of the Document object.
+                    self.insert_comment(
+                        token,
+                        Some(InsertionLocation {
+                            parent: self.document,
+                            after_child: None,
+                        }),
+                    );
+                }
+                Token::Doctype { .. } | whitespace!() => {
+                    // Process the token using the rules for the "in body" insertion mode.
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::Tag { .. } if token.is_start_tag_with_name(&["html"]) => {
+                    // Process the token using the rules for the "in body" insertion mode.
+                    self.process_token(InsertionMode::InBody, token);
+                }
+                Token::EndOfFile => self.stop_parsing(),
+                Token::Tag { .. } if token.is_start_tag_with_name(&["noframes"]) => {
+                    self.process_token(InsertionMode::InHead, token);
+                }
+                _ => {
+                    // Parse error. Ignore the token.
+                    self.error(format!("Unexpected token: {:?}", token).as_str());
+                }
+            },
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    ///
+    /// The "any other end tag" steps of the "in body" insertion mode.
+    fn handle_any_other_end_tag_in_body(&mut self, token: &Token) {
+        // Initialize node to be the current node (the bottommost node of the stack).
+        for node in self.stack_of_open_elements.elements.clone().iter().rev() {
+            // 2. Loop: If node is an HTML element with the same tag name as the token,
+            // then:
+            let token_tag_name = match token {
+                Token::Tag { tag_name, .. } => tag_name,
+                _ => panic!("Expected tag token"),
+            };
+
+            if self
+                .arena
+                .get_node(*node)
+                .is_element_with_tag_name(token_tag_name)
+            {
+                // 2.1. Generate implied end tags, except for HTML elements with the
+                // same tag name as the token.
+                self.generate_implied_end_tags_except_for(Some(token_tag_name));
+
+                // 2.2. If node is not the current node, then this is a parse error.
+                if *node != self.stack_of_open_elements.current_node() {
+                    self.error("Unexpected tag");
+                }
+
+                // 2.3. Pop all the nodes from the current node up to node, including
+                // node,
+                self.stack_of_open_elements
+                    .pop_elements_until_element_has_been_popped(*node);
+
+                // then stop these steps.
+                break;
+            } else {
+                // 3. Otherwise, if node is in the special category,
+                if self.arena.get_node(*node).is_special() {
+                    // then this is a parse error; ignore the token,
+                    self.error("Unexpected tag");
+                    // and return.
+                    return;
+                }
+
+                // 4. Set node to the previous entry in the stack of
+                //    open elements.
+                // 5. Return to the step labeled loop.
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+    fn run_adoption_agency_algorithm(&mut self, token: &Token) {
+        // 1. Let subject be token's tag name.
+        let subject = match token {
+            Token::Tag { tag_name, .. } => tag_name,
+            _ => panic!("Expected tag token"),
+        };
+
+        // If the current node is an HTML element whose tag name is subject, and the
+        // current node is not in the list of active formatting elements, then pop the
+        // current node off the stack of open elements and return.
+        let current_node = self.stack_of_open_elements.current_node();
+        if self
+            .arena
+            .get_node(current_node)
+            .is_element_with_tag_name(subject)
+            && !self.active_formatting_elements.contains(current_node)
+        {
+            self.stack_of_open_elements.pop();
+            return;
+        }
+
+        // Let outer loop counter be 0.
+        let mut outer_loop_counter = 0;
+
+        // While true:
+        loop {
+            // If outer loop counter is greater than or equal to 8, then return.
+            if outer_loop_counter >= 8 {
+                return;
+            }
+
+            // Increment outer loop counter by 1.
+            outer_loop_counter += 1;
+
+            // Let formatting element be the last element in the list of active
+            // formatting elements that:
+            //     * is between the end of the list and the last marker in the list, if any,
+            //       or the start of the list otherwise, and
+            //     * has the tag name subject.
+            let formatting_element = self
+                .active_formatting_elements
+                .last_element_with_tag_name_before_marker(self.arena, subject);
+
+            // If there is no such element, then return and instead act as described in the
+            // "any other end tag" entry above.
+            if formatting_element.is_none() {
+                self.handle_any_other_end_tag_in_body(token);
+                return;
+            }
+            let formatting_element = formatting_element.unwrap();
+
+            // If formatting element is not in the stack of open elements,
+            if !self.stack_of_open_elements.contains(formatting_element) {
+                // then this is a parse error;
+                self.error("Formatting element not in the stack of open elements");
+                // remove the element from the list
+                self.active_formatting_elements.remove(formatting_element);
+                // and return.
+                return;
+            }
+
+            // If formatting element is in the stack of open elements, but the element
+            // is not in scope,
+            let formatting_element_tag_name = match &self.arena.get_node(formatting_element).kind {
+                NodeKind::Element { tag_name, .. } => tag_name,
+                _ => panic!("Formatting element is not an element"),
+            };
+            if !self
+                .stack_of_open_elements
+                .has_element_in_scope(self.arena, formatting_element_tag_name)
+            {
+                // then this is a parse error; return.
+                self.error("Formatting element is not in scope");
+                return;
+            }
+
+            // If formatting element is not the current node,
+            if formatting_element != self.stack_of_open_elements.current_node() {
+                // this is a parse error. (But do not return.)
+                self.error("Formatting element is not the current node");
+            }
+
+            // Let furthest block be the topmost node in the stack of open elements that
+            // is lower in the stack than formatting element, and is an element in the
+            // special category. There might not be one.
+            let furthest_block = self
+                .stack_of_open_elements
+                .topmost_special_node_below(self.arena, formatting_element);
+
+            // If there is no furthest block, then the UA must first pop all the nodes
+            // from the bottom of the stack of open elements, from the current node up to
+            // and including formatting element,
+            if furthest_block.is_none() {
+                while formatting_element != self.stack_of_open_elements.current_node() {
+                    self.stack_of_open_elements.pop();
                 }
                 self.stack_of_open_elements.pop();
 
@@ -1100,10 +3902,15 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                 .unwrap();
 
             // Let node and last node be furthest block.
-            let mut node = furthest_block;
+            // NOTE: Removing or replacing node never changes the entries above it, so
+            //       node is tracked by its index in the stack of open elements.
             let mut last_node = furthest_block;
-
-            let node_above_node = self.stack_of_open_elements.element_immediately_above(node);
+            let mut node_index = self
+                .stack_of_open_elements
+                .elements
+                .iter()
+                .position(|element| *element == furthest_block)
+                .unwrap();
 
             // Let inner loop counter be 0.
             let mut inner_loop_count = 0;
@@ -1117,9 +3924,8 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                 // elements, or if node is no longer in the stack of open elements (e.g. because
                 // it got removed by this algorithm), the element that was immediately above
                 // node in the stack of open elements before node was removed.
-                if let Some(node_above_node) = node_above_node {
-                    node = node_above_node;
-                }
+                node_index -= 1;
+                let mut node = self.stack_of_open_elements.elements[node_index];
 
                 // If node is formatting element, then break.
                 if node == formatting_element {
@@ -1130,7 +3936,16 @@ impl<'input, 'arena> Parser<'input, 'arena> {
                 // active formatting elements, then remove node from the list of active
                 // formatting elements.
                 if inner_loop_count > 3 && self.active_formatting_elements.contains(node) {
+                    // NOTE: The bookmark is an index, so it has to move along when an entry
+                    //       before it is removed.
+                    let node_index = self
+                        .active_formatting_elements
+                        .first_index_of(node)
+                        .unwrap();
                     self.active_formatting_elements.remove(node);
+                    if bookmark > node_index {
+                        bookmark -= 1;
+                    }
                 }
 
                 // If node is not in the list of active formatting elements, then remove
@@ -1142,8 +3957,12 @@ impl<'input, 'arena> Parser<'input, 'arena> {
 
                 // Create an element for the token for which the element node was
                 // created, in the HTML namespace, with common ancestor as the intended parent;
-                let new_element =
-                    self.create_element_for_token(token, Namespace::Html, common_ancestor.unwrap());
+                let node_token = self.token_for_element(node);
+                let new_element = self.create_element_for_token(
+                    &node_token,
+                    Namespace::Html,
+                    common_ancestor.unwrap(),
+                );
 
                 // replace the entry for node in the list of active
                 // formatting elements with an entry for the new element,
@@ -1151,7 +3970,8 @@ impl<'input, 'arena> Parser<'input, 'arena> {
 
                 // replace the entry for node in the stack of open elements
                 // with an entry for the new element,
-                self.stack_of_open_elements.replace(node, new_element);
+                self.stack_of_open_elements
+                    .replace(self.arena, node, new_element);
 
                 // and let node be the new element.
                 node = new_element;
@@ -1187,7 +4007,12 @@ impl<'input, 'arena> Parser<'input, 'arena> {
 
             // Create an element for the token for which formatting element was created,
             // in the HTML namespace, with furthest block as the intended parent.
-            let new_element = self.create_element_for_token(token, Namespace::Html, furthest_block);
+            let formatting_element_token = self.token_for_element(formatting_element);
+            let new_element = self.create_element_for_token(
+                &formatting_element_token,
+                Namespace::Html,
+                furthest_block,
+            );
 
             // Take all of the child nodes of furthest block and append them to the
             // element created in the last step.
@@ -1200,7 +4025,14 @@ impl<'input, 'arena> Parser<'input, 'arena> {
             self.arena.append(new_element, furthest_block);
 
             // Remove formatting element from the list of active formatting elements,
+            let formatting_element_index = self
+                .active_formatting_elements
+                .first_index_of(formatting_element)
+                .unwrap();
             self.active_formatting_elements.remove(formatting_element);
+            if bookmark > formatting_element_index {
+                bookmark -= 1;
+            }
             // and insert the new element into the list of active formatting elements at the
             // position of the aforementioned bookmark.
             self.active_formatting_elements
@@ -1212,8 +4044,38 @@ impl<'input, 'arena> Parser<'input, 'arena> {
 
             // and insert the new element into the stack of open elements immediately below
             // the position of furthest block in that stack.
-            self.stack_of_open_elements
-                .insert_immediately_below(new_element, furthest_block);
+            self.stack_of_open_elements.insert_immediately_below(
+                self.arena,
+                new_element,
+                furthest_block,
+            );
+        }
+    }
+
+    /// Recreates the start tag token for which `element` was created, so that
+    /// a new element can be created for the same token.
+    fn token_for_element(&self, element: NodeId) -> Token {
+        match &self.arena.get_node(element).kind {
+            NodeKind::Element {
+                tag_name,
+                attributes,
+                ..
+            } => Token::Tag {
+                start: true,
+                tag_name: tag_name.clone(),
+                attributes: attributes
+                    .iter()
+                    .map(|attribute| tokenizer::Attribute {
+                        name: match &attribute.prefix {
+                            Some(prefix) => format!("{}:{}", prefix, attribute.local_name),
+                            None => attribute.local_name.clone(),
+                        },
+                        value: attribute.value.clone(),
+                    })
+                    .collect(),
+                self_closing: false,
+            },
+            _ => panic!("Expected element"),
         }
     }
 
@@ -1238,6 +4100,35 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         self.switch_insertion_mode(InsertionMode::Text);
     }
 
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-comment
+    fn insert_comment(&mut self, token: &Token, position: Option<InsertionLocation>) {
+        // Let data be the data given in the comment token being processed.
+        let data = match token {
+            Token::Comment { data } => data,
+            _ => panic!("Expected Token::Comment token, got {:?}", token),
+        };
+
+        // If position was specified, then let the adjusted insertion location be
+        // position. Otherwise, let adjusted insertion location be the
+        // appropriate place for inserting a node.
+        let adjusted_insertion_location =
+            position.unwrap_or_else(|| self.appropriate_place_for_inserting_node(None));
+
+        // Create a Comment node whose data attribute is set to data and whose
+        // node document is the same as that of the node in which the adjusted
+        // insertion location finds itself.
+        let comment = self
+            .arena
+            .create_node(Node::create_comment(self.document, data.clone()));
+
+        // Insert the newly created node at the adjusted insertion location.
+        self.arena.insert(
+            comment,
+            adjusted_insertion_location.parent,
+            adjusted_insertion_location.after_child,
+        );
+    }
+
     /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-character
     fn insert_character(&mut self, data: char) {
         // Let the adjusted insertion location be the appropriate place for
@@ -1291,7 +4182,7 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         let document = self
             .arena
             .get_node(adjusted_insertion_location.parent)
-            .node_document(self.arena);
+            .node_document();
 
         let text_node = Node::create_text(document, data.to_string());
         let text_node_id = self.arena.create_node(text_node);
@@ -1313,17 +4204,17 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         // given namespace, with the intended parent being the element in which
         // the adjusted insertion location finds itself.
         let element =
-            self.create_element_for_token(&token, namespace, adjusted_insertion_location.parent);
+            self.create_element_for_token(token, namespace, adjusted_insertion_location.parent);
 
         // If onlyAddToElementStack is false, then run insert an element at the
         // adjusted insertion location with element.
         if !only_add_to_element_stack {
-            adjusted_insertion_location.insert_element(&mut self.arena, element);
+            adjusted_insertion_location.insert_element(self.arena, element);
         }
 
         // Push element onto the stack of open elements so that it is the new
         // current node.
-        self.stack_of_open_elements.push(element);
+        self.stack_of_open_elements.push(self.arena, element);
 
         // Return element.
         element
@@ -1334,6 +4225,22 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         self.insert_foreign_element(token, Namespace::Html, false)
     }
 
+    /// https://html.spec.whatwg.org/multipage/parsing.html#acknowledge-self-closing-flag
+    fn acknowledge_self_closing_flag(&mut self) {
+        self.self_closing_flag_acknowledged = true;
+    }
+
+    /// Inserts an HTML element for a void element start tag and immediately
+    /// pops it off the stack of open elements, as void elements never have
+    /// any contents. This also acknowledges the token's self-closing flag.
+    fn insert_void_html_element(&mut self, token: &Token) -> NodeId {
+        let element = self.insert_html_element(token);
+        debug_assert!(self.arena.get_node(element).is_void_element());
+        self.stack_of_open_elements.pop();
+        self.acknowledge_self_closing_flag();
+        element
+    }
+
     /// https://html.spec.whatwg.org/multipage/parsing.html#create-an-element-for-the-token
     fn create_element_for_token(
         &mut self,
@@ -1351,14 +4258,11 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         // attributes of the given token.
 
         // Let document be intended parent's node document.
-        let document = self
-            .arena
-            .get_node(intended_parent)
-            .node_document(&self.arena);
+        let document = self.arena.get_node(intended_parent).node_document();
 
         // Let local name be the tag name of the token.
         let local_name = match token {
-            Token::Tag { tag_name, .. } => tag_name,
+            Token::Tag { tag_name, .. } => adjust_svg_tag_name(namespace, tag_name),
             _ => panic!("Expected Token::Tag token, got {:?}", token),
         };
 
@@ -1383,16 +4287,15 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         // document, localName, given namespace, null, and is. If will execute
         // script is true, set the synchronous custom elements flag; otherwise,
         // leave it unset.
-        let element = Node::create_element(
-            document,
-            local_name.clone(),
-            namespace,
-            None,
-            is,
-            execute_script,
-        );
+        let mut element =
+            Node::create_element(document, local_name, namespace, None, is, execute_script);
 
-        // TODO: Append each attribute in the given token to element.
+        // Append each attribute in the given token to element.
+        if let Token::Tag { attributes, .. } = token {
+            for attribute in attributes.iter() {
+                element.append_attribute(adjust_foreign_attributes(namespace, attribute));
+            }
+        }
 
         // If will execute script is true, then:
         if execute_script {
@@ -1436,8 +4339,15 @@ impl<'input, 'arena> Parser<'input, 'arena> {
 
         // Determine the adjusted insertion location using the first matching
         // steps from the following list:
-        let adjusted_insertion_location = if self.foster_parenting {
-            todo!("Implement foster parenting")
+        let adjusted_insertion_location = if self.foster_parenting
+            && self
+                .arena
+                .get_node(target)
+                .is_element_with_one_of_tag_names(&["table", "tbody", "tfoot", "thead", "tr"])
+        {
+            // If foster parenting is enabled and target is a table, tbody, tfoot, thead, or tr
+            // element:
+            self.foster_parenting_insertion_location()
         } else {
             // Let adjusted insertion location be inside target, after its last child (if
             // any).
@@ -1455,6 +4365,154 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         adjusted_insertion_location
     }
 
+    /// The adjusted insertion location for a node that is foster parented,
+    /// which is right before the last table in the stack of open elements.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#foster-parent
+    fn foster_parenting_insertion_location(&self) -> InsertionLocation {
+        let last_element_with_tag_name = |tag_name: &str| {
+            self.stack_of_open_elements
+                .elements
+                .iter()
+                .rev()
+                .find(|element| {
+                    self.arena
+                        .get_node(**element)
+                        .is_element_with_tag_name(tag_name)
+                })
+                .copied()
+        };
+
+        // 1. Let last template be the last template element in the stack of open elements,
+        //    if any.
+        // 2. Let last table be the last table element in the stack of open elements, if any.
+        let last_table = last_element_with_tag_name("table");
+
+        // TODO: 3. If there is a last template and either there is no last table, or there is
+        //    one, but last template is lower (more recently added) than last table in the
+        //    stack of open elements, then: let adjusted insertion location be inside last
+        //    template's template contents, after its last child (if any), and abort these
+        //    steps.
+
+        // 4. If there is no last table, then let adjusted insertion location be inside the
+        //    first element in the stack of open elements (the html element), after its last
+        //    child (if any), and abort these steps. (fragment case)
+        let Some(last_table) = last_table else {
+            return InsertionLocation {
+                parent: self.stack_of_open_elements.elements[0],
+                after_child: None,
+            };
+        };
+
+        // 5. If last table has a parent node, then let adjusted insertion location be inside
+        //    last table's parent node, immediately before last table, and abort these steps.
+        if let Some(parent) = self.arena.get_node(last_table).parent() {
+            return InsertionLocation {
+                parent,
+                after_child: Some(last_table),
+            };
+        }
+
+        // 6. Let previous element be the element immediately above last table in the stack of
+        //    open elements.
+        let previous_element = self
+            .stack_of_open_elements
+            .element_immediately_above(last_table)
+            .expect("The table is never the first element in the stack of open elements");
+
+        // 7. Let adjusted insertion location be inside previous element, after its last child
+        //    (if any).
+        InsertionLocation {
+            parent: previous_element,
+            after_child: None,
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reconstruct-the-active-formatting-elements
+    fn reconstruct_active_formatting_elements(&mut self) {
+        let is_marker_or_open_element =
+            |parser: &Self, index: usize| match parser.active_formatting_elements.elements[index] {
+                ActiveFormattingElement::Marker => true,
+                ActiveFormattingElement::Element(element) => {
+                    parser.stack_of_open_elements.contains(element)
+                }
+            };
+
+        // If there are no entries in the list of active formatting elements, then there is
+        // nothing to reconstruct; stop this algorithm.
+        if self.active_formatting_elements.elements.is_empty() {
+            return;
+        }
+
+        // If the last (most recently added) entry in the list of active formatting elements is
+        // a marker, or if it is an element that is in the stack of open elements, then there is
+        // nothing to reconstruct; stop this algorithm.
+        let last = self.active_formatting_elements.elements.len() - 1;
+        if is_marker_or_open_element(self, last) {
+            return;
+        }
+
+        // Let entry be the last (most recently added) element in the list of active formatting
+        // elements.
+        let mut entry = last;
+
+        loop {
+            // Rewind: If there are no entries before entry in the list of active formatting
+            // elements, then jump to the step labeled create.
+            if entry == 0 {
+                break;
+            }
+
+            // Let entry be the entry one earlier than entry in the list of active formatting
+            // elements.
+            entry -= 1;
+
+            // If entry is neither a marker nor an element that is also in the stack of open
+            // elements, go to the step labeled rewind.
+            if is_marker_or_open_element(self, entry) {
+                // Advance: Let entry be the element one later than entry in the list of active
+                // formatting elements.
+                entry += 1;
+                break;
+            }
+        }
+
+        loop {
+            // Create: Insert an HTML element for the token for which the element entry was
+            // created, to obtain new element.
+            let ActiveFormattingElement::Element(element) =
+                self.active_formatting_elements.elements[entry]
+            else {
+                unreachable!("Markers are never reconstructed");
+            };
+            let token = self.token_for_element(element);
+            let new_element = self.insert_html_element(&token);
+
+            // Replace the entry for entry in the list with an entry for new element.
+            self.active_formatting_elements.elements[entry] =
+                ActiveFormattingElement::Element(new_element);
+
+            // If the entry for new element in the list of active formatting elements is not the
+            // last entry in the list, return to the step labeled advance.
+            if entry == last {
+                break;
+            }
+            entry += 1;
+        }
+    }
+
+    /// Closes a p element if the stack of open elements has a p element in
+    /// button scope, which is the first step for most start tags of block
+    /// level elements in the "in body" insertion mode.
+    fn close_p_element_if_in_button_scope(&mut self) {
+        if self
+            .stack_of_open_elements
+            .has_element_in_button_scope(self.arena, "p")
+        {
+            self.close_p_element();
+        }
+    }
+
     /// https://html.spec.whatwg.org/multipage/parsing.html#close-a-p-element
     fn close_p_element(&mut self) {
         // Generate implied end tags, except for p elements.
@@ -1472,7 +4530,7 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         // Pop elements from the stack of open elements until a p element has been
         // popped from the stack.
         self.stack_of_open_elements
-            .pop_until_element_with_tag_name(&self.arena, "p");
+            .pop_until_element_with_tag_name(self.arena, "p");
     }
 
     /// https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
@@ -1502,6 +4560,323 @@ impl<'input, 'arena> Parser<'input, 'arena> {
         }
     }
 
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-the-cell
+    fn close_the_cell(&mut self) {
+        // Generate implied end tags.
+        self.generate_implied_end_tags_except_for(None);
+
+        // If the current node is not now a td element or a th element, then this is a parse
+        // error.
+        if !self
+            .arena
+            .get_node(self.stack_of_open_elements.current_node())
+            .is_element_with_one_of_tag_names(&["td", "th"])
+        {
+            self.error("Expected current node to be a td or th element while closing a cell");
+        }
+
+        // Pop elements from the stack of open elements until a td element or a th element has
+        // been popped from the stack.
+        self.stack_of_open_elements
+            .pop_until_element_with_one_of_tag_names(self.arena, &["td", "th"]);
+
+        // Clear the list of active formatting elements up to the last marker.
+        self.active_formatting_elements.clear_up_to_last_marker();
+
+        // Switch the insertion mode to "in row".
+        self.switch_insertion_mode(InsertionMode::InRow);
+    }
+
+    /// Closes the current table row, like the steps for a "tr" end tag in the
+    /// "in row" insertion mode. Returns `false` when there is no tr element in
+    /// table scope, in which case this is a parse error and nothing happens.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intr
+    fn close_the_table_row(&mut self) -> bool {
+        // If the stack of open elements does not have a tr element in table scope, this is a
+        // parse error; ignore the token.
+        if !self
+            .stack_of_open_elements
+            .has_element_in_table_scope(self.arena, "tr")
+        {
+            self.error("Expected a tr element in table scope while closing a table row");
+            return false;
+        }
+
+        // Otherwise: Clear the stack back to a table row context.
+        self.clear_stack_back_to_table_row_context();
+
+        // Pop the current node (which will be a tr element) from the stack of open elements.
+        // Switch the insertion mode to "in table body".
+        self.stack_of_open_elements.pop();
+        self.switch_insertion_mode(InsertionMode::InTableBody);
+
+        true
+    }
+
+    /// Closes the current caption, like the steps for a "caption" end tag in
+    /// the "in caption" insertion mode. Returns `false` when there is no
+    /// caption element in table scope, in which case this is a parse error and
+    /// nothing happens.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incaption
+    fn close_the_caption(&mut self) -> bool {
+        // If the stack of open elements does not have a caption element in table scope, this
+        // is a parse error; ignore the token. (fragment case)
+        if !self
+            .stack_of_open_elements
+            .has_element_in_table_scope(self.arena, "caption")
+        {
+            self.error("Expected a caption element in table scope while closing a caption");
+            return false;
+        }
+
+        // Otherwise: Generate implied end tags.
+        self.generate_implied_end_tags_except_for(None);
+
+        // Now, if the current node is not a caption element, then this is a parse error.
+        if !self.current_node_is_element_with_tag_name("caption") {
+            self.error("Expected current node to be a caption element");
+        }
+
+        // Pop elements from this stack until a caption element has been popped from the stack.
+        self.stack_of_open_elements
+            .pop_until_element_with_tag_name(self.arena, "caption");
+
+        // Clear the list of active formatting elements up to the last marker.
+        self.active_formatting_elements.clear_up_to_last_marker();
+
+        // Switch the insertion mode to "in table".
+        self.switch_insertion_mode(InsertionMode::InTable);
+
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-context
+    fn clear_stack_back_to_table_context(&mut self) {
+        self.clear_stack_back_to(&["table", "template", "html"]);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-body-context
+    fn clear_stack_back_to_table_body_context(&mut self) {
+        self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-row-context
+    fn clear_stack_back_to_table_row_context(&mut self) {
+        self.clear_stack_back_to(&["tr", "template", "html"]);
+    }
+
+    fn clear_stack_back_to(&mut self, tag_names: &[&str]) {
+        // While the current node is not one of the given elements, pop elements from the stack
+        // of open elements.
+        while !self
+            .arena
+            .get_node(self.stack_of_open_elements.current_node())
+            .is_element_with_one_of_tag_names(tag_names)
+        {
+            self.stack_of_open_elements.pop();
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately
+    fn reset_insertion_mode_appropriately(&mut self) {
+        let elements = self.stack_of_open_elements.elements.clone();
+
+        // Let last be false.
+        let mut last = false;
+
+        // Let node be the last node in the stack of open elements.
+        for (index, node) in elements.iter().enumerate().rev() {
+            // Loop: If node is the first node in the stack of open elements, then set last
+            // to true, and, if the parser was created as part of the HTML fragment parsing
+            // algorithm (fragment case), set node to the context element passed to that
+            // algorithm.
+            let mut node = *node;
+            if index == 0 {
+                last = true;
+                if let Some(context) = self.context_element {
+                    node = context;
+                }
+            }
+
+            let node = self.arena.get_node(node);
+
+            // If node is a select element, run these substeps:
+            if node.is_element_with_tag_name("select") {
+                // If last is true, jump to the step below labeled done.
+                if !last {
+                    // Let ancestor be node.
+                    // Loop: If ancestor is the first node in the stack of open elements,
+                    // jump to the step below labeled done.
+                    // Let ancestor be the node before ancestor in the stack of open
+                    // elements.
+                    for ancestor in elements[..index].iter().rev() {
+                        let ancestor = self.arena.get_node(*ancestor);
+
+                        // If ancestor is a template node, jump to the step below labeled
+                        // done.
+                        if ancestor.is_element_with_tag_name("template") {
+                            break;
+                        }
+
+                        // If ancestor is a table node, switch the insertion mode to "in
+                        // select in table" and return.
+                        if ancestor.is_element_with_tag_name("table") {
+                            self.switch_insertion_mode(InsertionMode::InSelectInTable);
+                            return;
+                        }
+
+                        // Return to the step labeled loop.
+                    }
+                }
+
+                // Done: Switch the insertion mode to "in select" and return.
+                self.switch_insertion_mode(InsertionMode::InSelect);
+                return;
+            }
+
+            // If node is a td or th element and last is false, then switch the insertion
+            // mode to "in cell" and return.
+            if node.is_element_with_one_of_tag_names(&["td", "th"]) && !last {
+                self.switch_insertion_mode(InsertionMode::InCell);
+                return;
+            }
+
+            // If node is a tr element, then switch the insertion mode to "in row" and
+            // return.
+            if node.is_element_with_tag_name("tr") {
+                self.switch_insertion_mode(InsertionMode::InRow);
+                return;
+            }
+
+            // If node is a tbody, thead, or tfoot element, then switch the insertion mode
+            // to "in table body" and return.
+            if node.is_element_with_one_of_tag_names(&["tbody", "thead", "tfoot"]) {
+                self.switch_insertion_mode(InsertionMode::InTableBody);
+                return;
+            }
+
+            // If node is a caption element, then switch the insertion mode to "in
+            // caption" and return.
+            if node.is_element_with_tag_name("caption") {
+                self.switch_insertion_mode(InsertionMode::InCaption);
+                return;
+            }
+
+            // If node is a colgroup element, then switch the insertion mode to "in column
+            // group" and return.
+            if node.is_element_with_tag_name("colgroup") {
+                self.switch_insertion_mode(InsertionMode::InColumnGroup);
+                return;
+            }
+
+            // If node is a table element, then switch the insertion mode to "in table"
+            // and return.
+            if node.is_element_with_tag_name("table") {
+                self.switch_insertion_mode(InsertionMode::InTable);
+                return;
+            }
+
+            // If node is a template element, then switch the insertion mode to the
+            // current template insertion mode and return.
+            if node.is_element_with_tag_name("template") {
+                // TODO: Switch to the current template insertion mode instead, once
+                //       there is a stack of template insertion modes.
+                self.unsupported("template elements");
+                self.switch_insertion_mode(InsertionMode::InBody);
+                return;
+            }
+
+            // If node is a head element and last is false, then switch the insertion mode
+            // to "in head" and return.
+            if node.is_element_with_tag_name("head") && !last {
+                self.switch_insertion_mode(InsertionMode::InHead);
+                return;
+            }
+
+            // If node is a body element, then switch the insertion mode to "in body" and
+            // return.
+            if node.is_element_with_tag_name("body") {
+                self.switch_insertion_mode(InsertionMode::InBody);
+                return;
+            }
+
+            // If node is a frameset element, then switch the insertion mode to "in
+            // frameset" and return. (fragment case)
+            if node.is_element_with_tag_name("frameset") {
+                self.switch_insertion_mode(InsertionMode::InFrameset);
+                return;
+            }
+
+            // If node is an html element, run these substeps:
+            if node.is_element_with_tag_name("html") {
+                // If the head element pointer is null, switch the insertion mode to
+                // "before head" and return. (fragment case)
+                // Otherwise, the head element pointer is not null, switch the insertion
+                // mode to "after head" and return.
+                match self.head_element {
+                    None => self.switch_insertion_mode(InsertionMode::BeforeHead),
+                    Some(_) => self.switch_insertion_mode(InsertionMode::AfterHead),
+                }
+                return;
+            }
+
+            // If last is true, then switch the insertion mode to "in body" and return.
+            // (fragment case)
+            if last {
+                self.switch_insertion_mode(InsertionMode::InBody);
+                return;
+            }
+
+            // Let node now be the node before node in the stack of open elements.
+            // Return to the step labeled loop.
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adjusted-current-node
+    fn adjusted_current_node(&self) -> NodeId {
+        // The adjusted current node is the context element if the parser was
+        // created as part of the HTML fragment parsing algorithm and the stack
+        // of open elements has only one element in it (fragment case);
+        // otherwise, the adjusted current node is the current node.
+        match self.context_element {
+            Some(context) if self.stack_of_open_elements.elements.len() == 1 => context,
+            _ => self.stack_of_open_elements.current_node(),
+        }
+    }
+
+    fn current_node_is_element_with_tag_name(&self, tag_name: &str) -> bool {
+        self.arena
+            .get_node(self.stack_of_open_elements.current_node())
+            .is_element_with_tag_name(tag_name)
+    }
+
+    /// Whether there is a node in the stack of open elements that is not either
+    /// a dd element, a dt element, an li element, an optgroup element, an
+    /// option element, a p element, an rb element, an rp element, an rt
+    /// element, an rtc element, a tbody element, a td element, a tfoot
+    /// element, a th element, a thead element, a tr element, the body element,
+    /// or the html element.
+    fn has_open_element_that_is_not_implicitly_closed(&self) -> bool {
+        self.stack_of_open_elements.elements.iter().any(|element| {
+            !self
+                .arena
+                .get_node(*element)
+                .is_element_with_one_of_tag_names(&[
+                    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc", "tbody",
+                    "td", "tfoot", "th", "thead", "tr", "body", "html",
+                ])
+        })
+    }
+
+    /// Whether `<noscript>` elements are parsed as raw text, which is the case
+    /// when the scripting flag is enabled, unless
+    /// [ParserOptions::noscript_as_markup] is set.
+    fn parses_noscript_as_raw_text(&self) -> bool {
+        self.scripting && !self.options.noscript_as_markup
+    }
+
     fn stop_parsing(&mut self) {
         self.should_stop_parsing = true;
     }
@@ -1521,30 +4896,43 @@ impl<'input, 'arena> Parser<'input, 'arena> {
             return false;
         }
 
-        let acn = self
-            .arena
-            .get_node(self.stack_of_open_elements.adjusted_current_node());
+        let acn = self.arena.get_node(self.adjusted_current_node());
 
         // If the adjusted current node is an element in the HTML namespace
         if acn.is_element_in_namespace(Namespace::Html) {
             return false;
         }
 
-        // TODO: If the adjusted current node is a MathML text integration point and the
+        // If the adjusted current node is a MathML text integration point and the
         // token is a start tag whose tag name is neither "mglyph" nor
         // "malignmark"
-
-        // TODO: If the adjusted current node is a MathML text integration point and the
+        // If the adjusted current node is a MathML text integration point and the
         // token is a character token
+        if acn.is_mathml_text_integration_point()
+            && ((token.is_start_tag() && !token.is_start_tag_with_name(&["mglyph", "malignmark"]))
+                || matches!(token, Token::Character(_)))
+        {
+            return false;
+        }
 
-        // TODO: If the adjusted current node is a MathML annotation-xml element and the
+        // If the adjusted current node is a MathML annotation-xml element and the
         // token is a start tag whose tag name is "svg"
+        if acn.is_element_in_namespace(Namespace::MathMl)
+            && acn.is_element_with_tag_name("annotation-xml")
+            && token.is_start_tag_with_name(&["svg"])
+        {
+            return false;
+        }
 
-        // TODO: If the adjusted current node is an HTML integration point and the token
+        // If the adjusted current node is an HTML integration point and the token
         // is a start tag
-
-        // TODO: If the adjusted current node is an HTML integration point and the token
+        // If the adjusted current node is an HTML integration point and the token
         // is a character token
+        if acn.is_html_integration_point()
+            && (token.is_start_tag() || matches!(token, Token::Character(_)))
+        {
+            return false;
+        }
 
         // If the token is an end-of-file token
         if token == &Token::EndOfFile {
@@ -1555,10 +4943,77 @@ impl<'input, 'arena> Parser<'input, 'arena> {
     }
 
     fn error(&mut self, message: &str) {
-        eprintln!("Parser error: {}", message);
+        let open_elements = if self.options.report_open_elements {
+            self.stack_of_open_elements
+                .elements
+                .iter()
+                .map(|element| self.arena.get_node(*element).qualified_name())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        self.report(ParseError::Recoverable {
+            message: message.to_string(),
+            open_elements,
+        });
+    }
+
+    /// Reports that the input uses `feature`, which the parser does not
+    /// implement yet. The caller recovers from it as best it can.
+    fn unsupported(&mut self, feature: &str) {
+        self.report(ParseError::Unsupported {
+            feature: feature.to_string(),
+        });
+    }
+
+    fn report(&mut self, error: ParseError) {
+        if self.aborted {
+            return;
+        }
+
+        if !matches!(error, ParseError::TooManyErrors { .. }) {
+            self.error_count += 1;
+            if self
+                .options
+                .max_errors
+                .is_some_and(|max_errors| self.error_count > max_errors)
+            {
+                return;
+            }
+        }
+
+        let Some(on_error) = &mut self.options.on_error else {
+            return;
+        };
+
+        if on_error(&error).is_break() {
+            self.aborted = true;
+        }
+    }
+
+    /// Adds each attribute on `token` that is not present on `element` yet
+    /// to that element.
+    fn add_missing_attributes(&mut self, element: NodeId, token: &Token) {
+        let Token::Tag { attributes, .. } = token else {
+            return;
+        };
+
+        let element = self.arena.get_node_mut(element);
+        for attribute in attributes {
+            if element.get_attribute(&attribute.name).is_none() {
+                element.append_attribute(Attribute::new(
+                    attribute.name.clone(),
+                    attribute.value.clone(),
+                ));
+            }
+        }
     }
 }
 
+/// The HTML elements in the special category, see [Node::is_special].
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#special
 pub static SPECIAL_TAGS: &[&str] = &[
     "address",
     "applet",
@@ -1643,17 +5098,14 @@ pub static SPECIAL_TAGS: &[&str] = &[
     "ul",
     "wbr",
     "xmp",
-    // FIXME: Implement MathML mi
-    // FIXME: Implement MathML mo
-    // FIXME: Implement MathML mn
-    // FIXME: Implement MathML ms
-    // FIXME: Implement MathML mtext
-    // FIXME: Implement MathML annotation-xml
-    // FIXME: Implement SVG foreignObject
-    // FIXME: Implement SVG desc
-    // FIXME: Implement SVG title
 ];
 
+/// The MathML elements in the special category, see [SPECIAL_TAGS].
+pub static SPECIAL_MATHML_TAGS: &[&str] = &["mi", "mo", "mn", "ms", "mtext", "annotation-xml"];
+
+/// The SVG elements in the special category, see [SPECIAL_TAGS].
+pub static SPECIAL_SVG_TAGS: &[&str] = &["foreignObject", "desc", "title"];
+
 pub static BASE_SCOPE_TAGS: &[&str] = &[
     "applet",
     "caption",
@@ -1679,11 +5131,23 @@ pub static BASE_SCOPE_TAGS: &[&str] = &[
 #[derive(Debug, Clone, PartialEq)]
 struct StackOfOpenElements {
     elements: Vec<NodeId>,
+    /// The tag names of `elements`, so they can be counted without looking
+    /// them up in the arena when they are popped.
+    tag_names: Vec<String>,
+    /// The number of elements on the stack with each tag name. The scope
+    /// checks use this to fail right away when no target element is open,
+    /// instead of walking the whole stack for every start tag of a deeply
+    /// nested document.
+    tag_name_counts: HashMap<String, usize>,
 }
 
 impl StackOfOpenElements {
     pub fn new() -> Self {
-        Self { elements: vec![] }
+        Self {
+            elements: vec![],
+            tag_names: vec![],
+            tag_name_counts: HashMap::new(),
+        }
     }
 
     pub fn is_empty(&self) -> bool {
@@ -1694,7 +5158,19 @@ impl StackOfOpenElements {
         self.elements.contains(&node)
     }
 
+    pub fn has_template_element(&self, arena: &NodeArena) -> bool {
+        self.elements.iter().any(|element| {
+            arena
+                .get_node(*element)
+                .is_element_with_tag_name("template")
+        })
+    }
+
     /// https://html.spec.whatwg.org/multipage/parsing.html#current-node
+    ///
+    /// The stack is only empty in the "initial" and "before html" insertion
+    /// modes, which insert comments and the DOCTYPE into the document directly
+    /// and never look at the current node.
     pub fn current_node(&self) -> NodeId {
         *self
             .elements
@@ -1702,23 +5178,42 @@ impl StackOfOpenElements {
             .expect("Should always have a value. If not the parser should have finished.")
     }
 
-    /// https://html.spec.whatwg.org/multipage/parsing.html#adjusted-current-node
-    fn adjusted_current_node(&self) -> NodeId {
-        // TODO: The adjusted current node is the context element
-        // if the parser was created as part of the
-        // HTML fragment parsing algorithm and the stack of open elements
-        // has only one element in it (fragment case);
+    pub fn push(&mut self, arena: &NodeArena, element: NodeId) {
+        self.insert(arena, self.elements.len(), element);
+    }
 
-        // otherwise, the adjusted current node is the current node.
-        self.current_node()
+    pub fn pop(&mut self) -> Option<NodeId> {
+        if self.elements.is_empty() {
+            return None;
+        }
+        Some(self.remove(self.elements.len() - 1))
     }
 
-    pub fn push(&mut self, element: NodeId) {
-        self.elements.push(element);
+    fn insert(&mut self, arena: &NodeArena, index: usize, element: NodeId) {
+        let tag_name = match &arena.get_node(element).kind {
+            NodeKind::Element { tag_name, .. } => tag_name.clone(),
+            _ => String::new(),
+        };
+        *self.tag_name_counts.entry(tag_name.clone()).or_default() += 1;
+        self.elements.insert(index, element);
+        self.tag_names.insert(index, tag_name);
     }
 
-    pub fn pop(&mut self) -> Option<NodeId> {
-        self.elements.pop()
+    fn remove(&mut self, index: usize) -> NodeId {
+        let tag_name = self.tag_names.remove(index);
+        if let Some(count) = self.tag_name_counts.get_mut(&tag_name) {
+            *count -= 1;
+        }
+        self.elements.remove(index)
+    }
+
+    /// Whether an element with one of `tag_names` is on the stack.
+    fn has_element_with_one_of_tag_names(&self, tag_names: &[&str]) -> bool {
+        tag_names.iter().any(|tag_name| {
+            self.tag_name_counts
+                .get(*tag_name)
+                .is_some_and(|count| *count > 0)
+        })
     }
 
     pub fn pop_until_element_with_tag_name(&mut self, arena: &NodeArena, tag_name: &str) {
@@ -1730,7 +5225,7 @@ impl StackOfOpenElements {
         arena: &NodeArena,
         tag_names: &[&str],
     ) {
-        while let Some(node) = self.elements.pop() {
+        while let Some(node) = self.pop() {
             if arena
                 .get_node(node)
                 .is_element_with_one_of_tag_names(tag_names)
@@ -1741,7 +5236,7 @@ impl StackOfOpenElements {
     }
 
     pub fn pop_elements_until_element_has_been_popped(&mut self, element: NodeId) {
-        while let Some(node) = self.elements.pop() {
+        while let Some(node) = self.pop() {
             if node == element {
                 break;
             }
@@ -1754,21 +5249,37 @@ impl StackOfOpenElements {
         target_node: &str,
         tag_names: &[&str],
     ) -> bool {
+        self.has_one_of_elements_in_specific_scope(arena, &[target_node], tag_names)
+    }
+
+    /// Like [StackOfOpenElements::has_element_in_specific_scope], but the
+    /// target node can have any of the `target_nodes` tag names, like "an
+    /// element in scope whose tag name is one of "h1", ..., "h6"".
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-the-specific-scope
+    pub fn has_one_of_elements_in_specific_scope(
+        &self,
+        arena: &NodeArena,
+        target_nodes: &[&str],
+        tag_names: &[&str],
+    ) -> bool {
+        if !self.has_element_with_one_of_tag_names(target_nodes) {
+            return false;
+        }
+
         // 1. Initialize node to be the current node (the bottommost node of the stack).
         for node in self.elements.iter().rev() {
             let node = arena.get_node(*node);
 
             // 2. If node is the target node, terminate in a match state.
-            if node.is_element_with_tag_name(target_node) {
+            if node.is_element_with_one_of_tag_names(target_nodes) {
                 return true;
             }
 
             // 3. Otherwise, if node is one of the element types in list, terminate in
             // a failure state.
-            for tag_name in tag_names.iter() {
-                if node.is_element_with_tag_name(tag_name) {
-                    return false;
-                }
+            if node.is_element_with_one_of_tag_names(tag_names) {
+                return false;
             }
 
             // 4. Otherwise, set node to the previous entry in the stack of open
@@ -1777,11 +5288,38 @@ impl StackOfOpenElements {
             // the top of the stack — an html element — is reached.)
         }
 
-        unreachable!()
+        // The html element is always at the top of the stack when parsing a
+        // document, but the stack can run out before reaching it, e.g. after
+        // popping everything in the fragment case. There is no target node in
+        // scope then.
+        false
     }
 
     pub fn has_element_in_scope(&self, arena: &NodeArena, element: &str) -> bool {
-        self.has_element_in_specific_scope(arena, element, &BASE_SCOPE_TAGS)
+        self.has_element_in_specific_scope(arena, element, BASE_SCOPE_TAGS)
+    }
+
+    pub fn has_one_of_elements_in_scope(&self, arena: &NodeArena, elements: &[&str]) -> bool {
+        self.has_one_of_elements_in_specific_scope(arena, elements, BASE_SCOPE_TAGS)
+    }
+
+    /// Like [StackOfOpenElements::has_element_in_scope], but the target node
+    /// is one specific element instead of any element with a tag name.
+    pub fn has_node_in_scope(&self, arena: &NodeArena, target_node: NodeId) -> bool {
+        for node in self.elements.iter().rev() {
+            if *node == target_node {
+                return true;
+            }
+
+            if arena
+                .get_node(*node)
+                .is_element_with_one_of_tag_names(BASE_SCOPE_TAGS)
+            {
+                return false;
+            }
+        }
+
+        false
     }
 
     /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-list-scope
@@ -1800,28 +5338,49 @@ impl StackOfOpenElements {
 
     /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-table-scope
     pub fn has_element_in_table_scope(&self, arena: &NodeArena, element: &str) -> bool {
-        self.has_element_in_specific_scope(
-            arena,
-            element,
-            &[BASE_SCOPE_TAGS, &["html", "table", "template"]].concat(),
-        )
+        self.has_element_in_specific_scope(arena, element, &["html", "table", "template"])
+    }
+
+    /// Unlike the other scopes, select scope is defined by the element types
+    /// that don't end it: every element except optgroup and option elements
+    /// ends the scope.
+    ///
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-select-scope
+    pub fn has_element_in_select_scope(&self, arena: &NodeArena, element: &str) -> bool {
+        for node in self.elements.iter().rev() {
+            let node = arena.get_node(*node);
+
+            // If node is the target node, terminate in a match state.
+            if node.is_element_with_tag_name(element) {
+                return true;
+            }
+
+            // Otherwise, if node is not an optgroup or option element,
+            // terminate in a failure state.
+            if !node.is_element_with_one_of_tag_names(&["optgroup", "option"]) {
+                return false;
+            }
+        }
+
+        false
     }
 
-    pub fn insert_immediately_below(&mut self, element: NodeId, target: NodeId) {
+    pub fn insert_immediately_below(&mut self, arena: &NodeArena, element: NodeId, target: NodeId) {
         if let Some(index) = self.elements.iter().position(|e| e == &target) {
-            self.elements.insert(index + 1, element);
+            self.insert(arena, index + 1, element);
         }
     }
 
-    pub fn replace(&mut self, target: NodeId, replacement: NodeId) {
+    pub fn replace(&mut self, arena: &NodeArena, target: NodeId, replacement: NodeId) {
         if let Some(index) = self.elements.iter().position(|e| e == &target) {
-            self.elements[index] = replacement;
+            self.remove(index);
+            self.insert(arena, index, replacement);
         }
     }
 
     pub fn remove_element(&mut self, element: NodeId) {
         if let Some(index) = self.elements.iter().position(|e| e == &element) {
-            self.elements.remove(index);
+            self.remove(index);
         }
     }
 
@@ -1843,10 +5402,7 @@ impl StackOfOpenElements {
             if *element == target {
                 break;
             }
-            if arena
-                .get_node(*element)
-                .is_element_with_one_of_tag_names(&SPECIAL_TAGS)
-            {
+            if arena.get_node(*element).is_special() {
                 best = Some(*element);
             }
         }
@@ -1854,18 +5410,44 @@ impl StackOfOpenElements {
     }
 }
 
+/// For the Noah's Ark clause, the attributes must be compared as they were
+/// when the elements were created by the parser; two elements have the same
+/// attributes if all their parsed attributes can be paired such that the two
+/// attributes in each pair have identical names, namespaces, and values (the
+/// order of the attributes does not matter).
+fn have_same_tag_name_namespace_and_attributes(arena: &NodeArena, a: NodeId, b: NodeId) -> bool {
+    match (&arena.get_node(a).kind, &arena.get_node(b).kind) {
+        (
+            NodeKind::Element {
+                tag_name: a_tag_name,
+                namespace_uri: a_namespace_uri,
+                attributes: a_attributes,
+                ..
+            },
+            NodeKind::Element {
+                tag_name: b_tag_name,
+                namespace_uri: b_namespace_uri,
+                attributes: b_attributes,
+                ..
+            },
+        ) => {
+            a_tag_name == b_tag_name
+                && a_namespace_uri == b_namespace_uri
+                && a_attributes.len() == b_attributes.len()
+                && a_attributes
+                    .iter()
+                    .all(|attribute| b_attributes.contains(attribute))
+        }
+        _ => false,
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum ActiveFormattingElement {
     Marker,
     Element(NodeId),
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-pub enum FormattingElementPosition {
-    End,
-    LastMarkerOrElseStart,
-}
-
 /// https://html.spec.whatwg.org/multipage/parsing.html#list-of-active-formatting-elements
 #[derive(Debug, Clone, PartialEq)]
 struct ActiveFormattingElements {
@@ -1877,57 +5459,53 @@ impl ActiveFormattingElements {
         Self { elements: vec![] }
     }
 
-    /// https://html.spec.whatwg.org/multipage/parsing.html#reconstruct-the-active-formatting-elements
-    pub fn reconstruct(&mut self, open_elements: &StackOfOpenElements) {
-        // If there are no entries in the list of active formatting elements,
-        // then there is nothing to reconstruct; stop this algorithm.
-        if self.elements.is_empty() {
-            return;
-        }
-
-        // If the last (most recently added) entry in the list of active
-        // formatting elements is a marker, or if it is an element that is in
-        // the stack of open elements, then there is nothing to reconstruct;
-        // stop this algorithm.
-        match self.elements.last().unwrap() {
-            ActiveFormattingElement::Marker => return,
-            ActiveFormattingElement::Element(element) if open_elements.contains(*element) => {
-                return;
+    /// https://html.spec.whatwg.org/multipage/parsing.html#push-onto-the-list-of-active-formatting-elements
+    pub fn push_formatting_element(&mut self, arena: &NodeArena, element: NodeId) {
+        // If there are already three elements in the list of active formatting
+        // elements after the last marker, if any, or anywhere in the list if
+        // there are no markers, that have the same tag name, namespace, and
+        // attributes as element,
+        let mut matching_elements = vec![];
+        for (index, entry) in self.elements.iter().enumerate().rev() {
+            match entry {
+                ActiveFormattingElement::Marker => break,
+                ActiveFormattingElement::Element(other) => {
+                    if have_same_tag_name_namespace_and_attributes(arena, element, *other) {
+                        matching_elements.push(index);
+                    }
+                }
             }
-            _ => {}
-        }
-
-        todo!("Fully implement reconstructing active formatting elements");
-
-        // TODO: Let entry be the last (most recently added) element in the list
-        // of active formatting elements.
-
-        // TODO: Rewind: If there are no entries before entry in the list of
-        // active formatting elements, then jump to the step labeled
-        // create.
-
-        // TODO: Let entry be the entry one earlier than entry in the list of
-        // active formatting elements.
-
-        // TODO: If entry is neither a marker nor an element that is also in the
-        // stack of open elements, go to the step labeled rewind.
-
-        // TODO: Advance: Let entry be the element one later than entry in the
-        // list of active formatting elements.
+        }
 
-        // TODO: Create: Insert an HTML element for the token for which the
-        // element entry was created, to obtain new element.
+        // then remove the earliest such element from the list of active
+        // formatting elements.
+        if matching_elements.len() >= 3 {
+            self.elements.remove(*matching_elements.last().unwrap());
+        }
 
-        // TODO: Replace the entry for entry in the list with an entry for new
-        // element.
+        // Add element to the list of active formatting elements.
+        self.elements
+            .push(ActiveFormattingElement::Element(element));
+    }
 
-        // TODO: If the entry for new element in the list of active formatting
-        // elements is not the last entry in the list, return to the step
-        // labeled advance.
+    /// Inserts a marker at the end of the list of active formatting elements.
+    pub fn push_marker(&mut self) {
+        self.elements.push(ActiveFormattingElement::Marker);
     }
 
-    pub fn push(&mut self, element: ActiveFormattingElement) {
-        self.elements.push(element);
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-list-of-active-formatting-elements-up-to-the-last-marker
+    pub fn clear_up_to_last_marker(&mut self) {
+        // Let entry be the last (most recently added) entry in the list of
+        // active formatting elements.
+        // Remove entry from the list of active formatting elements.
+        // If entry was a marker, then stop the algorithm at this point. The
+        // list has been cleared up to the last marker.
+        // Go to step 1.
+        while let Some(entry) = self.elements.pop() {
+            if entry == ActiveFormattingElement::Marker {
+                break;
+            }
+        }
     }
 
     pub fn first_index_of(&self, target: NodeId) -> Option<usize> {
@@ -1961,10 +5539,6 @@ impl ActiveFormattingElements {
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.elements.len()
-    }
-
     pub fn last_element_with_tag_name_before_marker(
         &self,
         arena: &NodeArena,
@@ -1991,41 +5565,1026 @@ impl ActiveFormattingElements {
             false
         })
     }
+}
 
-    pub fn contains_element_between(
-        &self,
-        arena: &NodeArena,
-        start: FormattingElementPosition,
-        end: FormattingElementPosition,
-        tag_name: &str,
-    ) -> bool {
-        if let Some(start) = self.index_from_position(start) {
-            if let Some(end) = self.index_from_position(end) {
-                for i in start..end {
-                    if let Some(ActiveFormattingElement::Element(element)) = self.elements.get(i) {
-                        if arena
-                            .get_node(*element)
-                            .is_element_with_one_of_tag_names(&[tag_name])
-                        {
-                            return true;
-                        }
-                    }
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::NodeArena;
+    use crate::{Dom, SerializerOptions};
+
+    /// Parses `html` as a document and serializes the whole tree.
+    fn parse(html: &str) -> String {
+        let dom = Dom::parse_owned(html);
+        dom.arena().inner_html(dom.document())
+    }
+
+    #[test]
+    fn reprocesses_token_after_mode_switch() {
+        assert_eq!(
+            parse("<div>x</div>"),
+            "<html><head></head><body><div>x</div></body></html>"
+        );
+        // The character token is reprocessed in four insertion modes before
+        // it is inserted.
+        assert_eq!(
+            parse("<!DOCTYPE html>text"),
+            "<!DOCTYPE html><html><head></head><body>text</body></html>"
+        );
+    }
+
+    #[test]
+    fn frameset_takes_over_an_implied_body() {
+        assert_eq!(
+            parse("<div><frameset><frame></frameset>"),
+            "<html><head></head><frameset><frame></frameset></html>"
+        );
+        // Whitespace-only content in the implied body is dropped with it.
+        assert_eq!(
+            parse("<p> \n <span>\t</span><frameset><frame></frameset>"),
+            "<html><head></head><frameset><frame></frameset></html>"
+        );
+        // Without a body, the frameset is inserted after the head directly.
+        assert_eq!(
+            parse("<head></head> \n<frameset></frameset>"),
+            "<html><head></head> \n<frameset></frameset></html>"
+        );
+
+        // An explicit body start tag sets the frameset-ok flag to "not ok", so
+        // the frameset is ignored, like in html5lib-tests.
+        assert_eq!(
+            parse("<body><frameset>"),
+            "<html><head></head><body></body></html>"
+        );
+    }
+
+    #[test]
+    fn deep_nesting_exceeds_max_depth() {
+        let html = "<div>".repeat(100_000);
+        let mut arena = NodeArena::new();
+        let options = ParserOptions {
+            max_depth: Some(512),
+            ..Default::default()
+        };
+        assert_eq!(
+            Dom::parse_with_options(&html, &mut arena, options).err(),
+            Some(ParseError::MaxDepthExceeded { max_depth: 512 })
+        );
+    }
+
+    #[test]
+    fn parses_deep_nesting() {
+        let html = "<div>".repeat(50_000);
+        let dom = Dom::parse_owned(&html);
+
+        let body = dom
+            .arena()
+            .elements_by_tag(dom.document(), "body")
+            .next()
+            .unwrap();
+        let deepest = dom.arena().elements_by_tag(body, "div").last().unwrap();
+        assert_eq!(dom.arena().depth(deepest), 50_002);
+    }
+
+    #[test]
+    fn stack_keeps_its_tag_name_counts_in_sync() {
+        // The scope checks trust these counts to skip walking the stack for
+        // tag names that are not on it, which keeps deeply nested documents
+        // from parsing in quadratic time.
+        fn assert_in_sync(arena: &NodeArena, stack: &StackOfOpenElements) {
+            let tag_names: Vec<String> = stack
+                .elements
+                .iter()
+                .map(|element| arena.get_node(*element).qualified_name())
+                .collect();
+            assert_eq!(stack.tag_names, tag_names);
+            for (tag_name, count) in &stack.tag_name_counts {
+                let expected = tag_names.iter().filter(|name| *name == tag_name).count();
+                assert_eq!(*count, expected, "{tag_name}");
             }
         }
-        false
+
+        let mut arena = NodeArena::new();
+        let mut stack = stack(&mut arena, &["html", "body", "div", "b", "p"]);
+        assert_in_sync(&arena, &stack);
+        let [_, _, div, b, p] = stack.elements[..] else {
+            unreachable!()
+        };
+
+        let i = arena.create_element("i");
+        stack.insert_immediately_below(&arena, i, b);
+        assert_in_sync(&arena, &stack);
+
+        let strong = arena.create_element("strong");
+        stack.replace(&arena, b, strong);
+        assert_in_sync(&arena, &stack);
+        assert!(!stack.has_element_in_scope(&arena, "b"));
+        assert!(stack.has_element_in_scope(&arena, "strong"));
+
+        stack.remove_element(div);
+        assert_in_sync(&arena, &stack);
+        assert!(!stack.has_element_in_scope(&arena, "div"));
+
+        assert_eq!(stack.pop(), Some(p));
+        assert_in_sync(&arena, &stack);
+        assert!(!stack.has_element_in_button_scope(&arena, "p"));
+
+        stack.push(&arena, p);
+        assert_in_sync(&arena, &stack);
+        assert!(stack.has_element_in_button_scope(&arena, "p"));
+
+        stack.pop_until_element_with_tag_name(&arena, "html");
+        assert_in_sync(&arena, &stack);
+        assert!(stack.is_empty());
     }
 
-    fn index_from_position(&self, position: FormattingElementPosition) -> Option<usize> {
-        match position {
-            FormattingElementPosition::End => Some(self.len().saturating_sub(1)),
-            FormattingElementPosition::LastMarkerOrElseStart => self
-                .elements
+    #[test]
+    fn table_end_tags_close_a_select_in_a_cell() {
+        assert_eq!(
+            parse("<table><tr><td><select><option>x</td></tr></table>after"),
+            "<html><head></head><body><table><tbody><tr><td><select><option>x</option></select>\
+             </td></tr></tbody></table>after</body></html>"
+        );
+        assert_eq!(
+            parse("<table><tr><td><select><option>x<td>y</table>"),
+            "<html><head></head><body><table><tbody><tr><td><select><option>x</option></select>\
+             </td><td>y</td></tr></tbody></table></body></html>"
+        );
+    }
+
+    #[test]
+    fn noscript_in_head_depends_on_scripting() {
+        let html = "<head><noscript><link rel=stylesheet href=a.css></noscript></head>";
+        let parse_with_scripting = |scripting| {
+            let mut arena = NodeArena::new();
+            let options = ParserOptions {
+                scripting,
+                ..Default::default()
+            };
+            let document = Dom::parse_with_options(html, &mut arena, options).unwrap();
+            let options = SerializerOptions {
+                scripting,
+                ..Default::default()
+            };
+            arena.inner_html_with_options(document, options)
+        };
+
+        assert_eq!(
+            parse_with_scripting(false),
+            "<html><head><noscript><link rel=\"stylesheet\" href=\"a.css\"></noscript></head>\
+             <body></body></html>"
+        );
+        // With scripting enabled, the contents are raw text, which is not
+        // escaped when serialized either.
+        assert_eq!(
+            parse_with_scripting(true),
+            "<html><head><noscript><link rel=stylesheet href=a.css></noscript></head>\
+             <body></body></html>"
+        );
+    }
+
+    #[test]
+    fn a_new_cell_closes_the_open_cell() {
+        assert_eq!(
+            parse("<table><tr><td>a<td>b"),
+            "<html><head></head><body><table><tbody><tr><td>a</td><td>b</td></tr></tbody>\
+             </table></body></html>"
+        );
+        // A new row closes both the cell and the row.
+        assert_eq!(
+            parse("<table><tr><td>a<tr><th>b"),
+            "<html><head></head><body><table><tbody><tr><td>a</td></tr><tr><th>b</th></tr>\
+             </tbody></table></body></html>"
+        );
+    }
+
+    #[test]
+    fn adjusts_foreign_attributes() {
+        let dom = Dom::parse_owned("<svg viewbox=\"0 0 1 1\"><a xlink:href=#x></a></svg>");
+        let arena = dom.arena();
+        let svg = arena.elements_by_tag(dom.document(), "svg").next().unwrap();
+        let a = arena.elements_by_tag(svg, "a").next().unwrap();
+
+        let viewbox = &arena.get_node(svg).attributes()[0];
+        assert_eq!(viewbox.local_name, "viewBox");
+        assert_eq!(viewbox.namespace_uri, None);
+
+        let href = &arena.get_node(a).attributes()[0];
+        assert_eq!(href.local_name, "href");
+        assert_eq!(href.prefix.as_deref(), Some("xlink"));
+        assert_eq!(href.namespace_uri.as_deref(), Some(Namespace::XLink.url()));
+    }
+
+    #[test]
+    fn content_before_a_frameset_prevents_the_takeover() {
+        assert_eq!(
+            parse("text<frameset><frame></frameset>"),
+            "<html><head></head><body>text</body></html>"
+        );
+        assert_eq!(
+            parse("<img><frameset>"),
+            "<html><head></head><body><img></body></html>"
+        );
+        // Whitespace keeps the frameset-ok flag.
+        assert_eq!(
+            parse("<div> \n<frameset></frameset>"),
+            "<html><head></head><frameset></frameset></html>"
+        );
+    }
+
+    /// Creates a stack of open elements with the given tag names, from the
+    /// topmost to the current node.
+    fn stack(arena: &mut NodeArena, tag_names: &[&str]) -> StackOfOpenElements {
+        let mut stack = StackOfOpenElements::new();
+        for tag_name in tag_names {
+            let element = arena.create_element(tag_name);
+            stack.push(arena, element);
+        }
+        stack
+    }
+
+    #[test]
+    fn select_scope_only_spans_options_and_optgroups() {
+        let mut arena = NodeArena::new();
+        let in_optgroup = stack(
+            &mut arena,
+            &["html", "body", "select", "optgroup", "option"],
+        );
+        assert!(in_optgroup.has_element_in_select_scope(&arena, "select"));
+        assert!(in_optgroup.has_element_in_select_scope(&arena, "option"));
+        assert!(!in_optgroup.has_element_in_select_scope(&arena, "body"));
+
+        let in_div = stack(&mut arena, &["html", "body", "select", "div", "option"]);
+        assert!(!in_div.has_element_in_select_scope(&arena, "select"));
+    }
+
+    #[test]
+    fn places_comments_after_the_body() {
+        assert_eq!(
+            parse("<p>x</p></body><!-- trailing --></html>"),
+            "<html><head></head><body><p>x</p></body><!-- trailing --></html>"
+        );
+        assert_eq!(
+            parse("<p>x</p></body></html><!-- trailing -->"),
+            "<html><head></head><body><p>x</p></body></html><!-- trailing -->"
+        );
+        // A DOCTYPE after the body is ignored.
+        assert_eq!(
+            parse("</body><!DOCTYPE html></html><!DOCTYPE html>"),
+            "<html><head></head><body></body></html>"
+        );
+    }
+
+    /// Parses `html` as a document and serializes the contents of the body.
+    fn parse_body(html: &str) -> String {
+        let dom = Dom::parse_owned(html);
+        let body = dom
+            .arena()
+            .elements_by_tag(dom.document(), "body")
+            .next()
+            .unwrap();
+        dom.arena().inner_html(body)
+    }
+
+    #[test]
+    fn a_start_tag_closes_an_open_a_element() {
+        assert_eq!(parse_body("<a>1<a>2"), "<a>1</a><a>2</a>");
+        assert_eq!(
+            parse_body("<a><p>X<a>Y</a>Z</p></a>"),
+            "<a></a><p><a>X</a><a>Y</a>Z</p>"
+        );
+        assert_eq!(
+            parse_body("<a><div><a>x"),
+            "<a></a><div><a></a><a>x</a></div>"
+        );
+        // The a element is not in table scope here, so the adoption agency
+        // algorithm leaves it alone and it is only removed from the stack.
+        assert_eq!(
+            parse_body("<a><table><td><a><table></table><a></tr><a></table><b>X</b>C<a>Y"),
+            "<a><a></a><table><tbody><tr><td><a><table></table></a><a></a></td></tr></tbody>\
+             </table></a><a><b>X</b>C</a><a>Y</a>"
+        );
+    }
+
+    #[test]
+    fn runs_the_adoption_agency_algorithm() {
+        // These are the adoption01 and adoption02 cases from html5lib-tests.
+        let cases = [
+            ("<a><p></a></p>", "<a></a><p><a></a></p>"),
+            ("<a>1<p>2</a>3</p>", "<a>1</a><p><a>2</a>3</p>"),
+            (
+                "<a>1<button>2</a>3</button>",
+                "<a>1</a><button><a>2</a>3</button>",
+            ),
+            ("<a>1<b>2</a>3</b>", "<a>1<b>2</b></a><b>3</b>"),
+            (
+                "<a>1<div>2<div>3</a>4</div>5</div>",
+                "<a>1</a><div><a>2</a><div><a>3</a>4</div>5</div>",
+            ),
+            (
+                "<table><a>1<p>2</a>3</p>",
+                "<a>1</a><p><a>2</a>3</p><table></table>",
+            ),
+            ("<b><b><a><p></a>", "<b><b><a></a><p><a></a></p></b></b>"),
+            (
+                "<b><a><b><p></a>",
+                "<b><a><b></b></a><b><p><a></a></p></b></b>",
+            ),
+            (
+                "<a><div><style></style><address><a>",
+                "<a></a><div><a><style></style></a><address><a></a><a></a></address></div>",
+            ),
+            (
+                "<b>1<i>2<p>3</b>4",
+                "<b>1<i>2</i></b><i><p><b>3</b>4</p></i>",
+            ),
+            // From tests1 in html5lib-tests. The inner loop runs more than
+            // three times, so b is removed from the list of active formatting
+            // elements before the bookmark.
+            (
+                "<a><b><big><em><strong><div>X</a>",
+                "<a><b><big><em><strong></strong></em></big></b></a>\
+                 <big><em><strong><div><a>X</a></div></strong></em></big>",
+            ),
+            (
+                "<i><b><u><s><em><p></i>x",
+                "<i><b><u><s><em></em></s></u></b></i>\
+                 <u><s><em><p><i></i>x</p></em></s></u>",
+            ),
+        ];
+        for (html, body) in cases {
+            assert_eq!(parse_body(html), body, "{html}");
+        }
+    }
+
+    #[test]
+    fn dialog_end_tag_closes_a_paragraph() {
+        assert_eq!(
+            parse_body("<dialog><p>x</dialog>y"),
+            "<dialog><p>x</p></dialog>y"
+        );
+        // A stray end tag is ignored.
+        assert_eq!(parse_body("a</dialog>b"), "ab");
+    }
+
+    #[test]
+    fn clears_formatting_elements_up_to_the_last_marker() {
+        let mut arena = NodeArena::new();
+        let [b, i, u, s] = ["b", "i", "u", "s"].map(|tag_name| arena.create_element(tag_name));
+        let mut list = ActiveFormattingElements::new();
+        list.push_formatting_element(&arena, b);
+        list.push_marker();
+        list.push_formatting_element(&arena, i);
+        list.push_marker();
+        list.push_formatting_element(&arena, u);
+        list.push_formatting_element(&arena, s);
+
+        list.clear_up_to_last_marker();
+        assert_eq!(
+            list.elements,
+            [
+                ActiveFormattingElement::Element(b),
+                ActiveFormattingElement::Marker,
+                ActiveFormattingElement::Element(i),
+            ]
+        );
+
+        list.clear_up_to_last_marker();
+        assert_eq!(list.elements, [ActiveFormattingElement::Element(b)]);
+
+        // Without a marker, the whole list is cleared.
+        list.clear_up_to_last_marker();
+        assert!(list.elements.is_empty());
+    }
+
+    #[test]
+    fn pushes_a_marker() {
+        let mut arena = NodeArena::new();
+        let b = arena.create_element("b");
+        let mut list = ActiveFormattingElements::new();
+        list.push_formatting_element(&arena, b);
+        list.push_marker();
+        assert_eq!(
+            list.elements,
+            [
+                ActiveFormattingElement::Element(b),
+                ActiveFormattingElement::Marker,
+            ]
+        );
+
+        // Elements before the marker are not found anymore.
+        assert_eq!(
+            list.last_element_with_tag_name_before_marker(&arena, "b"),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_at_most_three_identical_formatting_elements() {
+        let mut arena = NodeArena::new();
+        let bs = [(); 4].map(|_| arena.create_element("b"));
+        let mut list = ActiveFormattingElements::new();
+        for b in bs {
+            list.push_formatting_element(&arena, b);
+        }
+        assert_eq!(
+            list.elements,
+            bs[1..]
                 .iter()
-                .rev()
-                .enumerate()
-                .find(|(_, element)| matches!(element, ActiveFormattingElement::Marker))
-                .map(|element| element.0),
+                .map(|b| ActiveFormattingElement::Element(*b))
+                .collect::<Vec<_>>()
+        );
+
+        // Elements with other attributes are not identical.
+        let mut list = ActiveFormattingElements::new();
+        for b in bs {
+            list.push_formatting_element(&arena, b);
+        }
+        let with_class = arena.create_element("b");
+        arena
+            .get_node_mut(with_class)
+            .append_attribute(Attribute::new("class".to_string(), "x".to_string()));
+        list.push_formatting_element(&arena, with_class);
+        assert_eq!(list.elements.len(), 4);
+
+        // Only three of the four b elements are reconstructed.
+        assert_eq!(
+            parse_body("<p><b><b><b><b>x</p>y"),
+            "<p><b><b><b><b>x</b></b></b></b></p><b><b><b>y</b></b></b>"
+        );
+    }
+
+    #[test]
+    fn plaintext_consumes_the_rest_of_the_input() {
+        let dom = Dom::parse_owned("<p>a<plaintext>b <i>c</i> &amp; </plaintext><!-- d -->");
+        let arena = dom.arena();
+        let plaintext = arena
+            .elements_by_tag(dom.document(), "plaintext")
+            .next()
+            .unwrap();
+        assert_eq!(arena.child_count(plaintext), 1);
+        assert!(matches!(
+            &arena.get_node(arena.nth_child(plaintext, 0).unwrap()).kind,
+            NodeKind::Text { data } if data == "b <i>c</i> &amp; </plaintext><!-- d -->"
+        ));
+        // The p element is closed first.
+        assert_eq!(
+            arena.get_node(plaintext).parent(),
+            arena.elements_by_tag(dom.document(), "body").next()
+        );
+    }
+
+    #[test]
+    fn ignores_a_stray_table_end_tag() {
+        assert_eq!(
+            parse_body("<p>a</table>b</p><div>c</div>"),
+            "<p>ab</p><div>c</div>"
+        );
+        assert_eq!(
+            parse_body("<table><tr><td>a</table></table>b"),
+            "<table><tbody><tr><td>a</td></tr></tbody></table>b"
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace_and_comments_are_not_errors() {
+        let mut arena = NodeArena::new();
+        let (document, errors) = Dom::parse_lossy(
+            "<!DOCTYPE html><html><body>x</body></html>\n  <!-- c -->\n",
+            &mut arena,
+        );
+        assert_eq!(errors, []);
+        assert_eq!(
+            arena.inner_html(document),
+            "<!DOCTYPE html><html><head></head><body>x\n  \n</body></html><!-- c -->"
+        );
+    }
+
+    #[test]
+    fn optgroups_and_options_close_each_other() {
+        assert_eq!(
+            parse_body("<select><optgroup><option>a<optgroup><option>b</select>"),
+            "<select><optgroup><option>a</option></optgroup><optgroup><option>b</option>\
+             </optgroup></select>"
+        );
+        assert_eq!(
+            parse_body("<select><option>a<option>b</select>"),
+            "<select><option>a</option><option>b</option></select>"
+        );
+    }
+
+    /// Parses `html` with an error callback that returns `control_flow`, and
+    /// returns the parse result and the number of errors the callback saw.
+    fn count_errors(
+        html: &str,
+        control_flow: ControlFlow<()>,
+    ) -> (Result<NodeId, ParseError>, usize) {
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let options = ParserOptions {
+            on_error: Some(Box::new({
+                let count = count.clone();
+                move |_| {
+                    count.set(count.get() + 1);
+                    control_flow
+                }
+            })),
+            ..Default::default()
+        };
+        let result = Dom::parse_with_options(html, &mut NodeArena::new(), options);
+        (result, count.get())
+    }
+
+    #[test]
+    fn reports_errors_to_the_callback() {
+        // The missing doctype, the misnested </b>, the </i> for the element
+        // that </b> already closed, and the i element that is still open at
+        // the end of the file.
+        let (result, count) = count_errors("<p><b><i>x</b></i>y<i>", ControlFlow::Continue(()));
+        assert!(result.is_ok());
+        assert_eq!(count, 4);
+
+        let (result, count) = count_errors("<p><b><i>x</b></i>y<i>", ControlFlow::Break(()));
+        assert_eq!(result.err(), Some(ParseError::Aborted));
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn column_groups_contain_their_columns() {
+        let mut arena = NodeArena::new();
+        let (document, errors) = Dom::parse_lossy(
+            "<!DOCTYPE html><table><colgroup><col><col/></colgroup><col span=2></table>",
+            &mut arena,
+        );
+        // The self-closing flag on a col start tag is acknowledged.
+        assert_eq!(errors, []);
+        let table = arena.elements_by_tag(document, "table").next().unwrap();
+        assert_eq!(
+            arena.inner_html(table),
+            "<colgroup><col><col></colgroup><colgroup><col span=\"2\"></colgroup>"
+        );
+    }
+
+    #[test]
+    fn end_tags_use_the_right_scope() {
+        // Button scope: the p element outside the button is not closed, and
+        // an empty one is inserted instead.
+        assert_eq!(
+            parse_body("<p><button></p>x</button>"),
+            "<p><button><p></p>x</button></p>"
+        );
+        // List scope: the li element outside the ol is not closed.
+        assert_eq!(parse_body("<li><ol>x</li>y</ol>"), "<li><ol>xy</ol></li>");
+        // Regular scope: the div element outside the cell is not closed.
+        assert_eq!(
+            parse_body("<div><table><tr><td></div>x</table>"),
+            "<div><table><tbody><tr><td>x</td></tr></tbody></table></div>"
+        );
+        // Table scope: the cell outside the nested table is not closed.
+        assert_eq!(
+            parse_body("<table><tr><td><table></td><tr><td>x</table></table>"),
+            "<table><tbody><tr><td><table><tbody><tr><td>x</td></tr></tbody></table></td></tr>\
+             </tbody></table>"
+        );
+    }
+
+    #[test]
+    fn head_content_after_the_head_goes_into_the_head() {
+        assert_eq!(
+            parse("<head><title>t</title></head><meta charset=utf-8><body>x"),
+            "<html><head><title>t</title><meta charset=\"utf-8\"></head><body>x</body></html>"
+        );
+    }
+
+    #[test]
+    fn keygen_is_a_void_element() {
+        assert_eq!(
+            parse_body("<form><keygen name=key>text</form>"),
+            "<form><keygen name=\"key\">text</form>"
+        );
+        // The element is closed right away, even with the self-closing flag.
+        assert_eq!(parse_body("<p><keygen/><b>x"), "<p><keygen><b>x</b></p>");
+
+        // The serializer leaves out the children of void elements, so check
+        // that the text is not in the keygen element.
+        let dom = Dom::parse_owned("<keygen>text");
+        let keygen = dom
+            .arena()
+            .elements_by_tag(dom.document(), "keygen")
+            .next()
+            .unwrap();
+        assert_eq!(dom.arena().child_count(keygen), 0);
+    }
+
+    #[test]
+    fn handles_stray_document_end_tags_in_the_body() {
+        assert_eq!(
+            parse("<body>x</html>y"),
+            "<html><head></head><body>xy</body></html>"
+        );
+        assert_eq!(
+            parse("<body>x</head>y</body>z"),
+            "<html><head></head><body>xyz</body></html>"
+        );
+    }
+
+    #[test]
+    fn formatting_is_carried_across_blocks() {
+        assert_eq!(
+            parse_body("<b>bold<div>still bold</div>and more"),
+            "<b>bold<div>still bold</div>and more</b>"
+        );
+        assert_eq!(
+            parse_body("<p><b>bold</p>text <i>x"),
+            "<p><b>bold</b></p><b>text <i>x</i></b>"
+        );
+        // Whitespace reconstructs the formatting elements as well.
+        assert_eq!(parse_body("<p><b>x</p> "), "<p><b>x</b></p><b> </b>");
+    }
+
+    #[test]
+    fn ignores_stray_doctypes() {
+        let mut arena = NodeArena::new();
+        let (document, errors) = Dom::parse_lossy(
+            "<!DOCTYPE html><p>a<!DOCTYPE html>b</p></body><!DOCTYPE html><p>c",
+            &mut arena,
+        );
+        // The two doctypes and the p start tag after the body.
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            arena.inner_html(document),
+            "<!DOCTYPE html><html><head></head><body><p>ab</p><p>c</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn noscript_as_markup_parses_the_contents() {
+        let html = "<body><noscript><img src=x></noscript>";
+        let parse_noscript = |noscript_as_markup| {
+            let mut arena = NodeArena::new();
+            let options = ParserOptions {
+                scripting: true,
+                noscript_as_markup,
+                ..Default::default()
+            };
+            let document = Dom::parse_with_options(html, &mut arena, options).unwrap();
+            let noscript = arena.elements_by_tag(document, "noscript").next().unwrap();
+            let child = arena.nth_child(noscript, 0).unwrap();
+            arena.get_node(child).describe()
+        };
+
+        assert_eq!(parse_noscript(true), "img");
+        assert_eq!(parse_noscript(false), "#text");
+    }
+
+    #[test]
+    fn closes_headings_and_blocks_in_fragments() {
+        let cases = [
+            ("td", "<h1>a</h2>b</div>c", "<h1>a</h1>bc"),
+            ("li", "<div>a</li>b</div></h3>", "<div>ab</div>"),
+            ("select", "</p><option>x", "<option>x</option>"),
+            ("svg", "</p>x", "<p></p>x"),
+        ];
+        for (context, html, expected) in cases {
+            let (arena, nodes) = Dom::parse_fragment(html, context);
+            let serialized: String = nodes.iter().map(|node| arena.outer_html(*node)).collect();
+            assert_eq!(serialized, expected, "{html} in {context}");
+        }
+    }
+
+    #[test]
+    fn scope_checks_run_off_the_end_of_the_stack() {
+        let mut arena = NodeArena::new();
+        let without_html = stack(&mut arena, &["div", "span", "b"]);
+        assert!(without_html.has_element_in_scope(&arena, "div"));
+        assert!(!without_html.has_element_in_scope(&arena, "p"));
+        assert!(!without_html.has_element_in_table_scope(&arena, "table"));
+
+        // Without any elements, nothing is in scope.
+        let empty = StackOfOpenElements::new();
+        assert!(!empty.has_element_in_button_scope(&arena, "p"));
+        assert!(!empty.has_element_in_list_scope(&arena, "li"));
+        assert!(!empty.has_element_in_select_scope(&arena, "select"));
+    }
+
+    #[test]
+    fn fosters_text_out_of_tables() {
+        assert_eq!(
+            parse_body("<table>  stray  <tr>"),
+            "  stray  <table><tbody><tr></tr></tbody></table>"
+        );
+        // Whitespace-only text stays in the table.
+        assert_eq!(
+            parse_body("<table> <tr>"),
+            "<table> <tbody><tr></tr></tbody></table>"
+        );
+    }
+
+    #[test]
+    fn returns_to_html_at_integration_points() {
+        let dom =
+            Dom::parse_owned("<svg><foreignObject><div>html</div></foreignObject></svg><p>after");
+        let arena = dom.arena();
+        let foreign_object = arena
+            .elements_by_tag(dom.document(), "foreignObject")
+            .next()
+            .unwrap();
+        let div = arena.elements_by_tag(foreign_object, "div").next().unwrap();
+        assert!(arena
+            .get_node(foreign_object)
+            .is_element_in_namespace(Namespace::Svg));
+        assert!(arena.get_node(div).is_element_in_namespace(Namespace::Html));
+
+        assert_eq!(
+            parse_body("<svg><foreignObject><div>html</div></foreignObject></svg><p>after"),
+            "<svg><foreignObject><div>html</div></foreignObject></svg><p>after</p>"
+        );
+    }
+
+    #[test]
+    fn replaces_null_characters_in_text_elements() {
+        for tag in ["title", "textarea", "style", "script", "xmp"] {
+            let dom = Dom::parse_owned(&format!("<{tag}>a\0b</{tag}>"));
+            let arena = dom.arena();
+            let element = arena.elements_by_tag(dom.document(), tag).next().unwrap();
+            assert_eq!(arena.text_content(element), "a\u{FFFD}b", "<{tag}>");
+        }
+
+        // The data state emits null characters as they are, and the tree
+        // builder drops them in the body.
+        assert_eq!(parse_body("a\0b"), "ab");
+    }
+
+    #[test]
+    fn malformed_doctypes_put_the_document_in_quirks_mode() {
+        let mode = |html: &str| {
+            let dom = Dom::parse_owned(html);
+            dom.arena().get_node(dom.document()).document_mode()
+        };
+        assert_eq!(mode("<!DOCTYPE html>"), Some(DocumentMode::NoQuirks));
+        assert_eq!(mode("<!DOCTYPE>"), Some(DocumentMode::Quirks));
+        assert_eq!(mode("<!DOCTYPE html bogus>"), Some(DocumentMode::Quirks));
+    }
+
+    #[test]
+    fn parses_into_an_existing_document() {
+        let mut arena = NodeArena::new();
+        let document = Dom::parse("<!DOCTYPE html><title>t</title><p>one", &mut arena);
+
+        Dom::parse_into("<p>two<!-- c -->", &mut arena, document);
+        assert_eq!(
+            arena.inner_html(document),
+            "<!DOCTYPE html><html><head><title>t</title></head>\
+             <body><p>one</p><p>two<!-- c --></p></body></html>"
+        );
+    }
+
+    #[test]
+    fn whitespace_only_documents_get_the_implied_structure() {
+        for html in ["", "   ", "\n", "  \n\t\n  "] {
+            assert_eq!(parse(html), "<html><head></head><body></body></html>");
+        }
+    }
+
+    #[test]
+    fn block_start_tags_close_an_open_paragraph() {
+        for tag in [
+            "address",
+            "div",
+            "ul",
+            "h1",
+            "h6",
+            "hr",
+            "pre",
+            "listing",
+            "form",
+            "li",
+            "dd",
+            "dt",
+            "plaintext",
+            "xmp",
+        ] {
+            let body = parse_body(&format!("<p>a<{tag}>"));
+            assert!(
+                body.starts_with(&format!("<p>a</p><{tag}>")),
+                "<{tag}>: {body}"
+            );
         }
     }
+
+    #[test]
+    fn adjusts_svg_tag_names() {
+        assert_eq!(
+            parse_body("<svg><fegaussianblur/><FEBLEND></FEBLEND><clippath></svg>"),
+            "<svg><feGaussianBlur></feGaussianBlur><feBlend></feBlend>\
+             <clipPath></clipPath></svg>"
+        );
+        // HTML and MathML elements keep the lowercase name.
+        assert_eq!(
+            parse_body("<math><fegaussianblur/></math><fegaussianblur>"),
+            "<math><fegaussianblur></fegaussianblur></math><fegaussianblur></fegaussianblur>"
+        );
+    }
+
+    #[test]
+    fn form_end_tag_removes_only_the_form() {
+        assert_eq!(
+            parse_body("<form><input name=a></form><p>after"),
+            "<form><input name=\"a\"></form><p>after</p>"
+        );
+        // The div stays open, so the text after </form> still goes into it.
+        assert_eq!(
+            parse_body("<form><div>a</form>b</div>c"),
+            "<form><div>ab</div></form>c"
+        );
+        // A second form is ignored while the first is open.
+        assert_eq!(
+            parse_body("<form id=a><form id=b>x</form>y"),
+            "<form id=\"a\">x</form>y"
+        );
+    }
+
+    #[test]
+    fn closes_captions() {
+        assert_eq!(
+            parse_body("<table><caption>hi<tr>"),
+            "<table><caption>hi</caption><tbody><tr></tr></tbody></table>"
+        );
+        assert_eq!(
+            parse_body("<table><caption><b>hi</caption><tr>"),
+            "<table><caption><b>hi</b></caption><tbody><tr></tr></tbody></table>"
+        );
+        assert_eq!(
+            parse_body("<table><caption>hi</table>after"),
+            "<table><caption>hi</caption></table>after"
+        );
+    }
+
+    #[test]
+    fn caps_the_reported_errors() {
+        let errors_for = |max_errors| {
+            let errors = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+            let options = ParserOptions {
+                on_error: Some(Box::new({
+                    let errors = errors.clone();
+                    move |error| {
+                        errors.borrow_mut().push(error.clone());
+                        ControlFlow::Continue(())
+                    }
+                })),
+                max_errors,
+                ..Default::default()
+            };
+            let html = "</stray>".repeat(5000);
+            Dom::parse_with_options(&html, &mut NodeArena::new(), options).unwrap();
+            errors.take()
+        };
+
+        let all = errors_for(None);
+        assert!(all.len() > 5000);
+
+        let capped = errors_for(Some(10));
+        assert_eq!(capped.len(), 11);
+        assert_eq!(capped[..10], all[..10]);
+        assert_eq!(
+            capped[10],
+            ParseError::TooManyErrors {
+                omitted: all.len() - 10
+            }
+        );
+
+        // Nothing is summarized when the limit is not reached.
+        assert_eq!(errors_for(Some(all.len())), all);
+    }
+
+    #[test]
+    fn fosters_the_p_element_for_a_stray_p_end_tag() {
+        assert_eq!(parse_body("<table></p></table>"), "<p></p><table></table>");
+        assert_eq!(
+            parse_body("<table><tr></p><td>x</table>"),
+            "<p></p><table><tbody><tr><td>x</td></tr></tbody></table>"
+        );
+        // Inside a cell, the p element is inserted normally.
+        assert_eq!(
+            parse_body("<table><tr><td></p>x</table>"),
+            "<table><tbody><tr><td><p></p>x</td></tr></tbody></table>"
+        );
+    }
+
+    #[test]
+    fn fosters_misplaced_content_in_tables() {
+        assert_eq!(
+            parse_body("<table><div>x</div></table>"),
+            "<div>x</div><table></table>"
+        );
+        assert_eq!(
+            parse_body("<table>x<tr>"),
+            "x<table><tbody><tr></tr></tbody></table>"
+        );
+        assert_eq!(
+            parse_body("<table><tr><b>y</b><td>z</table>"),
+            "<b>y</b><table><tbody><tr><td>z</td></tr></tbody></table>"
+        );
+    }
+
+    #[test]
+    fn search_and_hgroup_are_block_elements() {
+        assert_eq!(
+            parse_body("<search><p>x</search>y"),
+            "<search><p>x</p></search>y"
+        );
+        assert_eq!(
+            parse_body("<hgroup><h1>a</h1></hgroup>b"),
+            "<hgroup><h1>a</h1></hgroup>b"
+        );
+        assert_eq!(
+            parse_body("<p>a<search>b</search><p>c<hgroup>d"),
+            "<p>a</p><search>b</search><p>c</p><hgroup>d</hgroup>"
+        );
+        // An end tag without a matching element in scope is ignored.
+        assert_eq!(parse_body("a</search>b</hgroup>"), "ab");
+    }
+
+    #[test]
+    fn implied_and_explicit_body_attributes() {
+        let body_attributes = |html: &str| {
+            let dom = Dom::parse_owned(html);
+            let arena = dom.arena();
+            let body = arena
+                .elements_by_tag(dom.document(), "body")
+                .next()
+                .unwrap();
+            arena
+                .get_node(body)
+                .attributes()
+                .iter()
+                .map(|attribute| (attribute.local_name.clone(), attribute.value.clone()))
+                .collect::<Vec<_>>()
+        };
+        let attribute = |name: &str, value: &str| (name.to_string(), value.to_string());
+
+        assert!(body_attributes("<title>t</title><p>x").is_empty());
+        assert!(body_attributes("<p class=x>").is_empty());
+        assert_eq!(
+            body_attributes("<body class=x id=y><p>"),
+            [attribute("class", "x"), attribute("id", "y")]
+        );
+        // A later body start tag only adds attributes that are missing.
+        assert_eq!(
+            body_attributes("<body class=x><p><body class=z lang=en>"),
+            [attribute("class", "x"), attribute("lang", "en")]
+        );
+    }
+
+    #[test]
+    fn reports_the_open_elements_with_errors() {
+        let errors_for = |report_open_elements| {
+            let errors = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+            let options = ParserOptions {
+                on_error: Some(Box::new({
+                    let errors = errors.clone();
+                    move |error| {
+                        errors.borrow_mut().push(error.clone());
+                        ControlFlow::Continue(())
+                    }
+                })),
+                report_open_elements,
+                ..Default::default()
+            };
+            let html = "<!DOCTYPE html><div><b>x</div>";
+            Dom::parse_with_options(html, &mut NodeArena::new(), options).unwrap();
+            errors.take()
+        };
+
+        let errors = errors_for(true);
+        let [ParseError::Recoverable { open_elements, .. }] = &errors[..] else {
+            panic!("expected a single recoverable error, got {errors:?}");
+        };
+        assert_eq!(open_elements, &["html", "body", "div", "b"]);
+        assert!(errors[0]
+            .to_string()
+            .ends_with(" (open elements: html > body > div > b)"));
+
+        let errors = errors_for(false);
+        let [ParseError::Recoverable { open_elements, .. }] = &errors[..] else {
+            panic!("expected a single recoverable error, got {errors:?}");
+        };
+        assert!(open_elements.is_empty());
+    }
+
+    #[test]
+    fn cells_in_a_row_get_their_own_formatting_elements() {
+        assert_eq!(
+            parse_body("<table><tr><td><b>a<td>b</tr></table>"),
+            "<table><tbody><tr><td><b>a</b></td><td>b</td></tr></tbody></table>"
+        );
+        assert_eq!(
+            parse_body("<table><tr><th><i>a<th>b</tr></table>c"),
+            "<table><tbody><tr><th><i>a</i></th><th>b</th></tr></tbody></table>c"
+        );
+        // Formatting elements from before the table are not reopened in cells.
+        assert_eq!(
+            parse_body("<b><table><tr><td>x</table>y"),
+            "<b><table><tbody><tr><td>x</td></tr></tbody></table>y</b>"
+        );
+    }
 }
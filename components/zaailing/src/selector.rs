@@ -0,0 +1,435 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::arena::{NodeArena, NodeId};
+use crate::node::{Node, NodeKind};
+use crate::parser::Namespace;
+
+/// A parsed selector list, like `a.external[target=_blank], nav > a`. Only
+/// the simple selectors and combinators from CSS 2 and the attribute
+/// selectors from Selectors Level 3 are supported. Pseudo-classes, namespace
+/// prefixes and escapes are not.
+///
+/// https://drafts.csswg.org/selectors/#selector-list
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SelectorList(Vec<ComplexSelector>);
+
+/// A sequence of compound selectors separated by combinators.
+/// `combinators[i]` is the combinator between `compounds[i]` and
+/// `compounds[i + 1]`.
+///
+/// https://drafts.csswg.org/selectors/#complex
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ComplexSelector {
+    compounds: Vec<CompoundSelector>,
+    combinators: Vec<Combinator>,
+}
+
+/// https://drafts.csswg.org/selectors/#combinator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// `a b`
+    Descendant,
+    /// `a > b`
+    Child,
+    /// `a + b`
+    NextSibling,
+    /// `a ~ b`
+    SubsequentSibling,
+}
+
+/// https://drafts.csswg.org/selectors/#compound
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct CompoundSelector {
+    /// The local name from the type selector, or `None` for the universal
+    /// selector or when there is no type selector.
+    local_name: Option<String>,
+    ids: Vec<String>,
+    classes: Vec<String>,
+    attributes: Vec<AttributeSelector>,
+}
+
+/// https://drafts.csswg.org/selectors/#attribute-selectors
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AttributeSelector {
+    name: String,
+    /// The operator and value, or `None` for `[name]`, which only checks
+    /// that the attribute is present.
+    value: Option<(AttributeOperator, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeOperator {
+    /// `[name=value]`
+    Equals,
+    /// `[name~=value]`
+    Includes,
+    /// `[name|=value]`
+    DashMatch,
+    /// `[name^=value]`
+    Prefix,
+    /// `[name$=value]`
+    Suffix,
+    /// `[name*=value]`
+    Substring,
+}
+
+impl SelectorList {
+    /// Parses `selector`, returning `None` if it is invalid or uses a feature
+    /// that is not supported.
+    fn parse(selector: &str) -> Option<SelectorList> {
+        let mut parser = SelectorParser {
+            input: selector.chars().peekable(),
+        };
+
+        let mut complex_selectors = vec![parser.parse_complex_selector()?];
+        while parser.input.next_if_eq(&',').is_some() {
+            complex_selectors.push(parser.parse_complex_selector()?);
+        }
+
+        match parser.input.peek() {
+            None => Some(SelectorList(complex_selectors)),
+            Some(_) => None,
+        }
+    }
+}
+
+struct SelectorParser<'input> {
+    input: Peekable<Chars<'input>>,
+}
+
+impl SelectorParser<'_> {
+    fn parse_complex_selector(&mut self) -> Option<ComplexSelector> {
+        self.skip_whitespace();
+        let mut compounds = vec![self.parse_compound_selector()?];
+        let mut combinators = vec![];
+
+        loop {
+            let had_whitespace = self.skip_whitespace();
+            let combinator = match self.input.peek() {
+                None | Some(',') => break,
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                Some(_) if had_whitespace => Combinator::Descendant,
+                Some(_) => return None,
+            };
+            if combinator != Combinator::Descendant {
+                self.input.next();
+                self.skip_whitespace();
+            }
+
+            combinators.push(combinator);
+            compounds.push(self.parse_compound_selector()?);
+        }
+
+        Some(ComplexSelector {
+            compounds,
+            combinators,
+        })
+    }
+
+    fn parse_compound_selector(&mut self) -> Option<CompoundSelector> {
+        let mut compound = CompoundSelector::default();
+        let mut is_empty = true;
+
+        if self.input.next_if_eq(&'*').is_some() {
+            is_empty = false;
+        } else if let Some(local_name) = self.parse_identifier() {
+            compound.local_name = Some(local_name);
+            is_empty = false;
+        }
+
+        loop {
+            match self.input.peek() {
+                Some('#') => {
+                    self.input.next();
+                    compound.ids.push(self.parse_identifier()?);
+                }
+                Some('.') => {
+                    self.input.next();
+                    compound.classes.push(self.parse_identifier()?);
+                }
+                Some('[') => {
+                    self.input.next();
+                    compound.attributes.push(self.parse_attribute_selector()?);
+                }
+                _ => break,
+            }
+            is_empty = false;
+        }
+
+        if is_empty {
+            return None;
+        }
+        Some(compound)
+    }
+
+    /// Parses an attribute selector after its opening `[`.
+    fn parse_attribute_selector(&mut self) -> Option<AttributeSelector> {
+        self.skip_whitespace();
+        let name = self.parse_identifier()?;
+        self.skip_whitespace();
+
+        if self.input.next_if_eq(&']').is_some() {
+            return Some(AttributeSelector { name, value: None });
+        }
+
+        let operator = match self.input.next()? {
+            '=' => AttributeOperator::Equals,
+            '~' => AttributeOperator::Includes,
+            '|' => AttributeOperator::DashMatch,
+            '^' => AttributeOperator::Prefix,
+            '$' => AttributeOperator::Suffix,
+            '*' => AttributeOperator::Substring,
+            _ => return None,
+        };
+        if operator != AttributeOperator::Equals {
+            self.input.next_if_eq(&'=')?;
+        }
+        self.skip_whitespace();
+
+        let value = match self.input.peek() {
+            Some(&quote @ ('"' | '\'')) => {
+                self.input.next();
+                let mut value = String::new();
+                loop {
+                    match self.input.next()? {
+                        c if c == quote => break,
+                        c => value.push(c),
+                    }
+                }
+                value
+            }
+            _ => self.parse_identifier()?,
+        };
+
+        self.skip_whitespace();
+        self.input.next_if_eq(&']')?;
+        Some(AttributeSelector {
+            name,
+            value: Some((operator, value)),
+        })
+    }
+
+    fn parse_identifier(&mut self) -> Option<String> {
+        let mut identifier = String::new();
+        while let Some(c) = self
+            .input
+            .next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_') || !c.is_ascii())
+        {
+            identifier.push(c);
+        }
+
+        if identifier.is_empty() {
+            return None;
+        }
+        Some(identifier)
+    }
+
+    /// Skips whitespace, returning whether there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let mut skipped = false;
+        while self.input.next_if(|c| c.is_ascii_whitespace()).is_some() {
+            skipped = true;
+        }
+        skipped
+    }
+}
+
+impl CompoundSelector {
+    fn matches(&self, node: &Node) -> bool {
+        let NodeKind::Element { local_name, .. } = &node.kind else {
+            return false;
+        };
+
+        // Type selectors are matched ASCII case-insensitively for elements in
+        // the HTML namespace.
+        let local_name_matches = match &self.local_name {
+            None => true,
+            Some(name) if node.is_element_in_namespace(Namespace::Html) => {
+                local_name.eq_ignore_ascii_case(name)
+            }
+            Some(name) => local_name == name,
+        };
+
+        local_name_matches
+            && self
+                .ids
+                .iter()
+                .all(|id| node.get_attribute("id") == Some(id.as_str()))
+            && self.classes.iter().all(|class| node.has_class(class))
+            && self
+                .attributes
+                .iter()
+                .all(|attribute| attribute.matches(node))
+    }
+}
+
+impl AttributeSelector {
+    fn matches(&self, node: &Node) -> bool {
+        node.attributes().iter().any(|attribute| {
+            if !attribute.qualified_name().eq_ignore_ascii_case(&self.name) {
+                return false;
+            }
+
+            let actual = attribute.value.as_str();
+            match &self.value {
+                None => true,
+                Some((AttributeOperator::Equals, value)) => actual == value,
+                Some((AttributeOperator::Includes, value)) => {
+                    actual.split_ascii_whitespace().any(|word| word == value)
+                }
+                Some((AttributeOperator::DashMatch, value)) => {
+                    actual == value
+                        || actual
+                            .strip_prefix(value.as_str())
+                            .is_some_and(|rest| rest.starts_with('-'))
+                }
+                // An empty value never matches for these operators.
+                Some((AttributeOperator::Prefix, value)) => {
+                    !value.is_empty() && actual.starts_with(value.as_str())
+                }
+                Some((AttributeOperator::Suffix, value)) => {
+                    !value.is_empty() && actual.ends_with(value.as_str())
+                }
+                Some((AttributeOperator::Substring, value)) => {
+                    !value.is_empty() && actual.contains(value.as_str())
+                }
+            }
+        })
+    }
+}
+
+/// # Selectors
+impl NodeArena {
+    /// Returns whether `node` is an element that matches `selector`, like
+    /// `element.matches(selector)`. Selectors that are invalid or use
+    /// unsupported features, like pseudo-classes, never match.
+    ///
+    /// The selector can contain compound selectors like
+    /// `a.external[target=_blank]`, combinators and comma-separated lists.
+    /// Combinators are matched against the ancestors and preceding siblings
+    /// of `node`, wherever it is in the tree.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-element-matches
+    pub fn matches(&self, node: NodeId, selector: &str) -> bool {
+        let Some(selector_list) = SelectorList::parse(selector) else {
+            return false;
+        };
+
+        selector_list.0.iter().any(|complex_selector| {
+            self.matches_complex_selector(
+                node,
+                &complex_selector.compounds,
+                &complex_selector.combinators,
+            )
+        })
+    }
+
+    /// Matches the last of `compounds` against `node`, and the ones before it
+    /// against the nodes reached through `combinators`, from right to left.
+    fn matches_complex_selector(
+        &self,
+        node: NodeId,
+        compounds: &[CompoundSelector],
+        combinators: &[Combinator],
+    ) -> bool {
+        let Some((compound, compounds)) = compounds.split_last() else {
+            return true;
+        };
+        if !compound.matches(self.get_node(node)) {
+            return false;
+        }
+
+        let Some((combinator, combinators)) = combinators.split_last() else {
+            return true;
+        };
+        let matches = |node: NodeId| self.matches_complex_selector(node, compounds, combinators);
+        match combinator {
+            Combinator::Descendant => {
+                std::iter::successors(self.get_node(node).parent(), |ancestor| {
+                    self.get_node(*ancestor).parent()
+                })
+                .any(matches)
+            }
+            Combinator::Child => self.get_node(node).parent().is_some_and(matches),
+            Combinator::NextSibling => self.previous_element_sibling(node).is_some_and(matches),
+            Combinator::SubsequentSibling => {
+                std::iter::successors(self.previous_element_sibling(node), |sibling| {
+                    self.previous_element_sibling(*sibling)
+                })
+                .any(matches)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::parse;
+
+    #[test]
+    fn matches_attribute_selectors() {
+        let (dom, div) = parse(
+            "<div data-foo=bar data-list=\"a b\" lang=en-US></div>",
+            "div",
+        );
+        let arena = dom.arena();
+        assert!(arena.matches(div, "[data-foo=bar]"));
+        assert!(arena.matches(div, "[data-foo='bar']"));
+        assert!(arena.matches(div, "div[data-foo]"));
+        assert!(!arena.matches(div, "[data-foo=baz]"));
+        assert!(!arena.matches(div, "[data-missing]"));
+
+        assert!(arena.matches(div, "[data-list~=b]"));
+        assert!(arena.matches(div, "[lang|=en]"));
+        assert!(arena.matches(div, "[lang^=en]"));
+        assert!(arena.matches(div, "[lang$=US]"));
+        assert!(arena.matches(div, "[lang*=n-U]"));
+        assert!(!arena.matches(div, "[lang^='']"));
+    }
+
+    #[test]
+    fn matches_compound_selectors() {
+        let (dom, a) = parse(
+            "<nav><a class='external link' id=home href=/ target=_blank>x</a></nav>",
+            "a",
+        );
+        let arena = dom.arena();
+        assert!(arena.matches(a, "a"));
+        assert!(arena.matches(a, "A"));
+        assert!(arena.matches(a, "*"));
+        assert!(arena.matches(a, "a.external[target=_blank]"));
+        assert!(arena.matches(a, "#home.link.external"));
+        assert!(arena.matches(a, "nav > a, p"));
+        assert!(arena.matches(a, "body a"));
+
+        assert!(!arena.matches(a, "p"));
+        assert!(!arena.matches(a, "a.internal"));
+        assert!(!arena.matches(a, "a.external[target=_self]"));
+        assert!(!arena.matches(a, "#other.external"));
+        assert!(!arena.matches(a, "div > a"));
+        assert!(!arena.matches(a, "a:hover"));
+        assert!(!arena.matches(a, "a."));
+
+        let text = arena.get_node(a).children()[0];
+        assert!(!arena.matches(text, "*"));
+    }
+
+    #[test]
+    fn matches_sibling_combinators() {
+        let (dom, ul) = parse("<ul><li>a<li class=b>b<li class=c>c</ul>", "ul");
+        let arena = dom.arena();
+        let [a, b, c] = arena.get_node(ul).children().to_vec()[..] else {
+            panic!("expected three items");
+        };
+        assert!(arena.matches(b, "li + .b"));
+        assert!(arena.matches(c, ".b + li"));
+        // An unsupported selector makes the whole list invalid.
+        assert!(!arena.matches(c, "li:not(x), li ~ .c"));
+        assert!(arena.matches(c, "li ~ .c"));
+        assert!(!arena.matches(a, "li + li"));
+        assert!(!arena.matches(b, ".c ~ li"));
+    }
+}
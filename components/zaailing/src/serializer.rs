@@ -0,0 +1,614 @@
+use crate::arena::{NodeArena, NodeId};
+use crate::node::{Node, NodeKind};
+use crate::parser::Namespace;
+
+/// A unit of work for the serializer. Elements are entered to write their
+/// start tag and children, and left to write their end tag.
+enum SerializationStep {
+    Enter(NodeId),
+    Leave(NodeId),
+}
+
+/// Options that change how nodes are serialized. The default options produce
+/// the same output as the `innerHTML` and `outerHTML` getters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SerializerOptions {
+    /// Whether text nodes that only contain whitespace, such as the
+    /// indentation between elements, are left out of the output. This loses
+    /// information, but is convenient when extracting content.
+    pub skip_whitespace_only_text: bool,
+    /// Whether the public and system identifiers of document types are
+    /// written, like `<!DOCTYPE html PUBLIC "pub" "sys">`. The HTML
+    /// serialization algorithm always leaves them out, so this is needed to
+    /// round-trip legacy doctypes faithfully.
+    pub include_doctype_identifiers: bool,
+    /// Whether an extra newline is written after the start tag of `pre`,
+    /// `textarea` and `listing` elements whose text starts with a newline.
+    /// The parser drops the first newline in these elements, so without this
+    /// it would be lost when the output is parsed again.
+    pub preserve_leading_newlines: bool,
+    /// Whether scripting is enabled, in which case the text in `noscript`
+    /// elements is written as it is instead of escaped. This should match
+    /// [ParserOptions::scripting](crate::ParserOptions::scripting) for the
+    /// parsed contents to round-trip.
+    pub scripting: bool,
+}
+
+/// # Serializing HTML fragments
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+impl NodeArena {
+    /// Serializes the children of `node`, like the `innerHTML` getter.
+    ///
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-innerhtml
+    pub fn inner_html(&self, node: NodeId) -> String {
+        self.inner_html_with_options(node, SerializerOptions::default())
+    }
+
+    /// Like [NodeArena::inner_html], but serializes according to `options`.
+    pub fn inner_html_with_options(&self, node: NodeId, options: SerializerOptions) -> String {
+        // If the node serializes as void, then return the empty string.
+        if serializes_as_void(self.get_node(node)) {
+            return String::new();
+        }
+
+        // TODO: If the node is a template element, then let the node instead be
+        // the template element's template contents (a DocumentFragment node).
+
+        self.serialize_nodes(self.get_node(node).children(), options)
+    }
+
+    /// Serializes `node` itself including its children, like the `outerHTML`
+    /// getter.
+    ///
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+    pub fn outer_html(&self, node: NodeId) -> String {
+        self.outer_html_with_options(node, SerializerOptions::default())
+    }
+
+    /// Like [NodeArena::outer_html], but serializes according to `options`.
+    pub fn outer_html_with_options(&self, node: NodeId, options: SerializerOptions) -> String {
+        self.serialize_nodes(&[node], options)
+    }
+
+    /// Serializes `node` including its children like [NodeArena::outer_html],
+    /// but puts every element, text and comment on its own line, indented by
+    /// `indent` spaces per level of nesting. This is meant for humans and
+    /// snapshot tests, as the whitespace it adds changes the document.
+    ///
+    /// Whitespace-only text is left out and other text is trimmed. Elements
+    /// that only contain text stay on a single line, and the contents of
+    /// `pre`, `textarea`, `script` and `style` elements are written as they
+    /// are, because whitespace is significant there.
+    pub fn serialize_pretty(&self, node: NodeId, indent: usize) -> String {
+        let options = SerializerOptions::default();
+        let mut output = String::new();
+        let mut steps = vec![(SerializationStep::Enter(node), 0)];
+
+        let mut push_line = |depth: usize, line: &str| {
+            output.push_str(&" ".repeat(depth * indent));
+            output.push_str(line);
+            output.push('\n');
+        };
+
+        while let Some((step, depth)) = steps.pop() {
+            let node = match step {
+                SerializationStep::Enter(node) => node,
+                SerializationStep::Leave(node) => {
+                    let mut end_tag = String::new();
+                    push_end_tag(&mut end_tag, self.get_node(node));
+                    push_line(depth, &end_tag);
+                    continue;
+                }
+            };
+
+            let current_node = self.get_node(node);
+            match &current_node.kind {
+                NodeKind::Document { .. } => {
+                    steps.extend(
+                        current_node
+                            .children()
+                            .iter()
+                            .rev()
+                            .map(|child| (SerializationStep::Enter(*child), depth)),
+                    );
+                }
+                NodeKind::Text { .. } if current_node.is_whitespace_only_text() => {}
+                NodeKind::Text { .. } => {
+                    push_line(depth, self.serialize_nodes(&[node], options).trim());
+                }
+                NodeKind::Element { .. }
+                    if serializes_as_void(current_node)
+                        || current_node.is_element_with_one_of_tag_names(&[
+                            "pre", "textarea", "script", "style",
+                        ])
+                        || current_node
+                            .children()
+                            .iter()
+                            .all(|child| !self.get_node(*child).is_element()) =>
+                {
+                    push_line(depth, &self.serialize_nodes(&[node], options));
+                }
+                NodeKind::Element { .. } => {
+                    let mut start_tag = String::new();
+                    push_start_tag(&mut start_tag, current_node);
+                    push_line(depth, &start_tag);
+
+                    steps.push((SerializationStep::Leave(node), depth));
+                    steps.extend(
+                        current_node
+                            .children()
+                            .iter()
+                            .rev()
+                            .map(|child| (SerializationStep::Enter(*child), depth + 1)),
+                    );
+                }
+                NodeKind::Comment { .. } | NodeKind::DocumentType { .. } => {
+                    push_line(depth, &self.serialize_nodes(&[node], options));
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Serializes only the start tag of `node` with its attributes, like
+    /// `<a href="/" class="nav">`, the same way [NodeArena::outer_html]
+    /// writes it. Returns `None` if `node` is not an element.
+    pub fn serialize_open_tag(&self, node: NodeId) -> Option<String> {
+        let node = self.get_node(node);
+        if !node.is_element() {
+            return None;
+        }
+
+        let mut start_tag = String::new();
+        push_start_tag(&mut start_tag, node);
+        Some(start_tag)
+    }
+
+    /// Serializes `nodes` and their descendants in tree order. This uses an
+    /// explicit stack instead of recursion, so arbitrarily deep trees can be
+    /// serialized.
+    fn serialize_nodes(&self, nodes: &[NodeId], options: SerializerOptions) -> String {
+        let mut output = String::new();
+        let mut steps: Vec<SerializationStep> = nodes
+            .iter()
+            .rev()
+            .map(|node| SerializationStep::Enter(*node))
+            .collect();
+
+        while let Some(step) = steps.pop() {
+            let node = match step {
+                SerializationStep::Enter(node) => node,
+                SerializationStep::Leave(node) => {
+                    push_end_tag(&mut output, self.get_node(node));
+                    continue;
+                }
+            };
+
+            let current_node = self.get_node(node);
+
+            if options.skip_whitespace_only_text && current_node.is_whitespace_only_text() {
+                continue;
+            }
+
+            // Append the appropriate string from the following list to s:
+            match &current_node.kind {
+                NodeKind::Element { .. } => {
+                    push_start_tag(&mut output, current_node);
+
+                    if options.preserve_leading_newlines
+                        && current_node
+                            .is_element_with_one_of_tag_names(&["pre", "textarea", "listing"])
+                        && current_node.children().first().is_some_and(|child| {
+                            matches!(
+                                &self.get_node(*child).kind,
+                                NodeKind::Text { data } if data.starts_with('\n')
+                            )
+                        })
+                    {
+                        output.push('\n');
+                    }
+
+                    // If current node serializes as void, then continue on to
+                    // the next child node at this point.
+                    if serializes_as_void(current_node) {
+                        continue;
+                    }
+
+                    // Append the value of running the HTML fragment
+                    // serialization algorithm on the current node element,
+                    // followed by the end tag.
+                    steps.push(SerializationStep::Leave(node));
+                    steps.extend(
+                        current_node
+                            .children()
+                            .iter()
+                            .rev()
+                            .map(|child| SerializationStep::Enter(*child)),
+                    );
+                }
+                NodeKind::Text { data } => {
+                    // If the parent of current node is a style, script, xmp,
+                    // iframe, noembed, noframes, or plaintext element, or if
+                    // the parent of current node is a noscript element and
+                    // scripting is enabled for the node, then append the value
+                    // of current node's data literally.
+                    let is_raw_text = current_node.parent().is_some_and(|parent| {
+                        let parent = self.get_node(parent);
+                        parent.is_element_with_one_of_tag_names(&[
+                            "style",
+                            "script",
+                            "xmp",
+                            "iframe",
+                            "noembed",
+                            "noframes",
+                            "plaintext",
+                        ]) || (options.scripting && parent.is_element_with_tag_name("noscript"))
+                    });
+
+                    // Otherwise, append the value of current node's data,
+                    // escaped as described below.
+                    if is_raw_text {
+                        output.push_str(data);
+                    } else {
+                        output.push_str(&escape_text(data));
+                    }
+                }
+                NodeKind::Comment { data } => {
+                    // Append "<!--" (U+003C LESS-THAN SIGN, U+0021 EXCLAMATION
+                    // MARK, U+002D HYPHEN-MINUS, U+002D HYPHEN-MINUS),
+                    // followed by the value of current node's data, followed
+                    // by the literal string "-->" (U+002D HYPHEN-MINUS,
+                    // U+002D HYPHEN-MINUS, U+003E GREATER-THAN SIGN).
+                    output.push_str("<!--");
+                    output.push_str(data);
+                    output.push_str("-->");
+                }
+                NodeKind::DocumentType {
+                    name,
+                    public_id,
+                    system_id,
+                } => {
+                    // Append "<!DOCTYPE", followed by a space, followed by the
+                    // value of current node's name, followed by ">".
+                    output.push_str("<!DOCTYPE ");
+                    output.push_str(name);
+                    if options.include_doctype_identifiers {
+                        if !public_id.is_empty() {
+                            output.push_str(" PUBLIC \"");
+                            output.push_str(public_id);
+                            output.push('"');
+                            if !system_id.is_empty() {
+                                output.push_str(" \"");
+                                output.push_str(system_id);
+                                output.push('"');
+                            }
+                        } else if !system_id.is_empty() {
+                            output.push_str(" SYSTEM \"");
+                            output.push_str(system_id);
+                            output.push('"');
+                        }
+                    }
+                    output.push('>');
+                }
+                NodeKind::Document { .. } => {
+                    steps.extend(
+                        current_node
+                            .children()
+                            .iter()
+                            .rev()
+                            .map(|child| SerializationStep::Enter(*child)),
+                    );
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Writes the start tag of `element`, including its attributes.
+fn push_start_tag(output: &mut String, element: &Node) {
+    // Append a U+003C LESS-THAN SIGN character (<), followed by tagname.
+    output.push('<');
+    output.push_str(&serialized_tag_name(element));
+
+    // For each attribute that the element has, append a U+0020 SPACE
+    // character, the attribute's serialized name, a U+003D EQUALS SIGN
+    // character (=), a U+0022 QUOTATION MARK character ("), the attribute's
+    // value, escaped as described below in attribute mode, and a second U+0022
+    // QUOTATION MARK character (").
+    for attribute in element.attributes().iter() {
+        output.push(' ');
+        output.push_str(&attribute.qualified_name());
+        output.push_str("=\"");
+        output.push_str(&escape_attribute(&attribute.value));
+        output.push('"');
+    }
+
+    // Append a U+003E GREATER-THAN SIGN character (>).
+    output.push('>');
+}
+
+/// Writes the end tag of `element`.
+fn push_end_tag(output: &mut String, element: &Node) {
+    // Followed by a "</" string, tagname again, and finally a ">" character.
+    output.push_str("</");
+    output.push_str(&serialized_tag_name(element));
+    output.push('>');
+}
+
+/// The tag name that is written for `element` by the serializer.
+fn serialized_tag_name(element: &Node) -> String {
+    // If current node is an element in the HTML namespace, the MathML
+    // namespace, or the SVG namespace, then let tagname be current node's
+    // local name. Otherwise, let tagname be current node's qualified name.
+    match &element.kind {
+        NodeKind::Element { local_name, .. }
+            if element.is_element_in_namespace(Namespace::Html)
+                || element.is_element_in_namespace(Namespace::MathMl)
+                || element.is_element_in_namespace(Namespace::Svg) =>
+        {
+            local_name.clone()
+        }
+        _ => element.qualified_name(),
+    }
+}
+
+/// Whether `node` serializes as void, which is the case for void elements and
+/// a few legacy elements that are parsed the same way.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#serializes-as-void
+fn serializes_as_void(node: &Node) -> bool {
+    node.is_void_element()
+        || (node.is_element_in_namespace(Namespace::Html)
+            && node.is_element_with_one_of_tag_names(&["basefont", "bgsound", "frame"]))
+}
+
+/// Escapes `text` so it can be safely inserted as the contents of an element.
+///
+/// This escapes `&`, U+00A0 NO-BREAK SPACE, `<` and `>`, like the serializer
+/// does for text nodes.
+pub fn escape_text(text: &str) -> String {
+    escape_string(text, false)
+}
+
+/// Escapes `value` so it can be safely inserted as a double quoted attribute
+/// value.
+///
+/// This escapes `&`, U+00A0 NO-BREAK SPACE and `"`, like the serializer does
+/// for attribute values.
+pub fn escape_attribute(value: &str) -> String {
+    escape_string(value, true)
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+fn escape_string(string: &str, attribute_mode: bool) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for character in string.chars() {
+        match character {
+            // Replace any occurrence of the "&" character by the string "&amp;".
+            '&' => escaped.push_str("&amp;"),
+            // Replace any occurrences of the U+00A0 NO-BREAK SPACE character by
+            // the string "&nbsp;".
+            '\u{00A0}' => escaped.push_str("&nbsp;"),
+            // If the algorithm was invoked in the attribute mode, then replace
+            // any occurrences of the """ character by the string "&quot;".
+            '"' if attribute_mode => escaped.push_str("&quot;"),
+            // If the algorithm was not invoked in the attribute mode, then
+            // replace any occurrences of the "<" character by the string
+            // "&lt;", and any occurrences of the ">" character by the string
+            // "&gt;".
+            '<' if !attribute_mode => escaped.push_str("&lt;"),
+            '>' if !attribute_mode => escaped.push_str("&gt;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse;
+    use crate::Dom;
+
+    #[test]
+    fn inner_html_leaves_out_the_node_itself() {
+        let (dom, div) = parse("<div>wrapping <b>text</b></div>", "div");
+        assert_eq!(dom.arena().inner_html(div), "wrapping <b>text</b>");
+        assert_eq!(
+            dom.arena().outer_html(div),
+            "<div>wrapping <b>text</b></div>"
+        );
+    }
+
+    #[test]
+    fn serializes_deep_nesting() {
+        let mut arena = NodeArena::new();
+        let mut node = arena.create_text("x");
+        for _ in 0..50_000 {
+            let parent = arena.create_element("b");
+            arena.append_child(parent, node).unwrap();
+            node = parent;
+        }
+        let html = arena.outer_html(node);
+        assert_eq!(
+            html,
+            format!("{}x{}", "<b>".repeat(50_000), "</b>".repeat(50_000))
+        );
+    }
+
+    #[test]
+    fn foreign_elements_keep_their_case() {
+        let (dom, svg) = parse(
+            "<svg><linearGradient id=g></linearGradient><foreignObject></foreignObject></svg>",
+            "svg",
+        );
+        assert_eq!(
+            dom.arena().outer_html(svg),
+            "<svg><linearGradient id=\"g\"></linearGradient><foreignObject></foreignObject></svg>"
+        );
+    }
+
+    #[test]
+    fn escapes_text_and_attribute_values() {
+        assert_eq!(
+            escape_text("<script>alert(\"x & y\")</script>\u{00A0}"),
+            "&lt;script&gt;alert(\"x &amp; y\")&lt;/script&gt;&nbsp;"
+        );
+        assert_eq!(
+            escape_attribute("say \"hi\" & <wave>"),
+            "say &quot;hi&quot; &amp; <wave>"
+        );
+    }
+
+    #[test]
+    fn void_elements_have_no_end_tag() {
+        let mut arena = NodeArena::new();
+        for tag in crate::node::VOID_ELEMENTS {
+            let element = arena.create_element(tag);
+            assert_eq!(arena.outer_html(element), format!("<{tag}>"));
+        }
+
+        let (dom, p) = parse("<p>a<br>b<img src=x.png></p>", "p");
+        assert_eq!(dom.arena().inner_html(p), "a<br>b<img src=\"x.png\">");
+    }
+
+    #[test]
+    fn comments_round_trip() {
+        let (dom, div) = parse("<div><!-- a comment --><!----><!--x-y--></div>", "div");
+        assert_eq!(
+            dom.arena().inner_html(div),
+            "<!-- a comment --><!----><!--x-y-->"
+        );
+        assert!(matches!(
+            &dom.arena().get_node(dom.arena().nth_child(div, 0).unwrap()).kind,
+            NodeKind::Comment { data } if data == " a comment "
+        ));
+    }
+
+    #[test]
+    fn whitespace_only_text_is_kept_or_skipped() {
+        let (dom, div) = parse("<div>  <b>x</b>  </div>", "div");
+        let arena = dom.arena();
+        assert_eq!(arena.inner_html(div), "  <b>x</b>  ");
+        assert_eq!(arena.text_content(div), "  x  ");
+
+        let options = SerializerOptions {
+            skip_whitespace_only_text: true,
+            ..Default::default()
+        };
+        assert_eq!(arena.inner_html_with_options(div, options), "<b>x</b>");
+        assert_eq!(arena.text_content_trimmed(div), "x");
+    }
+
+    #[test]
+    fn doctype_identifiers_are_written_in_faithful_mode() {
+        let dom = Dom::parse_owned(
+            "<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \
+             \"http://www.w3.org/TR/html4/loose.dtd\"><p>x",
+        );
+        let arena = dom.arena();
+        let doctype = arena.nth_child(dom.document(), 0).unwrap();
+        assert_eq!(arena.outer_html(doctype), "<!DOCTYPE html>");
+
+        let options = SerializerOptions {
+            include_doctype_identifiers: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            arena.outer_html_with_options(doctype, options),
+            "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \
+             \"http://www.w3.org/TR/html4/loose.dtd\">"
+        );
+
+        let dom = Dom::parse_owned("<!DOCTYPE html SYSTEM \"about:legacy-compat\">");
+        let doctype = dom.arena().nth_child(dom.document(), 0).unwrap();
+        assert_eq!(
+            dom.arena().outer_html_with_options(doctype, options),
+            "<!DOCTYPE html SYSTEM \"about:legacy-compat\">"
+        );
+    }
+
+    #[test]
+    fn keeps_the_attribute_order() {
+        // The duplicate attribute is dropped, keeping the first one.
+        let (dom, div) = parse("<div c=3 a=1 b=2 a=4></div>", "div");
+        assert_eq!(
+            dom.arena().outer_html(div),
+            "<div c=\"3\" a=\"1\" b=\"2\"></div>"
+        );
+    }
+
+    #[test]
+    fn serializes_pretty() {
+        let (dom, div) = parse(
+            "<div>  <h1>Title</h1><p>Some <b>bold</b> text</p>\n<pre>  keep\n  this</pre><!-- c --></div>",
+            "div",
+        );
+        assert_eq!(
+            dom.arena().serialize_pretty(div, 2),
+            "<div>\n  <h1>Title</h1>\n  <p>\n    Some\n    <b>bold</b>\n    text\n  </p>\n  \
+             <pre>  keep\n  this</pre>\n  <!-- c -->\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn parse_serialize_parse_round_trips() {
+        let corpus = [
+            // Tables, including implied and foster-parented content.
+            "<table><caption>c</caption><colgroup><col span=2></colgroup>\
+             <tr><th>h<td>d</table>",
+            "<table>stray<tr><td>a</td></tr></table>",
+            // Formatting elements, including misnested ones.
+            "<p><b>bold <i>both</b> italic</i></p>",
+            "<a href=#>one<a href=#>two</a>",
+            "<p><b><b><b><b>x</p>y",
+            // Void elements.
+            "<br><img src=a.png alt=''><input type=text><hr><wbr>",
+            "<meta charset=utf-8><link rel=icon href=x>",
+            // Comments.
+            "<!-- before --><p>a<!-- inside --></p><!-- after -->",
+            // Doctypes.
+            "<!DOCTYPE html><title>t</title>",
+            "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \
+             \"http://www.w3.org/TR/html4/strict.dtd\"><p>legacy",
+            // Leading newlines and raw text.
+            "<pre>\n\nindented</pre><textarea>\n\nx</textarea>",
+            "<script>if (a < b) {}</script><style>a > b {}</style>",
+            "<svg viewBox='0 0 1 1'><foreignObject><p>html</p></foreignObject></svg>",
+        ];
+        let options = SerializerOptions {
+            include_doctype_identifiers: true,
+            preserve_leading_newlines: true,
+            ..Default::default()
+        };
+
+        for html in corpus {
+            let mut arena = NodeArena::new();
+            let first = Dom::parse(html, &mut arena);
+            let serialized = arena.inner_html_with_options(first, options);
+            let second = Dom::parse(&serialized, &mut arena);
+
+            assert!(
+                arena.nodes_structurally_equal(first, second),
+                "{html:?} was serialized as {serialized:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn serializes_only_the_open_tag() {
+        let (dom, a) = parse("<a href='/?q=\"x\"&y' class=nav>home <b>page</b></a>", "a");
+        let arena = dom.arena();
+        assert_eq!(
+            arena.serialize_open_tag(a).as_deref(),
+            Some("<a href=\"/?q=&quot;x&quot;&amp;y\" class=\"nav\">")
+        );
+
+        let text = arena.get_node(a).children()[0];
+        assert_eq!(arena.serialize_open_tag(text), None);
+    }
+}
@@ -0,0 +1,15 @@
+//! Helpers shared by the tests of the modules in this crate.
+
+use crate::arena::NodeId;
+use crate::Dom;
+
+/// Parses `html` and returns the document with its first `tag` element.
+pub fn parse(html: &str, tag: &str) -> (Dom, NodeId) {
+    let dom = Dom::parse_owned(html);
+    let element = dom
+        .arena()
+        .elements_by_tag(dom.document(), tag)
+        .next()
+        .unwrap();
+    (dom, element)
+}
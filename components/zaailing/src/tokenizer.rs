@@ -1,5 +1,9 @@
 #![allow(dead_code)]
 
+use std::collections::VecDeque;
+
+use crate::entities;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     Data,
@@ -107,6 +111,7 @@ pub enum Token {
         name: String,
         public_identifier: Option<String>,
         system_identifier: Option<String>,
+        force_quirks: bool,
     },
 }
 
@@ -136,10 +141,54 @@ impl Token {
     pub fn is_end_tag(&self) -> bool {
         !self.is_start_tag()
     }
+}
+
+impl std::fmt::Display for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={:?}", self.name, self.value)
+    }
+}
 
-    pub fn acknowledge_self_closing_flag(&self) {
-        // TODO: Add a flag to the current tag token to note that it is
-        // self-closing.
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::EndOfFile => write!(f, "EOF"),
+            Token::Character(character) => write!(f, "Char({:?})", character),
+            Token::Tag {
+                start,
+                tag_name,
+                attributes,
+                self_closing,
+            } => {
+                let kind = if *start { "StartTag" } else { "EndTag" };
+                let attributes = attributes
+                    .iter()
+                    .map(|attribute| attribute.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let self_closing = if *self_closing { ", self-closing" } else { "" };
+                write!(f, "{kind}({tag_name}, [{attributes}]{self_closing})")
+            }
+            Token::Comment { data } => write!(f, "Comment({:?})", data),
+            Token::Doctype {
+                name,
+                public_identifier,
+                system_identifier,
+                force_quirks,
+            } => {
+                write!(f, "Doctype({name}")?;
+                if let Some(public_identifier) = public_identifier {
+                    write!(f, ", public={:?}", public_identifier)?;
+                }
+                if let Some(system_identifier) = system_identifier {
+                    write!(f, ", system={:?}", system_identifier)?;
+                }
+                if *force_quirks {
+                    write!(f, ", force-quirks")?;
+                }
+                write!(f, ")")
+            }
+        }
     }
 }
 
@@ -184,10 +233,22 @@ pub struct Tokenizer<'input> {
     html: &'input str,
     state: State,
     return_state: State,
-    tokens: Vec<Token>,
+    emitted_tokens: VecDeque<Token>,
     current_token: Option<Token>,
+    /// The byte offset in `html` of the next input character.
     insertion_point: usize,
+    /// The last character that was consumed, or `None` if that was the end of
+    /// the input.
+    current_input_character: Option<char>,
     temporary_buffer: String,
+    /// The tag name of the last start tag that was emitted, used to find
+    /// appropriate end tag tokens.
+    last_start_tag_name: Option<String>,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-code
+    character_reference_code: u32,
+    /// Whether the adjusted current node of the tree construction stage is
+    /// an element outside of the HTML namespace.
+    adjusted_current_node_is_foreign: bool,
 }
 
 impl<'input> Tokenizer<'input> {
@@ -196,36 +257,49 @@ impl<'input> Tokenizer<'input> {
             html,
             state: State::Data,
             return_state: State::Data,
-            tokens: vec![],
+            emitted_tokens: VecDeque::new(),
             current_token: None,
             insertion_point: 0,
+            current_input_character: None,
             temporary_buffer: String::new(),
+            last_start_tag_name: None,
+            character_reference_code: 0,
+            adjusted_current_node_is_foreign: false,
         }
     }
 
-    pub fn peek(&mut self) -> Option<&Token> {
-        self.tokens.last()
-    }
-
     pub fn next(&mut self) -> Option<Token> {
-        let mut emitted_tokens: Vec<Token> = vec![];
+        // Some states emit more than one token at once, so hand out the ones
+        // left over from the previous call before consuming more input.
+        if let Some(token) = self.emitted_tokens.pop_front() {
+            return Some(token);
+        }
 
         macro_rules! emit_token {
             ($token:expr) => {
-                emitted_tokens.push($token);
+                self.emitted_tokens.push_back($token);
             };
         }
 
         macro_rules! emit_current_token {
             () => {
-                if let Some(token) = self.current_token.take() {
+                if let Some(mut token) = self.current_token.take() {
+                    remove_duplicate_attributes(&mut token);
+                    if let Token::Tag {
+                        start: true,
+                        tag_name,
+                        ..
+                    } = &token
+                    {
+                        self.last_start_tag_name = Some(tag_name.clone());
+                    }
                     emit_token!(token);
                     self.current_token = None;
                 }
             };
         }
 
-        while emitted_tokens.is_empty() {
+        while self.emitted_tokens.is_empty() {
             match self.state {
                 State::Data => match self.consume_next_input_character() {
                     Some('&') => {
@@ -236,7 +310,8 @@ impl<'input> Tokenizer<'input> {
                         self.switch_to(State::TagOpen);
                     }
                     null!() => {
-                        todo!("This is an unexpected-null-character parse error. Emit the current input character as a character token.");
+                        // This is an unexpected-null-character parse error.
+                        emit_token!(Token::Character('\0'));
                     }
                     eof!() => {
                         emit_token!(Token::EndOfFile);
@@ -254,7 +329,8 @@ impl<'input> Tokenizer<'input> {
                         self.switch_to(State::RcDataLessThanSign);
                     }
                     null!() => {
-                        todo!("This is an unexpected-null-character parse error. Emit a U+FFFD REPLACEMENT CHARACTER character token.");
+                        // This is an unexpected-null-character parse error.
+                        emit_token!(Token::Character('\u{FFFD}'));
                     }
                     eof!() => {
                         emit_token!(Token::EndOfFile);
@@ -268,7 +344,35 @@ impl<'input> Tokenizer<'input> {
                         self.switch_to(State::RawTextLessThanSign);
                     }
                     null!() => {
-                        todo!("This is an unexpected-null-character parse error. Emit a U+FFFD REPLACEMENT CHARACTER character token.");
+                        // This is an unexpected-null-character parse error.
+                        emit_token!(Token::Character('\u{FFFD}'));
+                    }
+                    eof!() => {
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        emit_token!(Token::Character(anything_else));
+                    }
+                },
+                State::ScriptData => match self.consume_next_input_character() {
+                    Some('<') => {
+                        self.switch_to(State::ScriptDataLessThanSign);
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        emit_token!(Token::Character('\u{FFFD}'));
+                    }
+                    eof!() => {
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        emit_token!(Token::Character(anything_else));
+                    }
+                },
+                State::PlainText => match self.consume_next_input_character() {
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        emit_token!(Token::Character('\u{FFFD}'));
                     }
                     eof!() => {
                         emit_token!(Token::EndOfFile);
@@ -277,8 +381,6 @@ impl<'input> Tokenizer<'input> {
                         emit_token!(Token::Character(anything_else));
                     }
                 },
-                State::ScriptData => todo!("ScriptData"),
-                State::PlainText => todo!("PlainText"),
                 State::TagOpen => match self.consume_next_input_character() {
                     Some('!') => {
                         self.switch_to(State::MarkupDeclarationOpen);
@@ -296,13 +398,21 @@ impl<'input> Tokenizer<'input> {
                         self.reconsume_in_state(State::TagName);
                     }
                     Some('?') => {
-                        todo!("This is an unexpected-question-mark-instead-of-tag-name parse error. Create a comment token whose data is the empty string. Reconsume in the bogus comment state.");
+                        // This is an unexpected-question-mark-instead-of-tag-name parse error.
+                        self.set_current_token(Token::Comment {
+                            data: "".to_string(),
+                        });
+                        self.reconsume_in_state(State::BogusComment);
                     }
                     eof!() => {
-                        todo!("This is an eof-before-tag-name parse error. Emit a U+003C LESS-THAN SIGN character token and an end-of-file token.");
+                        // This is an eof-before-tag-name parse error.
+                        emit_token!(Token::Character('<'));
+                        emit_token!(Token::EndOfFile);
                     }
                     Some(_) => {
-                        todo!("This is an invalid-first-character-of-tag-name parse error. Emit a U+003C LESS-THAN SIGN character token. Reconsume in the data state.");
+                        // This is an invalid-first-character-of-tag-name parse error.
+                        emit_token!(Token::Character('<'));
+                        self.reconsume_in_state(State::Data);
                     }
                 },
                 State::EndTagOpen => {
@@ -317,13 +427,21 @@ impl<'input> Tokenizer<'input> {
                             self.reconsume_in_state(State::TagName);
                         }
                         Some('>') => {
-                            todo!("This is a missing-end-tag-name parse error. Switch to the data state.");
+                            // This is a missing-end-tag-name parse error.
+                            self.switch_to(State::Data);
                         }
                         eof!() => {
-                            todo!("This is an eof-before-tag-name parse error. Emit a U+003C LESS-THAN SIGN character token, a U+002F SOLIDUS character token and an end-of-file token.");
+                            // This is an eof-before-tag-name parse error.
+                            emit_token!(Token::Character('<'));
+                            emit_token!(Token::Character('/'));
+                            emit_token!(Token::EndOfFile);
                         }
                         Some(_) => {
-                            todo!("This is an invalid-first-character-of-tag-name parse error. Create a comment token whose data is the empty string. Reconsume in the bogus comment state.");
+                            // This is an invalid-first-character-of-tag-name parse error.
+                            self.set_current_token(Token::Comment {
+                                data: "".to_string(),
+                            });
+                            self.reconsume_in_state(State::BogusComment);
                         }
                     }
                 }
@@ -339,10 +457,14 @@ impl<'input> Tokenizer<'input> {
                         emit_current_token!();
                     }
                     null!() => {
-                        todo!("This is an unexpected-null-character parse error. Append a U+FFFD REPLACEMENT CHARACTER character to the current tag token's tag name.");
+                        // This is an unexpected-null-character parse error.
+                        if let Some(Token::Tag { tag_name, .. }) = &mut self.current_token {
+                            tag_name.push('\u{FFFD}');
+                        }
                     }
                     eof!() => {
-                        todo!("This is an eof-in-tag parse error. Emit an end-of-file token.");
+                        // This is an eof-in-tag parse error.
+                        emit_token!(Token::EndOfFile);
                     }
                     Some(anything_else) => {
                         // ASCII upper alpha:
@@ -383,23 +505,15 @@ impl<'input> Tokenizer<'input> {
                     }
                 },
                 State::RcDataEndTagName => match self.consume_next_input_character() {
-                    whitespace!() => {
-                        todo!("If the current end tag token is an appropriate end tag token, then switch to the before attribute name state. Otherwise, treat it as per the 'anything else' entry below.");
+                    whitespace!() if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::BeforeAttributeName);
                     }
-                    Some('/') => {
-                        if self.current_end_tag_token_is_appropriate() {
-                            self.switch_to(State::SelfClosingStartTag);
-                        } else {
-                            todo!("Otherwise, treat it as per the 'anything else' entry below.");
-                        }
+                    Some('/') if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::SelfClosingStartTag);
                     }
-                    Some('>') => {
-                        if self.current_end_tag_token_is_appropriate() {
-                            self.switch_to(State::Data);
-                            emit_current_token!();
-                        } else {
-                            todo!("Otherwise, treat it as per the 'anything else' entry below.");
-                        }
+                    Some('>') if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
                     }
                     ascii_upper_alpha!() => {
                         let char = self.current_input_character().unwrap();
@@ -416,6 +530,9 @@ impl<'input> Tokenizer<'input> {
                         self.temporary_buffer.push(char);
                     }
                     _ => {
+                        // Whitespace, "/" and ">" also end up here if the current end tag
+                        // token is not an appropriate end tag token.
+                        self.current_token = None;
                         emit_token!(Token::Character('<'));
                         emit_token!(Token::Character('/'));
                         for char in self.temporary_buffer.chars() {
@@ -451,27 +568,15 @@ impl<'input> Tokenizer<'input> {
                     }
                 },
                 State::RawTextEndTagName => match self.consume_next_input_character() {
-                    whitespace!() => {
-                        if self.current_end_tag_token_is_appropriate() {
-                            self.switch_to(State::BeforeAttributeName);
-                        } else {
-                            todo!("Otherwise, treat it as per the 'anything else' entry below.");
-                        }
+                    whitespace!() if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::BeforeAttributeName);
                     }
-                    Some('/') => {
-                        if self.current_end_tag_token_is_appropriate() {
-                            self.switch_to(State::SelfClosingStartTag);
-                        } else {
-                            todo!("Otherwise, treat it as per the 'anything else' entry below.");
-                        }
+                    Some('/') if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::SelfClosingStartTag);
                     }
-                    Some('>') => {
-                        if self.current_end_tag_token_is_appropriate() {
-                            self.switch_to(State::Data);
-                            emit_current_token!();
-                        } else {
-                            todo!("Otherwise, treat it as per the 'anything else' entry below.");
-                        }
+                    Some('>') if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
                     }
                     ascii_upper_alpha!() => {
                         let char = self.current_input_character().unwrap();
@@ -488,6 +593,9 @@ impl<'input> Tokenizer<'input> {
                         self.temporary_buffer.push(char);
                     }
                     _ => {
+                        // Whitespace, "/" and ">" also end up here if the current end tag
+                        // token is not an appropriate end tag token.
+                        self.current_token = None;
                         emit_token!(Token::Character('<'));
                         emit_token!(Token::Character('/'));
                         for char in self.temporary_buffer.chars() {
@@ -496,308 +604,1544 @@ impl<'input> Tokenizer<'input> {
                         self.reconsume_in_state(State::RawText);
                     }
                 },
-                State::ScriptDataLessThanSign => todo!("ScriptDataLessThanSign"),
-                State::ScriptDataEndTagOpen => todo!("ScriptDataEndTagOpen"),
-                State::ScriptDataEndTagName => todo!("ScriptDataEndTagName"),
-                State::ScriptDataEscapeStart => todo!("ScriptDataEscapeStart"),
-                State::ScriptDataEscapeStartDash => todo!("ScriptDataEscapeStartDash"),
-                State::ScriptDataEscaped => todo!("ScriptDataEscaped"),
-                State::ScriptDataEscapedDash => todo!("ScriptDataEscapedDash"),
-                State::ScriptDataEscapedDashDash => todo!("ScriptDataEscapedDashDash"),
-                State::ScriptDataEscapedLessThanSign => todo!("ScriptDataEscapedLessThanSign"),
-                State::ScriptDataEscapedEndTagOpen => todo!("ScriptDataEscapedEndTagOpen"),
-                State::ScriptDataEscapedEndTagName => todo!("ScriptDataEscapedEndTagName"),
-                State::ScriptDataDoubleEscapeStart => todo!("ScriptDataDoubleEscapeStart"),
-                State::ScriptDataDoubleEscaped => todo!("ScriptDataDoubleEscaped"),
-                State::ScriptDataDoubleEscapedDash => todo!("ScriptDataDoubleEscapedDash"),
-                State::ScriptDataDoubleEscapedDashDash => todo!("ScriptDataDoubleEscapedDashDash"),
-                State::ScriptDataDoubleEscapedLessThanSign => {
-                    todo!("ScriptDataDoubleEscapedLessThanSign")
-                }
-                State::ScriptDataDoubleEscapeEnd => todo!("ScriptDataDoubleEscapeEnd"),
-                State::BeforeAttributeName => match self.consume_next_input_character() {
-                    whitespace!() => {}
-                    Some('/') | Some('<') | eof!() => {
-                        self.reconsume_in_state(State::AfterAttributeName);
+                State::ScriptDataLessThanSign => match self.consume_next_input_character() {
+                    Some('/') => {
+                        self.temporary_buffer = String::new();
+                        self.switch_to(State::ScriptDataEndTagOpen);
                     }
-                    Some('=') => {
-                        todo!("This is an unexpected-equals-sign-before-attribute-name parse error. Start a new attribute in the current tag token. Set that attribute's name to the current input character, and its value to the empty string. Switch to the attribute name state.");
+                    Some('!') => {
+                        self.switch_to(State::ScriptDataEscapeStart);
+                        emit_token!(Token::Character('<'));
+                        emit_token!(Token::Character('!'));
                     }
-                    Some(_) => {
-                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
-                            attributes.push(Attribute {
-                                name: "".to_string(),
-                                value: "".to_string(),
-                            })
-                        }
-                        self.reconsume_in_state(State::AttributeName);
+                    _ => {
+                        emit_token!(Token::Character('<'));
+                        self.reconsume_in_state(State::ScriptData);
                     }
                 },
-                State::AttributeName => match self.consume_next_input_character() {
-                    whitespace!() | Some('/') | Some('>') | eof!() => {
-                        self.reconsume_in_state(State::AfterAttributeName);
+                State::ScriptDataEndTagOpen => match self.consume_next_input_character() {
+                    ascii_alpha!() => {
+                        self.set_current_token(Token::Tag {
+                            start: false,
+                            tag_name: "".to_string(),
+                            attributes: vec![],
+                            self_closing: false,
+                        });
+                        self.reconsume_in_state(State::ScriptDataEndTagName);
                     }
-                    Some('=') => {
-                        self.switch_to(State::BeforeAttributeValue);
+                    _ => {
+                        emit_token!(Token::Character('<'));
+                        emit_token!(Token::Character('/'));
+                        self.reconsume_in_state(State::ScriptData);
                     }
-                    null!() => {
-                        todo!("This is an unexpected-null-character parse error. Append a U+FFFD REPLACEMENT CHARACTER character to the current attribute's name.");
+                },
+                State::ScriptDataEndTagName => match self.consume_next_input_character() {
+                    whitespace!() if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::BeforeAttributeName);
                     }
-                    Some('"') | Some('\'') | Some('<') => {
-                        todo!("This is an unexpected-character-in-attribute-name parse error. Treat it as per the 'anything else' entry below.");
+                    Some('/') if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::SelfClosingStartTag);
                     }
-                    Some(anything_else) => {
-                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
-                            if let Some(attribute) = attributes.last_mut() {
-                                attribute.name.push(anything_else);
-                            }
+                    Some('>') if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    ascii_upper_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        if let Some(Token::Tag { tag_name, .. }) = &mut self.current_token {
+                            tag_name.push(char.to_ascii_lowercase());
+                        }
+                        self.temporary_buffer.push(char);
+                    }
+                    ascii_lower_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        if let Some(Token::Tag { tag_name, .. }) = &mut self.current_token {
+                            tag_name.push(char);
+                        }
+                        self.temporary_buffer.push(char);
+                    }
+                    _ => {
+                        // Whitespace, "/" and ">" also end up here if the current end tag
+                        // token is not an appropriate end tag token.
+                        self.current_token = None;
+                        emit_token!(Token::Character('<'));
+                        emit_token!(Token::Character('/'));
+                        for char in self.temporary_buffer.chars() {
+                            emit_token!(Token::Character(char));
                         }
+                        self.reconsume_in_state(State::ScriptData);
                     }
                 },
-                State::AfterAttributeName => todo!("AfterAttributeName"),
-                State::BeforeAttributeValue => match self.consume_next_input_character() {
-                    whitespace!() => {}
-                    Some('"') => {
-                        self.switch_to(State::AttributeValueDoubleQuoted);
+                State::ScriptDataEscapeStart => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::ScriptDataEscapeStartDash);
+                        emit_token!(Token::Character('-'));
                     }
-                    Some('\'') => {
-                        self.switch_to(State::AttributeValueSingleQuoted);
+                    _ => {
+                        self.reconsume_in_state(State::ScriptData);
                     }
-                    Some('>') => {
-                        todo!("This is a missing-attribute-value parse error. Switch to the data state. Emit the current tag token.");
+                },
+                State::ScriptDataEscapeStartDash => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::ScriptDataEscapedDashDash);
+                        emit_token!(Token::Character('-'));
                     }
-                    Some(_) | eof!() => {
-                        self.reconsume_in_state(State::AttributeValueUnquoted);
+                    _ => {
+                        self.reconsume_in_state(State::ScriptData);
                     }
                 },
-                State::AttributeValueDoubleQuoted => match self.consume_next_input_character() {
-                    Some('"') => {
-                        self.switch_to(State::AfterAttributeValueQuoted);
+                State::ScriptDataEscaped => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::ScriptDataEscapedDash);
+                        emit_token!(Token::Character('-'));
                     }
-                    Some('&') => {
-                        self.set_return_state(State::AttributeValueDoubleQuoted);
-                        self.switch_to(State::CharacterReference);
+                    Some('<') => {
+                        self.switch_to(State::ScriptDataEscapedLessThanSign);
                     }
                     null!() => {
-                        todo!("This is an unexpected-null-character parse error. Append a U+FFFD REPLACEMENT CHARACTER character to the current attribute's value.");
+                        // This is an unexpected-null-character parse error.
+                        emit_token!(Token::Character('\u{FFFD}'));
                     }
                     eof!() => {
-                        todo!("This is an eof-in-tag parse error. Emit an end-of-file token.");
+                        // This is an eof-in-script-html-comment-like-text parse error.
+                        emit_token!(Token::EndOfFile);
                     }
                     Some(anything_else) => {
-                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
-                            if let Some(attribute) = attributes.last_mut() {
-                                attribute.value.push(anything_else);
-                            }
-                        }
+                        emit_token!(Token::Character(anything_else));
                     }
                 },
-                State::AttributeValueSingleQuoted => todo!("AttributeValueSingleQuoted"),
-                State::AttributeValueUnquoted => todo!("AttributeValueUnquoted"),
-                State::AfterAttributeValueQuoted => match self.consume_next_input_character() {
-                    whitespace!() => {
-                        self.switch_to(State::BeforeAttributeName);
+                State::ScriptDataEscapedDash => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::ScriptDataEscapedDashDash);
+                        emit_token!(Token::Character('-'));
                     }
-                    Some('/') => {
-                        self.switch_to(State::SelfClosingStartTag);
+                    Some('<') => {
+                        self.switch_to(State::ScriptDataEscapedLessThanSign);
                     }
-                    Some('>') => {
-                        self.switch_to(State::Data);
-                        emit_current_token!();
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        self.switch_to(State::ScriptDataEscaped);
+                        emit_token!(Token::Character('\u{FFFD}'));
                     }
                     eof!() => {
-                        todo!("This is an eof-in-tag parse error. Emit an end-of-file token.");
+                        // This is an eof-in-script-html-comment-like-text parse error.
+                        emit_token!(Token::EndOfFile);
                     }
-                    Some(_) => {
-                        todo!("This is a missing-whitespace-between-attributes parse error. Reconsume in the before attribute name state.");
+                    Some(anything_else) => {
+                        self.switch_to(State::ScriptDataEscaped);
+                        emit_token!(Token::Character(anything_else));
                     }
                 },
-                State::SelfClosingStartTag => match self.consume_next_input_character() {
+                State::ScriptDataEscapedDashDash => match self.consume_next_input_character() {
+                    Some('-') => {
+                        emit_token!(Token::Character('-'));
+                    }
+                    Some('<') => {
+                        self.switch_to(State::ScriptDataEscapedLessThanSign);
+                    }
                     Some('>') => {
-                        if let Some(Token::Tag { self_closing, .. }) = &mut self.current_token {
-                            *self_closing = true;
-                        }
-                        self.switch_to(State::Data);
-                        emit_current_token!();
+                        self.switch_to(State::ScriptData);
+                        emit_token!(Token::Character('>'));
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        self.switch_to(State::ScriptDataEscaped);
+                        emit_token!(Token::Character('\u{FFFD}'));
                     }
                     eof!() => {
-                        todo!("This is an eof-in-tag parse error. Emit an end-of-file token.");
+                        // This is an eof-in-script-html-comment-like-text parse error.
+                        emit_token!(Token::EndOfFile);
                     }
-                    Some(_) => {
-                        todo!("This is an unexpected-solidus-in-tag parse error. Reconsume in the before attribute name state.");
+                    Some(anything_else) => {
+                        self.switch_to(State::ScriptDataEscaped);
+                        emit_token!(Token::Character(anything_else));
                     }
                 },
-                State::BogusComment => todo!("BogusComment"),
-                State::MarkupDeclarationOpen => {
-                    if self.next_few_input_characters_are("--", false) {
-                        self.consume_word("--");
-                        self.set_current_token(Token::Comment {
-                            data: "".to_string(),
-                        });
-                        self.switch_to(State::CommentStart);
-                    } else if self.next_few_input_characters_are("DOCTYPE", true) {
-                        self.consume_word("DOCTYPE");
-                        self.switch_to(State::Doctype);
-                    }
-                }
-                State::CommentStart => todo!("CommentStart"),
-                State::CommentStartDash => todo!("CommentStartDash"),
-                State::Comment => todo!("Comment"),
-                State::CommentLessThanSign => todo!("CommentLessThanSign"),
-                State::CommentLessThanSignBang => todo!("CommentLessThanSignBang"),
-                State::CommentLessThanSignBangDash => todo!("CommentLessThanSignBangDash"),
-                State::CommentLessThanSignBangDashDash => todo!("CommentLessThanSignBangDashDash"),
-                State::CommentEndDash => todo!("CommentEndDash"),
-                State::CommentEnd => todo!("CommentEnd"),
-                State::CommentEndBang => todo!("CommentEndBang"),
-                State::Doctype => match self.consume_next_input_character() {
-                    whitespace!() => {
-                        self.switch_to(State::BeforeDoctypeName);
-                    }
-                    Some('>') => {
-                        self.reconsume_in_state(State::BeforeDoctypeName);
+                State::ScriptDataEscapedLessThanSign => match self.consume_next_input_character() {
+                    Some('/') => {
+                        self.temporary_buffer = String::new();
+                        self.switch_to(State::ScriptDataEscapedEndTagOpen);
                     }
-                    eof!() => {
-                        todo!("This is an eof-in-doctype parse error. Create a new DOCTYPE token. Set its force-quirks flag to on. Emit the current token. Emit an end-of-file token.");
+                    ascii_alpha!() => {
+                        self.temporary_buffer = String::new();
+                        emit_token!(Token::Character('<'));
+                        self.reconsume_in_state(State::ScriptDataDoubleEscapeStart);
                     }
                     _ => {
-                        todo!("This is a missing-whitespace-before-doctype-name parse error. Reconsume in the before DOCTYPE name state.");
+                        emit_token!(Token::Character('<'));
+                        self.reconsume_in_state(State::ScriptDataEscaped);
                     }
                 },
-                State::BeforeDoctypeName => match self.consume_next_input_character() {
-                    whitespace!() => {}
-                    ascii_upper_alpha!() => {
-                        self.set_current_token(Token::Doctype {
-                            name: self
-                                .current_input_character()
-                                .unwrap()
-                                .to_ascii_lowercase()
-                                .to_string(),
-                            public_identifier: None,
-                            system_identifier: None,
+                State::ScriptDataEscapedEndTagOpen => match self.consume_next_input_character() {
+                    ascii_alpha!() => {
+                        self.set_current_token(Token::Tag {
+                            start: false,
+                            tag_name: "".to_string(),
+                            attributes: vec![],
+                            self_closing: false,
                         });
-                        self.switch_to(State::DoctypeName);
+                        self.reconsume_in_state(State::ScriptDataEscapedEndTagName);
                     }
-                    null!() => {
-                        todo!("This is an unexpected-null-character parse error. Create a new DOCTYPE token. Set the token's name to a U+FFFD REPLACEMENT CHARACTER character. Switch to the DOCTYPE name state.");
+                    _ => {
+                        emit_token!(Token::Character('<'));
+                        emit_token!(Token::Character('/'));
+                        self.reconsume_in_state(State::ScriptDataEscaped);
                     }
-                    Some('>') => {
-                        todo!("This is a missing-doctype-name parse error. Create a new DOCTYPE token. Set its force-quirks flag to on. Switch to the data state. Emit the current token.")
+                },
+                State::ScriptDataEscapedEndTagName => match self.consume_next_input_character() {
+                    whitespace!() if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::BeforeAttributeName);
                     }
-                    eof!() => {
-                        todo!("This is an eof-in-doctype parse error. Create a new DOCTYPE token. Set its force-quirks flag to on. Emit the current token. Emit an end-of-file token.");
+                    Some('/') if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::SelfClosingStartTag);
                     }
-                    Some(char) => {
-                        self.set_current_token(Token::Doctype {
-                            name: char.to_string(),
-                            public_identifier: None,
-                            system_identifier: None,
-                        });
-                        self.switch_to(State::DoctypeName);
+                    Some('>') if self.current_end_tag_token_is_appropriate() => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    ascii_upper_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        if let Some(Token::Tag { tag_name, .. }) = &mut self.current_token {
+                            tag_name.push(char.to_ascii_lowercase());
+                        }
+                        self.temporary_buffer.push(char);
+                    }
+                    ascii_lower_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        if let Some(Token::Tag { tag_name, .. }) = &mut self.current_token {
+                            tag_name.push(char);
+                        }
+                        self.temporary_buffer.push(char);
+                    }
+                    _ => {
+                        // Whitespace, "/" and ">" also end up here if the current end tag
+                        // token is not an appropriate end tag token.
+                        self.current_token = None;
+                        emit_token!(Token::Character('<'));
+                        emit_token!(Token::Character('/'));
+                        for char in self.temporary_buffer.chars() {
+                            emit_token!(Token::Character(char));
+                        }
+                        self.reconsume_in_state(State::ScriptDataEscaped);
+                    }
+                },
+                State::ScriptDataDoubleEscapeStart => match self.consume_next_input_character() {
+                    whitespace!() | Some('/') | Some('>') => {
+                        let char = self.current_input_character().unwrap();
+                        if self.temporary_buffer == "script" {
+                            self.switch_to(State::ScriptDataDoubleEscaped);
+                        } else {
+                            self.switch_to(State::ScriptDataEscaped);
+                        }
+                        emit_token!(Token::Character(char));
+                    }
+                    ascii_upper_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        self.temporary_buffer.push(char.to_ascii_lowercase());
+                        emit_token!(Token::Character(char));
+                    }
+                    ascii_lower_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        self.temporary_buffer.push(char);
+                        emit_token!(Token::Character(char));
+                    }
+                    _ => {
+                        self.reconsume_in_state(State::ScriptDataEscaped);
+                    }
+                },
+                State::ScriptDataDoubleEscaped => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::ScriptDataDoubleEscapedDash);
+                        emit_token!(Token::Character('-'));
+                    }
+                    Some('<') => {
+                        self.switch_to(State::ScriptDataDoubleEscapedLessThanSign);
+                        emit_token!(Token::Character('<'));
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        emit_token!(Token::Character('\u{FFFD}'));
+                    }
+                    eof!() => {
+                        // This is an eof-in-script-html-comment-like-text parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        emit_token!(Token::Character(anything_else));
+                    }
+                },
+                State::ScriptDataDoubleEscapedDash => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::ScriptDataDoubleEscapedDashDash);
+                        emit_token!(Token::Character('-'));
+                    }
+                    Some('<') => {
+                        self.switch_to(State::ScriptDataDoubleEscapedLessThanSign);
+                        emit_token!(Token::Character('<'));
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        self.switch_to(State::ScriptDataDoubleEscaped);
+                        emit_token!(Token::Character('\u{FFFD}'));
+                    }
+                    eof!() => {
+                        // This is an eof-in-script-html-comment-like-text parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        self.switch_to(State::ScriptDataDoubleEscaped);
+                        emit_token!(Token::Character(anything_else));
+                    }
+                },
+                State::ScriptDataDoubleEscapedDashDash => match self.consume_next_input_character()
+                {
+                    Some('-') => {
+                        emit_token!(Token::Character('-'));
+                    }
+                    Some('<') => {
+                        self.switch_to(State::ScriptDataDoubleEscapedLessThanSign);
+                        emit_token!(Token::Character('<'));
+                    }
+                    Some('>') => {
+                        self.switch_to(State::ScriptData);
+                        emit_token!(Token::Character('>'));
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        self.switch_to(State::ScriptDataDoubleEscaped);
+                        emit_token!(Token::Character('\u{FFFD}'));
+                    }
+                    eof!() => {
+                        // This is an eof-in-script-html-comment-like-text parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        self.switch_to(State::ScriptDataDoubleEscaped);
+                        emit_token!(Token::Character(anything_else));
+                    }
+                },
+                State::ScriptDataDoubleEscapedLessThanSign => {
+                    match self.consume_next_input_character() {
+                        Some('/') => {
+                            self.temporary_buffer = String::new();
+                            self.switch_to(State::ScriptDataDoubleEscapeEnd);
+                            emit_token!(Token::Character('/'));
+                        }
+                        _ => {
+                            self.reconsume_in_state(State::ScriptDataDoubleEscaped);
+                        }
+                    }
+                }
+                State::ScriptDataDoubleEscapeEnd => match self.consume_next_input_character() {
+                    whitespace!() | Some('/') | Some('>') => {
+                        let char = self.current_input_character().unwrap();
+                        if self.temporary_buffer == "script" {
+                            self.switch_to(State::ScriptDataEscaped);
+                        } else {
+                            self.switch_to(State::ScriptDataDoubleEscaped);
+                        }
+                        emit_token!(Token::Character(char));
+                    }
+                    ascii_upper_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        self.temporary_buffer.push(char.to_ascii_lowercase());
+                        emit_token!(Token::Character(char));
+                    }
+                    ascii_lower_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        self.temporary_buffer.push(char);
+                        emit_token!(Token::Character(char));
+                    }
+                    _ => {
+                        self.reconsume_in_state(State::ScriptDataDoubleEscaped);
+                    }
+                },
+                State::BeforeAttributeName => match self.consume_next_input_character() {
+                    whitespace!() => {}
+                    Some('/') | Some('>') | eof!() => {
+                        self.reconsume_in_state(State::AfterAttributeName);
+                    }
+                    Some('=') => {
+                        // This is an unexpected-equals-sign-before-attribute-name parse error.
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            attributes.push(Attribute {
+                                name: "=".to_string(),
+                                value: "".to_string(),
+                            })
+                        }
+                        self.switch_to(State::AttributeName);
+                    }
+                    Some(_) => {
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            attributes.push(Attribute {
+                                name: "".to_string(),
+                                value: "".to_string(),
+                            })
+                        }
+                        self.reconsume_in_state(State::AttributeName);
+                    }
+                },
+                State::AttributeName => match self.consume_next_input_character() {
+                    whitespace!() | Some('/') | Some('>') | eof!() => {
+                        self.reconsume_in_state(State::AfterAttributeName);
+                    }
+                    Some('=') => {
+                        self.switch_to(State::BeforeAttributeValue);
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            if let Some(attribute) = attributes.last_mut() {
+                                attribute.name.push('\u{FFFD}');
+                            }
+                        }
+                    }
+                    Some(anything_else) => {
+                        // U+0022 QUOTATION MARK ("), U+0027 APOSTROPHE (') and U+003C
+                        // LESS-THAN SIGN (<) are an unexpected-character-in-attribute-name
+                        // parse error, and are otherwise treated as per this entry.
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            if let Some(attribute) = attributes.last_mut() {
+                                attribute.name.push(anything_else);
+                            }
+                        }
+                    }
+                },
+                State::AfterAttributeName => match self.consume_next_input_character() {
+                    whitespace!() => {}
+                    Some('/') => {
+                        self.switch_to(State::SelfClosingStartTag);
+                    }
+                    Some('=') => {
+                        self.switch_to(State::BeforeAttributeValue);
+                    }
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-tag parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(_) => {
+                        // Start a new attribute in the current tag token. Set that attribute name
+                        // and value to the empty string.
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            attributes.push(Attribute {
+                                name: "".to_string(),
+                                value: "".to_string(),
+                            })
+                        }
+                        // Reconsume in the attribute name state.
+                        self.reconsume_in_state(State::AttributeName);
+                    }
+                },
+                State::BeforeAttributeValue => match self.consume_next_input_character() {
+                    whitespace!() => {}
+                    Some('"') => {
+                        self.switch_to(State::AttributeValueDoubleQuoted);
+                    }
+                    Some('\'') => {
+                        self.switch_to(State::AttributeValueSingleQuoted);
+                    }
+                    Some('>') => {
+                        // This is a missing-attribute-value parse error.
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    Some(_) | eof!() => {
+                        self.reconsume_in_state(State::AttributeValueUnquoted);
+                    }
+                },
+                State::AttributeValueDoubleQuoted => match self.consume_next_input_character() {
+                    Some('"') => {
+                        self.switch_to(State::AfterAttributeValueQuoted);
+                    }
+                    Some('&') => {
+                        self.set_return_state(State::AttributeValueDoubleQuoted);
+                        self.switch_to(State::CharacterReference);
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            if let Some(attribute) = attributes.last_mut() {
+                                attribute.value.push('\u{FFFD}');
+                            }
+                        }
+                    }
+                    eof!() => {
+                        // This is an eof-in-tag parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            if let Some(attribute) = attributes.last_mut() {
+                                attribute.value.push(anything_else);
+                            }
+                        }
+                    }
+                },
+                State::AttributeValueSingleQuoted => match self.consume_next_input_character() {
+                    Some('\'') => {
+                        self.switch_to(State::AfterAttributeValueQuoted);
+                    }
+                    Some('&') => {
+                        self.set_return_state(State::AttributeValueSingleQuoted);
+                        self.switch_to(State::CharacterReference);
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            if let Some(attribute) = attributes.last_mut() {
+                                attribute.value.push('\u{FFFD}');
+                            }
+                        }
+                    }
+                    eof!() => {
+                        // This is an eof-in-tag parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            if let Some(attribute) = attributes.last_mut() {
+                                attribute.value.push(anything_else);
+                            }
+                        }
+                    }
+                },
+                State::AttributeValueUnquoted => match self.consume_next_input_character() {
+                    whitespace!() => {
+                        self.switch_to(State::BeforeAttributeName);
+                    }
+                    Some('&') => {
+                        self.set_return_state(State::AttributeValueUnquoted);
+                        self.switch_to(State::CharacterReference);
+                    }
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            if let Some(attribute) = attributes.last_mut() {
+                                attribute.value.push('\u{FFFD}');
+                            }
+                        }
+                    }
+                    eof!() => {
+                        // This is an eof-in-tag parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        // U+0022 QUOTATION MARK ("), U+0027 APOSTROPHE ('), U+003C LESS-THAN SIGN
+                        // (<), U+003D EQUALS SIGN (=) and U+0060 GRAVE ACCENT (`) are an
+                        // unexpected-character-in-unquoted-attribute-value parse error, and are
+                        // otherwise treated as per this entry.
+                        if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                            if let Some(attribute) = attributes.last_mut() {
+                                attribute.value.push(anything_else);
+                            }
+                        }
+                    }
+                },
+                State::AfterAttributeValueQuoted => match self.consume_next_input_character() {
+                    whitespace!() => {
+                        self.switch_to(State::BeforeAttributeName);
+                    }
+                    Some('/') => {
+                        self.switch_to(State::SelfClosingStartTag);
+                    }
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-tag parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(_) => {
+                        // This is a missing-whitespace-between-attributes parse error.
+                        self.reconsume_in_state(State::BeforeAttributeName);
+                    }
+                },
+                State::SelfClosingStartTag => match self.consume_next_input_character() {
+                    Some('>') => {
+                        if let Some(Token::Tag { self_closing, .. }) = &mut self.current_token {
+                            *self_closing = true;
+                        }
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-tag parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(_) => {
+                        // This is an unexpected-solidus-in-tag parse error.
+                        self.reconsume_in_state(State::BeforeAttributeName);
+                    }
+                },
+                State::BogusComment => match self.consume_next_input_character() {
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        emit_current_token!();
+                        emit_token!(Token::EndOfFile);
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        self.append_to_comment("\u{FFFD}");
+                    }
+                    Some(anything_else) => {
+                        if let Some(Token::Comment { data }) = &mut self.current_token {
+                            data.push(anything_else);
+                        }
+                    }
+                },
+                State::MarkupDeclarationOpen => {
+                    if self.next_few_input_characters_are("--", false) {
+                        self.consume_word("--");
+                        self.set_current_token(Token::Comment {
+                            data: "".to_string(),
+                        });
+                        self.switch_to(State::CommentStart);
+                    } else if self.next_few_input_characters_are("DOCTYPE", false) {
+                        self.consume_word("DOCTYPE");
+                        self.switch_to(State::Doctype);
+                    } else if self.next_few_input_characters_are("[CDATA[", true) {
+                        self.consume_word("[CDATA[");
+                        // If there is an adjusted current node and it is not an element in
+                        // the HTML namespace, then switch to the CDATA section state.
+                        if self.adjusted_current_node_is_foreign {
+                            self.switch_to(State::CDataSection);
+                        } else {
+                            // Otherwise, this is a cdata-in-html-content parse error.
+                            self.set_current_token(Token::Comment {
+                                data: "[CDATA[".to_string(),
+                            });
+                            self.switch_to(State::BogusComment);
+                        }
+                    } else {
+                        // This is an incorrectly-opened-comment parse error.
+                        self.set_current_token(Token::Comment {
+                            data: "".to_string(),
+                        });
+                        // The next character that is consumed, if any, is the first
+                        // character that will be in the comment.
+                        self.switch_to(State::BogusComment);
+                    }
+                }
+                State::CommentStart => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::CommentStartDash);
+                    }
+                    Some('>') => {
+                        // This is an abrupt-closing-of-empty-comment parse error.
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    _ => {
+                        self.reconsume_in_state(State::Comment);
+                    }
+                },
+                State::CommentStartDash => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::CommentEnd);
+                    }
+                    Some('>') => {
+                        // This is an abrupt-closing-of-empty-comment parse error.
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-comment parse error.
+                        emit_current_token!();
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        self.append_to_comment("-");
+                        self.reconsume_in_state(State::Comment);
+                    }
+                },
+                State::Comment => match self.consume_next_input_character() {
+                    Some('<') => {
+                        self.append_to_comment("<");
+                        self.switch_to(State::CommentLessThanSign);
+                    }
+                    Some('-') => {
+                        self.switch_to(State::CommentEndDash);
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        self.append_to_comment("\u{FFFD}");
+                    }
+                    eof!() => {
+                        // This is an eof-in-comment parse error.
+                        emit_current_token!();
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        self.append_to_comment(anything_else.encode_utf8(&mut [0; 4]));
+                    }
+                },
+                State::CommentLessThanSign => match self.consume_next_input_character() {
+                    Some('!') => {
+                        self.append_to_comment("!");
+                        self.switch_to(State::CommentLessThanSignBang);
+                    }
+                    Some('<') => {
+                        self.append_to_comment("<");
+                    }
+                    _ => {
+                        self.reconsume_in_state(State::Comment);
+                    }
+                },
+                State::CommentLessThanSignBang => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::CommentLessThanSignBangDash);
+                    }
+                    _ => {
+                        self.reconsume_in_state(State::Comment);
+                    }
+                },
+                State::CommentLessThanSignBangDash => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::CommentLessThanSignBangDashDash);
+                    }
+                    _ => {
+                        self.reconsume_in_state(State::CommentEndDash);
+                    }
+                },
+                State::CommentLessThanSignBangDashDash => {
+                    match self.consume_next_input_character() {
+                        Some('>') | eof!() => {
+                            self.reconsume_in_state(State::CommentEnd);
+                        }
+                        _ => {
+                            // This is a nested-comment parse error.
+                            self.reconsume_in_state(State::CommentEnd);
+                        }
+                    }
+                }
+                State::CommentEndDash => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.switch_to(State::CommentEnd);
+                    }
+                    eof!() => {
+                        // This is an eof-in-comment parse error.
+                        emit_current_token!();
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        self.append_to_comment("-");
+                        self.reconsume_in_state(State::Comment);
+                    }
+                },
+                State::CommentEnd => match self.consume_next_input_character() {
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    Some('!') => {
+                        self.switch_to(State::CommentEndBang);
+                    }
+                    Some('-') => {
+                        self.append_to_comment("-");
+                    }
+                    eof!() => {
+                        // This is an eof-in-comment parse error.
+                        emit_current_token!();
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        self.append_to_comment("--");
+                        self.reconsume_in_state(State::Comment);
+                    }
+                },
+                State::CommentEndBang => match self.consume_next_input_character() {
+                    Some('-') => {
+                        self.append_to_comment("--!");
+                        self.switch_to(State::CommentEndDash);
+                    }
+                    Some('>') => {
+                        // This is an incorrectly-closed-comment parse error.
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-comment parse error.
+                        emit_current_token!();
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        self.append_to_comment("--!");
+                        self.reconsume_in_state(State::Comment);
+                    }
+                },
+                State::Doctype => match self.consume_next_input_character() {
+                    whitespace!() => {
+                        self.switch_to(State::BeforeDoctypeName);
+                    }
+                    Some('>') => {
+                        self.reconsume_in_state(State::BeforeDoctypeName);
+                    }
+                    eof!() => {
+                        // This is an eof-in-doctype parse error.
+                        // Create a new DOCTYPE token. Set its force-quirks flag to on.
+                        self.set_current_token(Token::Doctype {
+                            name: String::new(),
+                            public_identifier: None,
+                            system_identifier: None,
+                            force_quirks: true,
+                        });
+                        // Emit the current token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        // This is a missing-whitespace-before-doctype-name parse error.
+                        self.reconsume_in_state(State::BeforeDoctypeName);
+                    }
+                },
+                State::BeforeDoctypeName => match self.consume_next_input_character() {
+                    whitespace!() => {}
+                    ascii_upper_alpha!() => {
+                        self.set_current_token(Token::Doctype {
+                            name: self
+                                .current_input_character()
+                                .unwrap()
+                                .to_ascii_lowercase()
+                                .to_string(),
+                            public_identifier: None,
+                            system_identifier: None,
+                            force_quirks: false,
+                        });
+                        self.switch_to(State::DoctypeName);
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        self.set_current_token(Token::Doctype {
+                            name: '\u{FFFD}'.to_string(),
+                            public_identifier: None,
+                            system_identifier: None,
+                            force_quirks: false,
+                        });
+                        self.switch_to(State::DoctypeName);
+                    }
+                    Some('>') => {
+                        // This is a missing-doctype-name parse error.
+                        // Create a new DOCTYPE token. Set its force-quirks flag to on.
+                        self.set_current_token(Token::Doctype {
+                            name: String::new(),
+                            public_identifier: None,
+                            system_identifier: None,
+                            force_quirks: true,
+                        });
+                        // Switch to the data state.
+                        self.switch_to(State::Data);
+                        // Emit the current token.
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-doctype parse error.
+                        // Create a new DOCTYPE token. Set its force-quirks flag to on.
+                        self.set_current_token(Token::Doctype {
+                            name: String::new(),
+                            public_identifier: None,
+                            system_identifier: None,
+                            force_quirks: true,
+                        });
+                        // Emit the current token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(char) => {
+                        self.set_current_token(Token::Doctype {
+                            name: char.to_string(),
+                            public_identifier: None,
+                            system_identifier: None,
+                            force_quirks: false,
+                        });
+                        self.switch_to(State::DoctypeName);
+                    }
+                },
+                State::DoctypeName => match self.consume_next_input_character() {
+                    whitespace!() => {
+                        self.switch_to(State::AfterDoctypeName);
+                    }
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    ascii_upper_alpha!() => {
+                        let char = self.current_input_character().unwrap();
+                        if let Some(Token::Doctype { name, .. }) = &mut self.current_token {
+                            name.push(char.to_ascii_lowercase());
+                        }
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error.
+                        if let Some(Token::Doctype { name, .. }) = &mut self.current_token {
+                            name.push('\u{FFFD}');
+                        }
+                    }
+                    eof!() => {
+                        // This is an eof-in-doctype parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        // Emit the current DOCTYPE token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(char) => {
+                        if let Some(Token::Doctype { name, .. }) = &mut self.current_token {
+                            name.push(char);
+                        }
+                    }
+                },
+                State::AfterDoctypeName => match self.consume_next_input_character() {
+                    whitespace!() => {}
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-doctype parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        // Emit the current DOCTYPE token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        // NOTE: The character was already consumed, but the
+                        //       checks below start at the current input
+                        //       character.
+                        self.unconsume_current_input_character();
+
+                        // If the six characters starting from the current input
+                        // character are an ASCII case-insensitive match for the
+                        // word "PUBLIC", then consume those characters and
+                        // switch to the after DOCTYPE public keyword state.
+                        if self.next_few_input_characters_are("PUBLIC", false) {
+                            self.consume_word("PUBLIC");
+                            self.switch_to(State::AfterDoctypePublicKeyword);
+                        }
+                        // Otherwise, if the six characters starting from the
+                        // current input character are an ASCII case-insensitive
+                        // match for the word "SYSTEM", then consume those
+                        // characters and switch to the after DOCTYPE system
+                        // keyword state.
+                        else if self.next_few_input_characters_are("SYSTEM", false) {
+                            self.consume_word("SYSTEM");
+                            self.switch_to(State::AfterDoctypeSystemKeyword);
+                        }
+                        // Otherwise, this is an
+                        // invalid-character-sequence-after-doctype-name parse
+                        // error.
+                        else {
+                            // Set the current DOCTYPE token's force-quirks flag
+                            // to on.
+                            self.set_doctype_force_quirks();
+                            // Reconsume in the bogus DOCTYPE state.
+                            self.switch_to(State::BogusDoctype);
+                        }
+                    }
+                },
+                State::AfterDoctypePublicKeyword => match self.consume_next_input_character() {
+                    whitespace!() => {
+                        self.switch_to(State::BeforeDoctypePublicIdentifier);
+                    }
+                    Some('"') => {
+                        // This is a missing-whitespace-after-doctype-public-keyword parse error.
+                        self.set_doctype_public_identifier("");
+                        self.switch_to(State::DoctypePublicIdentifierDoubleQuoted);
+                    }
+                    Some('\'') => {
+                        // This is a missing-whitespace-after-doctype-public-keyword parse error.
+                        self.set_doctype_public_identifier("");
+                        self.switch_to(State::DoctypePublicIdentifierSingleQuoted);
+                    }
+                    Some('>') => {
+                        // This is a missing-doctype-public-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-doctype parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        // Emit the current DOCTYPE token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        // This is a missing-quote-before-doctype-public-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        self.reconsume_in_state(State::BogusDoctype);
+                    }
+                },
+                State::BeforeDoctypePublicIdentifier => match self.consume_next_input_character() {
+                    whitespace!() => {}
+                    Some('"') => {
+                        self.set_doctype_public_identifier("");
+                        self.switch_to(State::DoctypePublicIdentifierDoubleQuoted);
+                    }
+                    Some('\'') => {
+                        self.set_doctype_public_identifier("");
+                        self.switch_to(State::DoctypePublicIdentifierSingleQuoted);
+                    }
+                    Some('>') => {
+                        // This is a missing-doctype-public-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-doctype parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        // Emit the current DOCTYPE token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        // This is a missing-quote-before-doctype-public-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        self.reconsume_in_state(State::BogusDoctype);
                     }
                 },
-                State::DoctypeName => match self.consume_next_input_character() {
+                State::DoctypePublicIdentifierDoubleQuoted => {
+                    match self.consume_next_input_character() {
+                        Some('"') => {
+                            self.switch_to(State::AfterDoctypePublicIdentifier);
+                        }
+                        null!() => {
+                            // This is an unexpected-null-character parse error.
+                            self.append_to_doctype_public_identifier('\u{FFFD}');
+                        }
+                        Some('>') => {
+                            // This is an abrupt-doctype-public-identifier parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            self.switch_to(State::Data);
+                            emit_current_token!();
+                        }
+                        eof!() => {
+                            // This is an eof-in-doctype parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            // Emit the current DOCTYPE token.
+                            emit_current_token!();
+                            // Emit an end-of-file token.
+                            emit_token!(Token::EndOfFile);
+                        }
+                        Some(char) => {
+                            self.append_to_doctype_public_identifier(char);
+                        }
+                    }
+                }
+                State::DoctypePublicIdentifierSingleQuoted => {
+                    match self.consume_next_input_character() {
+                        Some('\'') => {
+                            self.switch_to(State::AfterDoctypePublicIdentifier);
+                        }
+                        null!() => {
+                            // This is an unexpected-null-character parse error.
+                            self.append_to_doctype_public_identifier('\u{FFFD}');
+                        }
+                        Some('>') => {
+                            // This is an abrupt-doctype-public-identifier parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            self.switch_to(State::Data);
+                            emit_current_token!();
+                        }
+                        eof!() => {
+                            // This is an eof-in-doctype parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            // Emit the current DOCTYPE token.
+                            emit_current_token!();
+                            // Emit an end-of-file token.
+                            emit_token!(Token::EndOfFile);
+                        }
+                        Some(char) => {
+                            self.append_to_doctype_public_identifier(char);
+                        }
+                    }
+                }
+                State::AfterDoctypePublicIdentifier => match self.consume_next_input_character() {
                     whitespace!() => {
-                        self.switch_to(State::AfterDoctypeName);
+                        self.switch_to(State::BetweenDoctypePublicAndSystemIdentifiers);
                     }
                     Some('>') => {
                         self.switch_to(State::Data);
                         emit_current_token!();
                     }
-                    ascii_upper_alpha!() => {
-                        let char = self.current_input_character().unwrap();
-                        if let Some(Token::Doctype { name, .. }) = &mut self.current_token {
-                            name.push(char.to_ascii_lowercase());
-                        }
+                    Some('"') => {
+                        // This is a missing-whitespace-between-doctype-public-and-system-identifiers
+                        // parse error.
+                        self.set_doctype_system_identifier("");
+                        self.switch_to(State::DoctypeSystemIdentifierDoubleQuoted);
                     }
-                    null!() => {
-                        todo!("This is an unexpected-null-character parse error. Append a U+FFFD REPLACEMENT CHARACTER character to the current DOCTYPE token's name.");
+                    Some('\'') => {
+                        // This is a missing-whitespace-between-doctype-public-and-system-identifiers
+                        // parse error.
+                        self.set_doctype_system_identifier("");
+                        self.switch_to(State::DoctypeSystemIdentifierSingleQuoted);
                     }
                     eof!() => {
-                        todo!("This is an eof-in-doctype parse error. Set the current DOCTYPE token's force-quirks flag to on. Emit the current DOCTYPE token. Emit an end-of-file token.");
+                        // This is an eof-in-doctype parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        // Emit the current DOCTYPE token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
                     }
-                    Some(char) => {
-                        if let Some(Token::Doctype { name, .. }) = &mut self.current_token {
-                            name.push(char);
+                    _ => {
+                        // This is a missing-quote-before-doctype-system-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        self.reconsume_in_state(State::BogusDoctype);
+                    }
+                },
+                State::BetweenDoctypePublicAndSystemIdentifiers => {
+                    match self.consume_next_input_character() {
+                        whitespace!() => {}
+                        Some('>') => {
+                            self.switch_to(State::Data);
+                            emit_current_token!();
+                        }
+                        Some('"') => {
+                            self.set_doctype_system_identifier("");
+                            self.switch_to(State::DoctypeSystemIdentifierDoubleQuoted);
+                        }
+                        Some('\'') => {
+                            self.set_doctype_system_identifier("");
+                            self.switch_to(State::DoctypeSystemIdentifierSingleQuoted);
                         }
+                        eof!() => {
+                            // This is an eof-in-doctype parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            // Emit the current DOCTYPE token.
+                            emit_current_token!();
+                            // Emit an end-of-file token.
+                            emit_token!(Token::EndOfFile);
+                        }
+                        _ => {
+                            // This is a missing-quote-before-doctype-system-identifier parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            self.reconsume_in_state(State::BogusDoctype);
+                        }
+                    }
+                }
+                State::AfterDoctypeSystemKeyword => match self.consume_next_input_character() {
+                    whitespace!() => {
+                        self.switch_to(State::BeforeDoctypeSystemIdentifier);
+                    }
+                    Some('"') => {
+                        // This is a missing-whitespace-after-doctype-system-keyword parse error.
+                        self.set_doctype_system_identifier("");
+                        self.switch_to(State::DoctypeSystemIdentifierDoubleQuoted);
+                    }
+                    Some('\'') => {
+                        // This is a missing-whitespace-after-doctype-system-keyword parse error.
+                        self.set_doctype_system_identifier("");
+                        self.switch_to(State::DoctypeSystemIdentifierSingleQuoted);
+                    }
+                    Some('>') => {
+                        // This is a missing-doctype-system-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-doctype parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        // Emit the current DOCTYPE token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        // This is a missing-quote-before-doctype-system-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        self.reconsume_in_state(State::BogusDoctype);
                     }
                 },
-                State::AfterDoctypeName => match self.consume_next_input_character() {
+                State::BeforeDoctypeSystemIdentifier => match self.consume_next_input_character() {
                     whitespace!() => {}
+                    Some('"') => {
+                        self.set_doctype_system_identifier("");
+                        self.switch_to(State::DoctypeSystemIdentifierDoubleQuoted);
+                    }
+                    Some('\'') => {
+                        self.set_doctype_system_identifier("");
+                        self.switch_to(State::DoctypeSystemIdentifierSingleQuoted);
+                    }
                     Some('>') => {
+                        // This is a missing-doctype-system-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
                         self.switch_to(State::Data);
                         emit_current_token!();
                     }
                     eof!() => {
-                        todo!("This is an eof-in-doctype parse error. Set the current DOCTYPE token's force-quirks flag to on. Emit the current DOCTYPE token. Emit an end-of-file token.");
+                        // This is an eof-in-doctype parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        // Emit the current DOCTYPE token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
                     }
                     _ => {
-                        todo!();
+                        // This is a missing-quote-before-doctype-system-identifier parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        self.reconsume_in_state(State::BogusDoctype);
                     }
                 },
-                State::AfterDoctypePublicKeyword => todo!("AfterDoctypePublicKeyword"),
-                State::BeforeDoctypePublicIdentifier => todo!("BeforeDoctypePublicIdentifier"),
-                State::DoctypePublicIdentifierDoubleQuoted => {
-                    todo!("DoctypePublicIdentifierDoubleQuoted")
+                State::DoctypeSystemIdentifierDoubleQuoted => {
+                    match self.consume_next_input_character() {
+                        Some('"') => {
+                            self.switch_to(State::AfterDoctypeSystemIdentifier);
+                        }
+                        null!() => {
+                            // This is an unexpected-null-character parse error.
+                            self.append_to_doctype_system_identifier('\u{FFFD}');
+                        }
+                        Some('>') => {
+                            // This is an abrupt-doctype-system-identifier parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            self.switch_to(State::Data);
+                            emit_current_token!();
+                        }
+                        eof!() => {
+                            // This is an eof-in-doctype parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            // Emit the current DOCTYPE token.
+                            emit_current_token!();
+                            // Emit an end-of-file token.
+                            emit_token!(Token::EndOfFile);
+                        }
+                        Some(char) => {
+                            self.append_to_doctype_system_identifier(char);
+                        }
+                    }
                 }
-                State::DoctypePublicIdentifierSingleQuoted => {
-                    todo!("DoctypePublicIdentifierSingleQuoted")
+                State::DoctypeSystemIdentifierSingleQuoted => {
+                    match self.consume_next_input_character() {
+                        Some('\'') => {
+                            self.switch_to(State::AfterDoctypeSystemIdentifier);
+                        }
+                        null!() => {
+                            // This is an unexpected-null-character parse error.
+                            self.append_to_doctype_system_identifier('\u{FFFD}');
+                        }
+                        Some('>') => {
+                            // This is an abrupt-doctype-system-identifier parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            self.switch_to(State::Data);
+                            emit_current_token!();
+                        }
+                        eof!() => {
+                            // This is an eof-in-doctype parse error.
+                            // Set the current DOCTYPE token's force-quirks flag to on.
+                            self.set_doctype_force_quirks();
+                            // Emit the current DOCTYPE token.
+                            emit_current_token!();
+                            // Emit an end-of-file token.
+                            emit_token!(Token::EndOfFile);
+                        }
+                        Some(char) => {
+                            self.append_to_doctype_system_identifier(char);
+                        }
+                    }
                 }
-                State::AfterDoctypePublicIdentifier => todo!("AfterDoctypePublicIdentifier"),
-                State::BetweenDoctypePublicAndSystemIdentifiers => {
-                    todo!("BetweenDoctypePublicAndSystemIdentifiers")
+                State::AfterDoctypeSystemIdentifier => match self.consume_next_input_character() {
+                    whitespace!() => {}
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    eof!() => {
+                        // This is an eof-in-doctype parse error.
+                        // Set the current DOCTYPE token's force-quirks flag to on.
+                        self.set_doctype_force_quirks();
+                        // Emit the current DOCTYPE token.
+                        emit_current_token!();
+                        // Emit an end-of-file token.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        // This is an unexpected-character-after-doctype-system-identifier parse error.
+                        // (This does not set the current DOCTYPE token's force-quirks flag to on.)
+                        self.reconsume_in_state(State::BogusDoctype);
+                    }
+                },
+                State::BogusDoctype => match self.consume_next_input_character() {
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                        emit_current_token!();
+                    }
+                    null!() => {
+                        // This is an unexpected-null-character parse error. Ignore the character.
+                    }
+                    eof!() => {
+                        emit_current_token!();
+                        emit_token!(Token::EndOfFile);
+                    }
+                    _ => {
+                        // Ignore the character.
+                    }
+                },
+                State::CDataSection => match self.consume_next_input_character() {
+                    Some(']') => {
+                        self.switch_to(State::CDataSectionBracket);
+                    }
+                    eof!() => {
+                        // This is an eof-in-cdata parse error.
+                        emit_token!(Token::EndOfFile);
+                    }
+                    Some(anything_else) => {
+                        // U+0000 NULL characters are handled in the tree construction stage,
+                        // as part of the in foreign content insertion mode, which is the
+                        // only place where CDATA sections can appear.
+                        emit_token!(Token::Character(anything_else));
+                    }
+                },
+                State::CDataSectionBracket => match self.consume_next_input_character() {
+                    Some(']') => {
+                        self.switch_to(State::CDataSectionEnd);
+                    }
+                    _ => {
+                        emit_token!(Token::Character(']'));
+                        self.reconsume_in_state(State::CDataSection);
+                    }
+                },
+                State::CDataSectionEnd => match self.consume_next_input_character() {
+                    Some(']') => {
+                        emit_token!(Token::Character(']'));
+                    }
+                    Some('>') => {
+                        self.switch_to(State::Data);
+                    }
+                    _ => {
+                        emit_token!(Token::Character(']'));
+                        emit_token!(Token::Character(']'));
+                        self.reconsume_in_state(State::CDataSection);
+                    }
+                },
+                State::CharacterReference => {
+                    self.temporary_buffer = "&".to_string();
+                    match self.consume_next_input_character() {
+                        Some('0'..='9') | ascii_alpha!() => {
+                            self.reconsume_in_state(State::NamedCharacterReference);
+                        }
+                        Some('#') => {
+                            self.temporary_buffer.push('#');
+                            self.switch_to(State::NumericCharacterReference);
+                        }
+                        _ => {
+                            self.flush_code_points_consumed_as_character_reference();
+                            self.reconsume_in_state(self.return_state);
+                        }
+                    }
                 }
-                State::AfterDoctypeSystemKeyword => todo!("AfterDoctypeSystemKeyword"),
-                State::BeforeDoctypeSystemIdentifier => todo!("BeforeDoctypeSystemIdentifier"),
-                State::DoctypeSystemIdentifierDoubleQuoted => {
-                    todo!("DoctypeSystemIdentifierDoubleQuoted")
+                State::NamedCharacterReference => {
+                    // Consume the maximum number of characters possible, where the
+                    // consumed characters are one of the identifiers in the named
+                    // character references table.
+                    let remaining_input = &self.html[self.insertion_point..];
+                    match entities::longest_named_character_reference(remaining_input) {
+                        Some((name, characters)) => {
+                            self.consume_word(name);
+                            self.temporary_buffer.push_str(name);
+
+                            let next_input_character =
+                                self.html[self.insertion_point..].chars().next();
+
+                            // If the character reference was consumed as part of an
+                            // attribute, and the last character matched is not a
+                            // U+003B SEMICOLON character (;), and the next input character
+                            // is either a U+003D EQUALS SIGN character (=) or an ASCII
+                            // alphanumeric, then, for historical reasons, flush code points
+                            // consumed as a character reference and switch to the return
+                            // state.
+                            if self.is_consumed_as_part_of_an_attribute()
+                                && !name.ends_with(';')
+                                && matches!(
+                                    next_input_character,
+                                    Some('=' | '0'..='9') | ascii_alpha!()
+                                )
+                            {
+                                self.flush_code_points_consumed_as_character_reference();
+                                self.switch_to(self.return_state);
+                            } else {
+                                // If the last character matched is not a U+003B SEMICOLON
+                                // character (;), this is a
+                                // missing-semicolon-after-character-reference parse error.
+                                self.temporary_buffer = characters.to_string();
+                                self.flush_code_points_consumed_as_character_reference();
+                                self.switch_to(self.return_state);
+                            }
+                        }
+                        None => {
+                            self.flush_code_points_consumed_as_character_reference();
+                            self.switch_to(State::AmbiguousAmpersand);
+                        }
+                    }
                 }
-                State::DoctypeSystemIdentifierSingleQuoted => {
-                    todo!("DoctypeSystemIdentifierSingleQuoted")
+                State::AmbiguousAmpersand => match self.consume_next_input_character() {
+                    Some(character @ ('0'..='9' | 'a'..='z' | 'A'..='Z')) => {
+                        if self.is_consumed_as_part_of_an_attribute() {
+                            if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                                if let Some(attribute) = attributes.last_mut() {
+                                    attribute.value.push(character);
+                                }
+                            }
+                        } else {
+                            emit_token!(Token::Character(character));
+                        }
+                    }
+                    Some(';') => {
+                        // This is an unknown-named-character-reference parse error.
+                        self.reconsume_in_state(self.return_state);
+                    }
+                    _ => {
+                        self.reconsume_in_state(self.return_state);
+                    }
+                },
+                State::NumericCharacterReference => {
+                    self.character_reference_code = 0;
+                    match self.consume_next_input_character() {
+                        Some(character @ ('x' | 'X')) => {
+                            self.temporary_buffer.push(character);
+                            self.switch_to(State::HexadecimalCharacterReferenceStart);
+                        }
+                        _ => {
+                            self.reconsume_in_state(State::DecimalCharacterReferenceStart);
+                        }
+                    }
                 }
-                State::AfterDoctypeSystemIdentifier => todo!("AfterDoctypeSystemIdentifier"),
-                State::BogusDoctype => todo!("BogusDoctype"),
-                State::CDataSection => todo!("CDataSection"),
-                State::CDataSectionBracket => todo!("CDataSectionBracket"),
-                State::CDataSectionEnd => todo!("CDataSectionEnd"),
-                State::CharacterReference => todo!("CharacterReference"),
-                State::NamedCharacterReference => todo!("NamedCharacterReference"),
-                State::AmbiguousAmpersand => todo!("AmbiguousAmpersand"),
-                State::NumericCharacterReference => todo!("NumericCharacterReference"),
                 State::HexadecimalCharacterReferenceStart => {
-                    todo!("HexadecimalCharacterReferenceStart")
+                    match self.consume_next_input_character() {
+                        Some('0'..='9' | 'a'..='f' | 'A'..='F') => {
+                            self.reconsume_in_state(State::HexadecimalCharacterReference);
+                        }
+                        _ => {
+                            // This is an absence-of-digits-in-numeric-character-reference
+                            // parse error.
+                            self.flush_code_points_consumed_as_character_reference();
+                            self.reconsume_in_state(self.return_state);
+                        }
+                    }
+                }
+                State::DecimalCharacterReferenceStart => {
+                    match self.consume_next_input_character() {
+                        Some('0'..='9') => {
+                            self.reconsume_in_state(State::DecimalCharacterReference);
+                        }
+                        _ => {
+                            // This is an absence-of-digits-in-numeric-character-reference parse
+                            // error.
+                            self.flush_code_points_consumed_as_character_reference();
+                            self.reconsume_in_state(self.return_state);
+                        }
+                    }
+                }
+                State::HexadecimalCharacterReference => match self.consume_next_input_character() {
+                    Some(digit @ ('0'..='9' | 'a'..='f' | 'A'..='F')) => {
+                        self.add_digit_to_character_reference_code(digit, 16);
+                    }
+                    Some(';') => {
+                        self.switch_to(State::NumericCharacterReferenceEnd);
+                    }
+                    _ => {
+                        // This is a missing-semicolon-after-character-reference parse error.
+                        self.reconsume_in_state(State::NumericCharacterReferenceEnd);
+                    }
+                },
+                State::DecimalCharacterReference => match self.consume_next_input_character() {
+                    Some(digit @ '0'..='9') => {
+                        self.add_digit_to_character_reference_code(digit, 10);
+                    }
+                    Some(';') => {
+                        self.switch_to(State::NumericCharacterReferenceEnd);
+                    }
+                    _ => {
+                        // This is a missing-semicolon-after-character-reference parse error.
+                        self.reconsume_in_state(State::NumericCharacterReferenceEnd);
+                    }
+                },
+                State::NumericCharacterReferenceEnd => {
+                    // Check the character reference code. A code of 0x00, one outside
+                    // of the range of Unicode or one that is a surrogate is a parse
+                    // error, and is replaced by U+FFFD REPLACEMENT CHARACTER. Codes in
+                    // the C1 control range are replaced using the table of the
+                    // windows-1252 encoding.
+                    let code = self.character_reference_code;
+                    let character = C1_CONTROL_REPLACEMENTS
+                        .iter()
+                        .find(|(number, _)| *number == code)
+                        .map(|(_, replacement)| *replacement)
+                        .or_else(|| char::from_u32(code).filter(|_| code != 0))
+                        .unwrap_or('\u{FFFD}');
+
+                    self.temporary_buffer = character.to_string();
+                    self.flush_code_points_consumed_as_character_reference();
+                    self.switch_to(self.return_state);
                 }
-                State::DecimalCharacterReferenceStart => todo!("DecimalCharacterReferenceStart"),
-                State::HexadecimalCharacterReference => todo!("HexadecimalCharacterReference"),
-                State::DecimalCharacterReference => todo!("DecimalCharacterReference"),
-                State::NumericCharacterReferenceEnd => todo!("NumericCharacterReferenceEnd"),
             }
         }
 
-        self.tokens.append(&mut emitted_tokens);
-
-        self.peek().cloned()
+        self.emitted_tokens.pop_front()
     }
 
+    /// https://html.spec.whatwg.org/multipage/parsing.html#current-input-character
+    ///
+    /// The current input character is the last character to have been
+    /// consumed.
     fn current_input_character(&self) -> Option<char> {
-        self.html.chars().nth(self.insertion_point)
-    }
-
-    fn next_input_character(&mut self) -> Option<char> {
-        self.html.chars().nth(self.insertion_point + 1)
+        self.current_input_character
     }
 
     fn next_few_input_characters_are(&self, word: &str, case_sensitive: bool) -> bool {
-        self.html[self.insertion_point..]
-            .chars()
-            .zip(word.chars())
-            .all(|(a, b)| {
+        let mut input = self.html[self.insertion_point..].chars();
+        word.chars().all(|b| {
+            input.next().is_some_and(|a| {
                 if case_sensitive {
                     a == b
                 } else {
                     a.eq_ignore_ascii_case(&b)
                 }
             })
+        })
     }
 
     pub fn switch_to(&mut self, state: State) {
@@ -809,30 +2153,267 @@ impl<'input> Tokenizer<'input> {
     }
 
     fn reconsume_in_state(&mut self, state: State) {
-        self.insertion_point -= 1;
+        self.unconsume_current_input_character();
         self.switch_to(state);
     }
 
+    /// Moves the insertion point back before the current input character, so
+    /// it is consumed again by the next state.
+    fn unconsume_current_input_character(&mut self) {
+        self.insertion_point -= self.current_input_character.map_or(0, char::len_utf8);
+    }
+
     fn set_current_token(&mut self, token: Token) {
         self.current_token = Some(token);
     }
 
     fn consume_next_input_character(&mut self) -> Option<char> {
-        let char = self.current_input_character();
-        self.insertion_point += 1;
+        let char = self.html[self.insertion_point..].chars().next();
+        self.insertion_point += char.map_or(0, char::len_utf8);
+        self.current_input_character = char;
         char
     }
 
+    fn append_to_comment(&mut self, string: &str) {
+        if let Some(Token::Comment { data }) = &mut self.current_token {
+            data.push_str(string);
+        }
+    }
+
+    fn set_doctype_force_quirks(&mut self) {
+        if let Some(Token::Doctype { force_quirks, .. }) = &mut self.current_token {
+            *force_quirks = true;
+        }
+    }
+
+    fn set_doctype_public_identifier(&mut self, identifier: &str) {
+        if let Some(Token::Doctype {
+            public_identifier, ..
+        }) = &mut self.current_token
+        {
+            *public_identifier = Some(identifier.to_string());
+        }
+    }
+
+    fn append_to_doctype_public_identifier(&mut self, char: char) {
+        if let Some(Token::Doctype {
+            public_identifier: Some(public_identifier),
+            ..
+        }) = &mut self.current_token
+        {
+            public_identifier.push(char);
+        }
+    }
+
+    fn set_doctype_system_identifier(&mut self, identifier: &str) {
+        if let Some(Token::Doctype {
+            system_identifier, ..
+        }) = &mut self.current_token
+        {
+            *system_identifier = Some(identifier.to_string());
+        }
+    }
+
+    fn append_to_doctype_system_identifier(&mut self, char: char) {
+        if let Some(Token::Doctype {
+            system_identifier: Some(system_identifier),
+            ..
+        }) = &mut self.current_token
+        {
+            system_identifier.push(char);
+        }
+    }
+
     fn consume_word(&mut self, word: &str) {
         self.insertion_point += word.len();
     }
 
+    /// Tells the tokenizer whether the adjusted current node of the tree
+    /// construction stage is an element outside of the HTML namespace, which
+    /// decides if `<![CDATA[` starts a CDATA section or a bogus comment.
+    pub fn set_adjusted_current_node_is_foreign(&mut self, foreign: bool) {
+        self.adjusted_current_node_is_foreign = foreign;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#charref-in-attribute
+    fn is_consumed_as_part_of_an_attribute(&self) -> bool {
+        matches!(
+            self.return_state,
+            State::AttributeValueDoubleQuoted
+                | State::AttributeValueSingleQuoted
+                | State::AttributeValueUnquoted
+        )
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#flush-code-points-consumed-as-a-character-reference
+    fn flush_code_points_consumed_as_character_reference(&mut self) {
+        let temporary_buffer = std::mem::take(&mut self.temporary_buffer);
+        if self.is_consumed_as_part_of_an_attribute() {
+            if let Some(Token::Tag { attributes, .. }) = &mut self.current_token {
+                if let Some(attribute) = attributes.last_mut() {
+                    attribute.value.push_str(&temporary_buffer);
+                }
+            }
+        } else {
+            self.emitted_tokens
+                .extend(temporary_buffer.chars().map(Token::Character));
+        }
+    }
+
+    /// Multiplies the character reference code by `radix` and adds the
+    /// numeric version of `digit` to it. Codes that no longer fit are kept
+    /// above 0x10FFFF, so they are still replaced in the numeric character
+    /// reference end state.
+    fn add_digit_to_character_reference_code(&mut self, digit: char, radix: u32) {
+        let value = digit.to_digit(radix).unwrap_or_default();
+        self.character_reference_code = self
+            .character_reference_code
+            .saturating_mul(radix)
+            .saturating_add(value);
+    }
+
     /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
     fn current_end_tag_token_is_appropriate(&self) -> bool {
-        // TODO: An appropriate end tag token is an end tag token whose tag name
+        // An appropriate end tag token is an end tag token whose tag name
         // matches the tag name of the last start tag to have been emitted from
         // this tokenizer, if any. If no start tag has been emitted from this
         // tokenizer, then no end tag token is appropriate.
-        true
+        match (&self.current_token, &self.last_start_tag_name) {
+            (
+                Some(Token::Tag {
+                    start: false,
+                    tag_name,
+                    ..
+                }),
+                Some(last_start_tag_name),
+            ) => tag_name == last_start_tag_name,
+            _ => false,
+        }
+    }
+}
+
+/// When the user agent leaves the attribute name state (and before emitting
+/// the tag token, if appropriate), the complete attribute's name must be
+/// compared to the other attributes on the same token; if there is already an
+/// attribute on the token with the exact same name, then this is a
+/// duplicate-attribute parse error and the new attribute must be removed from
+/// the token.
+///
+/// The remaining attributes keep the order in which they were authored.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#attribute-name-state
+fn remove_duplicate_attributes(token: &mut Token) {
+    if let Token::Tag { attributes, .. } = token {
+        let mut index = 0;
+        while index < attributes.len() {
+            let name = &attributes[index].name;
+            if attributes[..index].iter().any(|other| &other.name == name) {
+                attributes.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
+
+/// Replacements for character reference codes in the C1 control range, taken
+/// from the windows-1252 encoding.
+///
+/// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+static C1_CONTROL_REPLACEMENTS: &[(u32, char)] = &[
+    (0x80, '\u{20AC}'),
+    (0x82, '\u{201A}'),
+    (0x83, '\u{0192}'),
+    (0x84, '\u{201E}'),
+    (0x85, '\u{2026}'),
+    (0x86, '\u{2020}'),
+    (0x87, '\u{2021}'),
+    (0x88, '\u{02C6}'),
+    (0x89, '\u{2030}'),
+    (0x8A, '\u{0160}'),
+    (0x8B, '\u{2039}'),
+    (0x8C, '\u{0152}'),
+    (0x8E, '\u{017D}'),
+    (0x91, '\u{2018}'),
+    (0x92, '\u{2019}'),
+    (0x93, '\u{201C}'),
+    (0x94, '\u{201D}'),
+    (0x95, '\u{2022}'),
+    (0x96, '\u{2013}'),
+    (0x97, '\u{2014}'),
+    (0x98, '\u{02DC}'),
+    (0x99, '\u{2122}'),
+    (0x9A, '\u{0161}'),
+    (0x9B, '\u{203A}'),
+    (0x9C, '\u{0153}'),
+    (0x9E, '\u{017E}'),
+    (0x9F, '\u{0178}'),
+];
+
+#[cfg(test)]
+mod tests {
+    fn tokens(html: &str) -> Vec<String> {
+        crate::tokenize(html)
+            .map(|token| token.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn display_renders_tokens_compactly() {
+        assert_eq!(
+            tokens("<p>hi</p>"),
+            [
+                "StartTag(p, [])",
+                "Char('h')",
+                "Char('i')",
+                "EndTag(p, [])",
+                "EOF"
+            ]
+        );
+    }
+
+    #[test]
+    fn display_renders_attributes_and_flags() {
+        assert_eq!(
+            tokens("<div class=x id='y'><br/>"),
+            [
+                r#"StartTag(div, [class="x", id="y"])"#,
+                "StartTag(br, [], self-closing)",
+                "EOF"
+            ]
+        );
+        assert_eq!(
+            tokens("<!-- c --><!DOCTYPE html>"),
+            [r#"Comment(" c ")"#, "Doctype(html)", "EOF"]
+        );
+    }
+
+    #[test]
+    fn malformed_doctypes_force_quirks() {
+        let force_quirks = |html: &str| match crate::tokenize(html).next() {
+            Some(super::Token::Doctype { force_quirks, .. }) => force_quirks,
+            token => panic!("expected a DOCTYPE, got {token:?}"),
+        };
+        assert!(!force_quirks("<!DOCTYPE html>"));
+        assert!(force_quirks("<!DOCTYPE>"));
+        assert!(force_quirks("<!DOCTYPE html"));
+        assert!(force_quirks("<!DOCTYPE html bogus>"));
+    }
+
+    #[test]
+    fn long_text_becomes_a_single_text_node() {
+        let text = "lorem ipsum dolor sit amet ".repeat(100 * 1024 / 27);
+        let dom = crate::Dom::parse_owned(&format!("<p>{text}</p>"));
+        let arena = dom.arena();
+        let p = arena.elements_by_tag(dom.document(), "p").next().unwrap();
+
+        let [child] = arena.get_node(p).children() else {
+            panic!("expected a single text node");
+        };
+        assert!(matches!(
+            arena.get_node(*child).kind,
+            crate::node::NodeKind::Text { .. }
+        ));
+        assert_eq!(arena.text_content(p), text);
     }
 }
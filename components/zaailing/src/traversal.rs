@@ -0,0 +1,509 @@
+use crate::arena::{NodeArena, NodeId};
+use crate::node::{DocumentMode, Node, NodeKind};
+use crate::parser::Namespace;
+
+/// Callbacks for [NodeArena::visit].
+///
+/// `enter` is called for a node before any of its descendants (pre-order) and
+/// `leave` after all of them (post-order), so every `enter` is paired with
+/// exactly one `leave`.
+pub trait Visitor {
+    fn enter(&mut self, _arena: &NodeArena, _node: NodeId) {}
+
+    fn leave(&mut self, _arena: &NodeArena, _node: NodeId) {}
+}
+
+enum VisitStep {
+    Enter(NodeId),
+    Leave(NodeId),
+}
+
+impl NodeArena {
+    /// Walks `root` and its descendants in tree order, calling the hooks of
+    /// `visitor` for each node.
+    pub fn visit(&self, root: NodeId, visitor: &mut impl Visitor) {
+        let mut steps = vec![VisitStep::Enter(root)];
+
+        while let Some(step) = steps.pop() {
+            match step {
+                VisitStep::Enter(node) => {
+                    visitor.enter(self, node);
+                    steps.push(VisitStep::Leave(node));
+                    steps.extend(
+                        self.get_node(node)
+                            .children()
+                            .iter()
+                            .rev()
+                            .map(|child| VisitStep::Enter(*child)),
+                    );
+                }
+                VisitStep::Leave(node) => visitor.leave(self, node),
+            }
+        }
+    }
+}
+
+/// An iterator over the descendants of a node in tree order, created by
+/// [NodeArena::descendants]. Nodes are only visited when the iterator is
+/// advanced.
+pub struct Descendants<'arena> {
+    arena: &'arena NodeArena,
+    stack: Vec<NodeId>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let node = self.stack.pop()?;
+        self.stack
+            .extend(self.arena.get_node(node).children().iter().rev());
+        Some(node)
+    }
+}
+
+impl NodeArena {
+    /// Returns the descendants of `root` in tree order, not including `root`
+    /// itself.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-tree-descendant
+    pub fn descendants(&self, root: NodeId) -> Descendants<'_> {
+        Descendants {
+            arena: self,
+            stack: self
+                .get_node(root)
+                .children()
+                .iter()
+                .rev()
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Returns the descendants of `root` for which `predicate` returns
+    /// `true`, in tree order. Like [NodeArena::descendants], the nodes are
+    /// only visited when the iterator is advanced.
+    pub fn filter_descendants<'a>(
+        &'a self,
+        root: NodeId,
+        predicate: impl Fn(&Node) -> bool + 'a,
+    ) -> impl Iterator<Item = NodeId> + 'a {
+        self.descendants(root)
+            .filter(move |node| predicate(self.get_node(*node)))
+    }
+
+    /// Returns the descendant elements of `root` with the qualified name
+    /// `tag`, like `getElementsByTagName`. HTML elements are matched
+    /// case-insensitively, and `*` matches all elements.
+    ///
+    /// The elements are found lazily, so taking only the first few matches
+    /// does not traverse the rest of the tree.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-getelementsbytagname
+    pub fn elements_by_tag<'a>(
+        &'a self,
+        root: NodeId,
+        tag: &'a str,
+    ) -> impl Iterator<Item = NodeId> + 'a {
+        let lowercase_tag = tag.to_ascii_lowercase();
+
+        self.filter_descendants(root, move |node| {
+            if !node.is_element() {
+                return false;
+            }
+
+            // If qualifiedName is "*", return a HTMLCollection rooted at root,
+            // whose filter matches only descendant elements.
+            if tag == "*" {
+                return true;
+            }
+
+            // Otherwise, return a HTMLCollection rooted at root, whose filter
+            // matches descendant elements:
+            // * whose namespace is the HTML namespace and whose qualified name
+            //   is qualifiedName, in ASCII lowercase.
+            // * whose namespace is not the HTML namespace and whose qualified
+            //   name is qualifiedName.
+            if node.is_element_in_namespace(Namespace::Html) {
+                node.qualified_name() == lowercase_tag
+            } else {
+                node.qualified_name() == tag
+            }
+        })
+    }
+
+    /// Returns the descendant elements of `root` that have all of the classes
+    /// in the whitespace-separated `class_names`, in tree order, like
+    /// `getElementsByClassName`. Classes are matched case-sensitively, except
+    /// in quirks mode documents where they are matched ASCII
+    /// case-insensitively.
+    ///
+    /// https://dom.spec.whatwg.org/#concept-getelementsbyclassname
+    pub fn get_elements_by_class_name(&self, root: NodeId, class_names: &str) -> Vec<NodeId> {
+        // Let classes be the result of running the ordered set parser on
+        // classNames.
+        let classes: Vec<&str> = class_names.split_ascii_whitespace().collect();
+
+        // If classes is the empty set, return an empty HTMLCollection.
+        if classes.is_empty() {
+            return vec![];
+        }
+
+        // Return a HTMLCollection rooted at root, whose filter matches
+        // descendant elements that have all their classes in classes.
+        //
+        // The comparisons for the classes must be done in an ASCII
+        // case-insensitive manner if root's node document's mode is "quirks";
+        // otherwise in an identical to manner.
+        let document = self.get_node(root).node_document();
+        let quirks = self.get_node(document).document_mode() == Some(DocumentMode::Quirks);
+
+        self.filter_descendants(root, |node| {
+            node.is_element()
+                && classes.iter().all(|class| {
+                    node.classes().any(|other| {
+                        if quirks {
+                            other.eq_ignore_ascii_case(class)
+                        } else {
+                            other == *class
+                        }
+                    })
+                })
+        })
+        .collect()
+    }
+
+    /// Returns the first descendant element of `root` in tree order that has
+    /// an attribute `name` with exactly the value `value`. See
+    /// [NodeArena::find_all_by_attribute].
+    pub fn find_by_attribute(&self, root: NodeId, name: &str, value: &str) -> Option<NodeId> {
+        self.find_all_by_attribute(root, name, value).next()
+    }
+
+    /// Returns the descendant elements of `root` that have an attribute
+    /// `name` with exactly the value `value`, like the `[name="value"]`
+    /// attribute selector. The attribute name is matched ASCII
+    /// case-insensitively, and the value case-sensitively.
+    pub fn find_all_by_attribute<'a>(
+        &'a self,
+        root: NodeId,
+        name: &'a str,
+        value: &'a str,
+    ) -> impl Iterator<Item = NodeId> + 'a {
+        self.filter_descendants(root, move |node| {
+            node.attributes().iter().any(|attribute| {
+                attribute.qualified_name().eq_ignore_ascii_case(name) && attribute.value == value
+            })
+        })
+    }
+}
+
+/// # Element Traversal
+///
+/// Navigation that skips text, comment and other non-element nodes.
+///
+/// https://dom.spec.whatwg.org/#interface-parentnode
+impl NodeArena {
+    /// Returns the first child of `node` that is an element.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-parentnode-firstelementchild
+    pub fn first_element_child(&self, node: NodeId) -> Option<NodeId> {
+        self.get_node(node)
+            .children()
+            .iter()
+            .copied()
+            .find(|child| self.get_node(*child).is_element())
+    }
+
+    /// Returns the last child of `node` that is an element.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-parentnode-lastelementchild
+    pub fn last_element_child(&self, node: NodeId) -> Option<NodeId> {
+        self.get_node(node)
+            .children()
+            .iter()
+            .copied()
+            .rfind(|child| self.get_node(*child).is_element())
+    }
+
+    /// Returns the first following sibling of `node` that is an element.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-nondocumenttypechildnode-nextelementsibling
+    pub fn next_element_sibling(&self, node: NodeId) -> Option<NodeId> {
+        std::iter::successors(self.next_sibling(node), |sibling| {
+            self.next_sibling(*sibling)
+        })
+        .find(|sibling| self.get_node(*sibling).is_element())
+    }
+
+    /// Returns the first preceding sibling of `node` that is an element.
+    ///
+    /// https://dom.spec.whatwg.org/#dom-nondocumenttypechildnode-previouselementsibling
+    pub fn previous_element_sibling(&self, node: NodeId) -> Option<NodeId> {
+        std::iter::successors(self.previous_sibling(node), |sibling| {
+            self.previous_sibling(*sibling)
+        })
+        .find(|sibling| self.get_node(*sibling).is_element())
+    }
+}
+
+/// # Scraping
+impl NodeArena {
+    /// Returns the `href` attribute of every `a` element under `root` that
+    /// has one, in tree order.
+    pub fn links(&self, root: NodeId) -> Vec<&str> {
+        self.attribute_values_by_tag(root, "a", "href")
+    }
+
+    /// Returns the `src` attribute of every `img` element under `root` that
+    /// has one, in tree order.
+    pub fn images(&self, root: NodeId) -> Vec<&str> {
+        self.attribute_values_by_tag(root, "img", "src")
+    }
+
+    fn attribute_values_by_tag(&self, root: NodeId, tag: &str, attribute: &str) -> Vec<&str> {
+        self.elements_by_tag(root, tag)
+            .filter_map(|element| self.get_node(element).get_attribute(attribute))
+            .collect()
+    }
+}
+
+/// Counts of the nodes in a subtree, returned by [NodeArena::stats].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    pub elements: usize,
+    pub text_nodes: usize,
+    pub comments: usize,
+    /// The depth of the deepest node, relative to the root of the subtree,
+    /// which has depth 0.
+    pub max_depth: usize,
+}
+
+#[derive(Default)]
+struct StatsVisitor {
+    stats: ParseStats,
+    depth: usize,
+}
+
+impl Visitor for StatsVisitor {
+    fn enter(&mut self, arena: &NodeArena, node: NodeId) {
+        match arena.get_node(node).kind {
+            NodeKind::Element { .. } => self.stats.elements += 1,
+            NodeKind::Text { .. } => self.stats.text_nodes += 1,
+            NodeKind::Comment { .. } => self.stats.comments += 1,
+            NodeKind::Document { .. } | NodeKind::DocumentType { .. } => {}
+        }
+
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+        self.depth += 1;
+    }
+
+    fn leave(&mut self, _arena: &NodeArena, _node: NodeId) {
+        self.depth -= 1;
+    }
+}
+
+/// # Statistics
+impl NodeArena {
+    /// Counts the elements, text nodes and comments in `root` and its
+    /// descendants, and finds the depth of the deepest one, in a single
+    /// traversal.
+    pub fn stats(&self, root: NodeId) -> ParseStats {
+        let mut visitor = StatsVisitor::default();
+        self.visit(root, &mut visitor);
+        visitor.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse;
+    use crate::Dom;
+
+    #[test]
+    fn visit_pairs_enter_and_leave() {
+        #[derive(Default)]
+        struct Events {
+            events: Vec<String>,
+            depth: usize,
+            max_depth: usize,
+        }
+
+        impl Visitor for Events {
+            fn enter(&mut self, arena: &NodeArena, node: NodeId) {
+                self.events
+                    .push(format!("+{}", arena.get_node(node).describe()));
+                self.depth += 1;
+                self.max_depth = self.max_depth.max(self.depth);
+            }
+
+            fn leave(&mut self, arena: &NodeArena, node: NodeId) {
+                self.events
+                    .push(format!("-{}", arena.get_node(node).describe()));
+                self.depth -= 1;
+            }
+        }
+
+        let (dom, div) = parse("<div><p>a<b>b</b></p><hr></div>", "div");
+        let mut events = Events::default();
+        dom.arena().visit(div, &mut events);
+        assert_eq!(
+            events.events,
+            [
+                "+div", "+p", "+#text", "-#text", "+b", "+#text", "-#text", "-b", "-p", "+hr",
+                "-hr", "-div"
+            ]
+        );
+        assert_eq!(events.depth, 0);
+        assert_eq!(events.max_depth, 4);
+    }
+
+    #[test]
+    fn filtering_descendants_stops_early() {
+        let html = format!("<p>first</p><p>second</p>{}", "<div>x</div>".repeat(1000));
+        let dom = Dom::parse_owned(&html);
+        let arena = dom.arena();
+
+        let visited = std::cell::Cell::new(0);
+        let paragraphs: Vec<NodeId> = arena
+            .filter_descendants(dom.document(), |node| {
+                visited.set(visited.get() + 1);
+                node.is_element_with_tag_name("p")
+            })
+            .take(2)
+            .collect();
+        assert_eq!(paragraphs.len(), 2);
+        // html, head, body, p, #text and p.
+        assert_eq!(visited.get(), 6);
+
+        let first = arena.elements_by_tag(dom.document(), "P").next().unwrap();
+        assert_eq!(first, paragraphs[0]);
+        assert_eq!(arena.elements_by_tag(dom.document(), "div").count(), 1000);
+    }
+
+    #[test]
+    fn collects_links_and_images() {
+        let dom = Dom::parse_owned(
+            "<a href=/one>1</a><a name=anchor>2</a><p><a href=\"/three\">3</a>\
+             <img src=a.png><img alt=missing></p>",
+        );
+        assert_eq!(dom.arena().links(dom.document()), ["/one", "/three"]);
+        assert_eq!(dom.arena().images(dom.document()), ["a.png"]);
+    }
+
+    #[test]
+    fn filters_descendants_with_a_predicate() {
+        let (dom, ul) = parse(
+            "<ul><li>a<ol><li>b</li></ol></li><!--c--><li>d</li></ul>",
+            "ul",
+        );
+        let arena = dom.arena();
+
+        let items: Vec<String> = arena
+            .filter_descendants(ul, |node| node.is_element_with_tag_name("li"))
+            .map(|li| arena.text_content(li))
+            .collect();
+        assert_eq!(items, ["ab", "b", "d"]);
+
+        let comments =
+            arena.filter_descendants(ul, |node| matches!(node.kind, NodeKind::Comment { .. }));
+        assert_eq!(comments.count(), 1);
+    }
+
+    #[test]
+    fn finds_elements_by_attribute() {
+        let (dom, form) = parse(
+            "<form><input NAME=user><input name=csrf value=1><input name=Csrf>\
+             <input name=csrf value=2></form>",
+            "form",
+        );
+        let arena = dom.arena();
+        let value = |input: NodeId| arena.get_node(input).get_attribute("value");
+
+        let csrf = arena.find_by_attribute(form, "name", "csrf").unwrap();
+        assert_eq!(value(csrf), Some("1"));
+        // Only the attribute name is case-insensitive.
+        assert!(arena.find_by_attribute(form, "name", "user").is_some());
+        assert!(arena.find_by_attribute(form, "name", "USER").is_none());
+        assert!(arena.find_by_attribute(form, "name", "missing").is_none());
+
+        let all: Vec<_> = arena
+            .find_all_by_attribute(form, "name", "csrf")
+            .map(value)
+            .collect();
+        assert_eq!(all, [Some("1"), Some("2")]);
+    }
+
+    #[test]
+    fn stats_count_nodes_and_depth() {
+        let dom = Dom::parse_owned("<!DOCTYPE html><!-- c --><ul><li>a<li><b>b</b></ul><br>");
+        let arena = dom.arena();
+        assert_eq!(
+            arena.stats(dom.document()),
+            ParseStats {
+                // html, head, body, ul, li, li, b and br.
+                elements: 8,
+                text_nodes: 2,
+                comments: 1,
+                // document > html > body > ul > li > b > "b"
+                max_depth: 6,
+            }
+        );
+
+        let (dom, ul) = parse("<ul><li>a</ul>", "ul");
+        assert_eq!(
+            dom.arena().stats(ul),
+            ParseStats {
+                elements: 2,
+                text_nodes: 1,
+                comments: 0,
+                max_depth: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn gets_elements_by_class_name() {
+        let paragraphs =
+            "<p class='a b'>1</p><p class=a>2</p><p class='ab b'>3</p><p class=A>4</p>";
+        let texts = |html: &str, class_names| {
+            let dom = Dom::parse_owned(html);
+            let arena = dom.arena();
+            arena
+                .get_elements_by_class_name(dom.document(), class_names)
+                .into_iter()
+                .map(|element| arena.text_content(element))
+                .collect::<Vec<_>>()
+        };
+
+        let no_quirks = format!("<!DOCTYPE html>{paragraphs}");
+        assert_eq!(texts(&no_quirks, "a"), ["1", "2"]);
+        assert_eq!(texts(&no_quirks, "b"), ["1", "3"]);
+        assert_eq!(texts(&no_quirks, " b  a "), ["1"]);
+        assert_eq!(texts(&no_quirks, "A"), ["4"]);
+        assert!(texts(&no_quirks, "").is_empty());
+
+        // Without a DOCTYPE, the document is in quirks mode.
+        assert_eq!(texts(paragraphs, "a"), ["1", "2", "4"]);
+    }
+
+    #[test]
+    fn element_children_and_siblings_skip_other_nodes() {
+        let (dom, div) = parse(
+            "<div> text <span></span> text <!-- c --><b></b> </div>",
+            "div",
+        );
+        let arena = dom.arena();
+        let span = arena.first_element_child(div).unwrap();
+        let b = arena.last_element_child(div).unwrap();
+        assert!(arena.get_node(span).is_element_with_tag_name("span"));
+        assert!(arena.get_node(b).is_element_with_tag_name("b"));
+
+        assert_eq!(arena.next_element_sibling(span), Some(b));
+        assert_eq!(arena.previous_element_sibling(b), Some(span));
+        assert_eq!(arena.previous_element_sibling(span), None);
+        assert_eq!(arena.next_element_sibling(b), None);
+        assert_eq!(arena.first_element_child(span), None);
+    }
+}